@@ -1,4 +1,3 @@
-add_log("run file init.lua", "The logging process has started up")
 add_log_type({
     name = "test",
     attrs = {
@@ -18,12 +17,14 @@ function add_log_interactive(name)
             return
         end
     end
+    typ = name
     name, _err = readline("Name: ")
     if not name then return end
     desc, _err = readline("Description: ")
     if not desc then return end
     props = {}
     if log_type then
+        props["type"] = typ
         for attr, attr_prop in pairs(log_type) do
             if attr_prop.hidden then
                 default = attr_prop.default
@@ -42,4 +43,17 @@ function add_log_interactive(name)
     add_log_with_props(name, desc, props)
 end
 
-repl()
+on("finish", function(event)
+    add_log("finished " .. event.kind .. " " .. event.name, "")
+end)
+
+on("overdue", function(event)
+    print("Warning: " .. event.kind .. " " .. event.name .. " is overdue")
+    add_log("overdue " .. event.kind .. " " .. event.name, "")
+end)
+
+-- `mode` is set to the subcommand when there is one, like `sched add` or `sched daemon`
+if mode == nil then
+    add_log("run file init.lua", "The logging process has started up")
+    repl()
+end
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE events;
+DROP TABLE intervals;
+DROP TABLE event_attrs;
@@ -0,0 +1,23 @@
+-- Your SQL goes here
+CREATE TABLE events (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    kind VARCHAR NOT NULL,
+    name VARCHAR NOT NULL DEFAULT "",
+    priority INTEGER NOT NULL,
+    finished BOOLEAN NOT NULL DEFAULT 0,
+    parent INTEGER,
+    created DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+CREATE TABLE intervals (
+    interval_id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    id INTEGER NOT NULL,
+    start DATETIME NOT NULL,
+    length INTEGER NOT NULL
+);
+CREATE TABLE event_attrs (
+    attr_id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    id INTEGER NOT NULL,
+    key VARCHAR NOT NULL,
+    val VARCHAR NOT NULL DEFAULT "",
+    UNIQUE(id, key) ON CONFLICT REPLACE
+);
@@ -0,0 +1,13 @@
+-- This file should undo anything in `up.sql`
+UPDATE events SET priority = CASE priority
+    WHEN 9 THEN 0
+    WHEN 8 THEN 1
+    WHEN 6 THEN 2
+    WHEN 7 THEN 3
+    WHEN 5 THEN 4
+    WHEN 3 THEN 5
+    WHEN 4 THEN 6
+    WHEN 2 THEN 7
+    WHEN 1 THEN 8
+    ELSE 4
+END;
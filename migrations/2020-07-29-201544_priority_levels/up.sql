@@ -0,0 +1,16 @@
+-- Your SQL goes here
+-- Priorities used to be stored as their position from `VeryHigh` (0) down to `VeryLow` (8), with
+-- `NotSoHigh` above `MediumHigh` and `MediumLow` above `NotSoLow`. They are now stored as their
+-- level, from `VeryLow` (1) up to `VeryHigh` (9).
+UPDATE events SET priority = CASE priority
+    WHEN 0 THEN 9
+    WHEN 1 THEN 8
+    WHEN 2 THEN 6
+    WHEN 3 THEN 7
+    WHEN 4 THEN 5
+    WHEN 5 THEN 3
+    WHEN 6 THEN 4
+    WHEN 7 THEN 2
+    WHEN 8 THEN 1
+    ELSE 5
+END;
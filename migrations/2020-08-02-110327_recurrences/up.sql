@@ -0,0 +1,13 @@
+-- Your SQL goes here
+CREATE TABLE recurrences (
+    id INTEGER PRIMARY KEY NOT NULL,
+    rule VARCHAR NOT NULL
+);
+CREATE TABLE recurrence_exceptions (
+    exception_id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    id INTEGER NOT NULL,
+    original DATETIME NOT NULL,
+    start DATETIME,
+    length INTEGER,
+    UNIQUE(id, original) ON CONFLICT REPLACE
+);
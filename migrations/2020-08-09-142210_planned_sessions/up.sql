@@ -0,0 +1,8 @@
+-- Your SQL goes here
+CREATE TABLE planned_sessions (
+    session_id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    id INTEGER NOT NULL,
+    start DATETIME NOT NULL,
+    length INTEGER NOT NULL,
+    reason TEXT NOT NULL DEFAULT ""
+);
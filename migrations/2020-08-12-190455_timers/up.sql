@@ -0,0 +1,5 @@
+-- Your SQL goes here
+CREATE TABLE timers (
+    id INTEGER PRIMARY KEY NOT NULL,
+    start DATETIME NOT NULL
+);
@@ -0,0 +1,5 @@
+-- Your SQL goes here
+CREATE TABLE estimates (
+    id INTEGER PRIMARY KEY NOT NULL,
+    length INTEGER NOT NULL
+);
@@ -0,0 +1,8 @@
+-- Your SQL goes here
+CREATE TABLE dependencies (
+    dependency_id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    id INTEGER NOT NULL,
+    depends_on INTEGER NOT NULL,
+    lag INTEGER NOT NULL DEFAULT 0,
+    UNIQUE(id, depends_on) ON CONFLICT REPLACE
+);
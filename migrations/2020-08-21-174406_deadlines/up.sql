@@ -0,0 +1,5 @@
+-- Your SQL goes here
+CREATE TABLE deadlines (
+    id INTEGER PRIMARY KEY NOT NULL,
+    due DATETIME NOT NULL
+);
@@ -0,0 +1,5 @@
+-- Your SQL goes here
+CREATE TABLE uids (
+    id INTEGER PRIMARY KEY NOT NULL,
+    uid VARCHAR NOT NULL UNIQUE
+);
@@ -0,0 +1,7 @@
+-- Your SQL goes here
+CREATE TABLE sync_items (
+    id INTEGER PRIMARY KEY NOT NULL,
+    href VARCHAR NOT NULL UNIQUE,
+    etag VARCHAR,
+    data TEXT NOT NULL
+);
@@ -0,0 +1,5 @@
+-- Your SQL goes here
+CREATE TABLE transition_checks (
+    id INTEGER PRIMARY KEY NOT NULL,
+    time DATETIME NOT NULL
+);
@@ -0,0 +1,13 @@
+-- Your SQL goes here
+CREATE TABLE focus_sessions (
+    id INTEGER PRIMARY KEY NOT NULL,
+    work INTEGER NOT NULL,
+    short_break INTEGER NOT NULL,
+    long_break INTEGER NOT NULL,
+    long_break_every INTEGER NOT NULL,
+    max_idle_blocks INTEGER,
+    phase TEXT NOT NULL,
+    phase_start DATETIME NOT NULL,
+    blocks INTEGER NOT NULL,
+    interruptions INTEGER NOT NULL
+);
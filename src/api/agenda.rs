@@ -0,0 +1,416 @@
+//! The agenda: what a day (or a week) holds, from the stored events, the planned task sessions,
+//! the deadlines and the habits. Which sections show up, in what order, and what gets left out
+//! is set by the agenda config, which scripts can change.
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{DateTime, Duration, Local, NaiveDate, TimeZone};
+
+use super::{
+    deadline::get_overdue,
+    error::*,
+    event::*,
+    habit::{habits_today, HabitPeriod, HabitStatus},
+    schedule::get_plan,
+    state::API_STATE,
+    time,
+};
+
+/// The most days an agenda covers
+pub const MAX_DAYS: i64 = 366;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AgendaSection {
+    /// Unfinished tasks and projects past their deadline
+    Overdue,
+    /// The occurrences of events, by day
+    Events,
+    /// The planned task sessions, by day
+    Sessions,
+    /// Tasks and projects due before the end of the agenda, or soon after
+    Due,
+    Habits,
+}
+
+impl AgendaSection {
+    pub fn name(&self) -> &'static str {
+        match self {
+            AgendaSection::Overdue => "overdue",
+            AgendaSection::Events => "events",
+            AgendaSection::Sessions => "sessions",
+            AgendaSection::Due => "due",
+            AgendaSection::Habits => "habits",
+        }
+    }
+
+    /// Whether the section is split by day
+    pub fn is_daily(&self) -> bool {
+        matches!(self, AgendaSection::Events | AgendaSection::Sessions)
+    }
+
+    fn title(&self) -> &'static str {
+        match self {
+            AgendaSection::Overdue => "Overdue",
+            AgendaSection::Events => "Events",
+            AgendaSection::Sessions => "Sessions",
+            AgendaSection::Due => "Due soon",
+            AgendaSection::Habits => "Habits",
+        }
+    }
+}
+
+impl fmt::Display for AgendaSection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for AgendaSection {
+    type Err = ErrorKind;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "overdue" => Ok(AgendaSection::Overdue),
+            "events" => Ok(AgendaSection::Events),
+            "sessions" => Ok(AgendaSection::Sessions),
+            "due" => Ok(AgendaSection::Due),
+            "habits" => Ok(AgendaSection::Habits),
+            _ => Err(ErrorKind::InvalidField {
+                field: "sections".into(),
+                message: format!(
+                    "'{}' is not one of 'overdue', 'events', 'sessions', 'due' or 'habits'",
+                    s
+                ),
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AgendaConfig {
+    /// The sections to show, in order. The daily ones are shown together under each day, at the
+    /// place of the first of them.
+    pub sections: Vec<AgendaSection>,
+    /// How long after the end of the agenda deadlines still count as due soon
+    pub due_within: Duration,
+    /// Events, tasks and projects less important than this are left out
+    pub min_priority: Priority,
+}
+
+impl Default for AgendaConfig {
+    fn default() -> Self {
+        Self {
+            sections: vec![
+                AgendaSection::Overdue,
+                AgendaSection::Events,
+                AgendaSection::Sessions,
+                AgendaSection::Due,
+                AgendaSection::Habits,
+            ],
+            due_within: Duration::days(3),
+            min_priority: Priority::VeryLow,
+        }
+    }
+}
+
+/// An event occurrence, a planned session, or a task or project with a deadline
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AgendaItem {
+    pub section: AgendaSection,
+    pub id: i32,
+    pub kind: &'static str,
+    pub name: String,
+    pub priority: Priority,
+    /// When it happens, for events and sessions
+    pub interval: Option<Interval>,
+    /// When it is due, for tasks and projects
+    pub deadline: Option<DateTime<Local>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AgendaDay {
+    pub date: NaiveDate,
+    /// In time order
+    pub events: Vec<AgendaItem>,
+    /// In time order
+    pub sessions: Vec<AgendaItem>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Agenda {
+    pub range: Interval,
+    pub days: Vec<AgendaDay>,
+    /// The longest overdue first
+    pub overdue: Vec<AgendaItem>,
+    /// The earliest due first
+    pub due: Vec<AgendaItem>,
+    pub habits: Vec<HabitStatus>,
+}
+
+impl Agenda {
+    /// Keeps the items `keep` is true for
+    pub fn retain<F: FnMut(&AgendaItem) -> bool>(&mut self, mut keep: F) {
+        for day in self.days.iter_mut() {
+            day.events.retain(&mut keep);
+            day.sessions.retain(&mut keep);
+        }
+        self.overdue.retain(&mut keep);
+        self.due.retain(&mut keep);
+    }
+}
+
+pub fn get_agenda_config() -> AgendaConfig {
+    API_STATE.with(|s| s.lock().unwrap().agenda.clone())
+}
+
+pub fn set_agenda_config(config: AgendaConfig) {
+    API_STATE.with(|s| s.lock().unwrap().agenda = config);
+}
+
+/// The `days` days starting with `first`, from one day up to `MAX_DAYS`
+pub fn agenda_range(first: NaiveDate, days: i64) -> Interval {
+    let days = Duration::days(days.clamp(1, MAX_DAYS));
+    let start = time::start_of_day(first)
+        .unwrap_or_else(|| Local.from_utc_datetime(&first.and_hms(0, 0, 0)));
+    let end = time::start_of_day(first + days).unwrap_or(start + days);
+    Interval::builder().start(start).end(end).build().unwrap()
+}
+
+fn deadline_item(section: AgendaSection, item: &EventType) -> AgendaItem {
+    let deadline = match item {
+        EventType::Task(t) => t.deadline(),
+        EventType::Project(p) => p.deadline(),
+        EventType::Event(_) => None,
+    };
+    AgendaItem {
+        section,
+        id: item.common().id().unwrap(),
+        kind: item.kind(),
+        name: item.common().name().to_string(),
+        priority: item.common().priority(),
+        interval: None,
+        deadline,
+    }
+}
+
+/// The agenda of every day in `range`, with what is overdue or due at `now` according to
+/// `config`. Finished items are left out, and so are the sections `config` doesn't have.
+pub fn agenda(range: &Interval, now: DateTime<Local>, config: &AgendaConfig) -> Agenda {
+    let wanted = |section| config.sections.contains(&section);
+    let important = |common: &EventCommon| common.priority() >= config.min_priority;
+
+    let mut days = Vec::new();
+    let mut date = range.start().naive_local().date();
+    let last = (range.end() - Duration::nanoseconds(1))
+        .naive_local()
+        .date();
+    while date <= last {
+        days.push(AgendaDay {
+            date,
+            events: Vec::new(),
+            sessions: Vec::new(),
+        });
+        date += Duration::days(1);
+    }
+    // Things that started before the range are shown on its first day
+    let day_of = |t: DateTime<Local>| {
+        let date = t.max(range.start()).naive_local().date();
+        days.iter().position(|d| d.date == date)
+    };
+
+    let mut events = Vec::new();
+    if wanted(AgendaSection::Events) {
+        for event in get_events() {
+            let common = event.common();
+            if common.finished() || !important(common) {
+                continue;
+            }
+            for occurrence in event.occurrences(range) {
+                events.push(AgendaItem {
+                    section: AgendaSection::Events,
+                    id: common.id().unwrap(),
+                    kind: "event",
+                    name: common.name().to_string(),
+                    priority: common.priority(),
+                    interval: Some(occurrence),
+                    deadline: None,
+                });
+            }
+        }
+    }
+    let mut sessions = Vec::new();
+    if wanted(AgendaSection::Sessions) {
+        for placement in get_plan() {
+            if !placement.interval.overlaps(range) {
+                continue;
+            }
+            let task = match get_event(placement.task) {
+                Some(task) => task,
+                None => continue,
+            };
+            if task.common().finished() || !important(task.common()) {
+                continue;
+            }
+            sessions.push(AgendaItem {
+                section: AgendaSection::Sessions,
+                id: placement.task,
+                kind: task.kind(),
+                name: task.common().name().to_string(),
+                priority: task.common().priority(),
+                interval: Some(placement.interval),
+                deadline: None,
+            });
+        }
+    }
+    let mut by_day: Vec<(Vec<AgendaItem>, Vec<AgendaItem>)> =
+        days.iter().map(|_| (Vec::new(), Vec::new())).collect();
+    for item in events {
+        if let Some(i) = day_of(item.interval.unwrap().start()) {
+            by_day[i].0.push(item);
+        }
+    }
+    for item in sessions {
+        if let Some(i) = day_of(item.interval.unwrap().start()) {
+            by_day[i].1.push(item);
+        }
+    }
+    for (day, (mut events, mut sessions)) in days.iter_mut().zip(by_day) {
+        events.sort_by_key(|e| e.interval.unwrap().start());
+        sessions.sort_by_key(|s| s.interval.unwrap().start());
+        day.events = events;
+        day.sessions = sessions;
+    }
+
+    let overdue = if wanted(AgendaSection::Overdue) {
+        get_overdue(now)
+            .iter()
+            .filter(|e| important(e.common()))
+            .map(|e| deadline_item(AgendaSection::Overdue, e))
+            .collect()
+    } else {
+        Vec::new()
+    };
+    let due = if wanted(AgendaSection::Due) {
+        // `None` when past the range of dates, so that everything is due, or nothing is if
+        // `due_within` is that far back
+        let until = range.end().checked_add_signed(config.due_within);
+        let due_by = |d| until.map_or(config.due_within > Duration::zero(), |u| d <= u);
+        let mut due: Vec<AgendaItem> = get_tasks()
+            .into_iter()
+            .map(EventType::Task)
+            .chain(get_projects().into_iter().map(EventType::Project))
+            .filter(|e| !e.common().finished() && important(e.common()))
+            .map(|e| deadline_item(AgendaSection::Due, &e))
+            .filter(|i| matches!(i.deadline, Some(d) if now <= d && due_by(d)))
+            .collect();
+        due.sort_by_key(|i| i.deadline);
+        due
+    } else {
+        Vec::new()
+    };
+    let habits = if wanted(AgendaSection::Habits) {
+        habits_today()
+    } else {
+        Vec::new()
+    };
+
+    Agenda {
+        range: *range,
+        days,
+        overdue,
+        due,
+        habits,
+    }
+}
+
+fn format_interval(interval: &Interval, date: NaiveDate) -> String {
+    let time = |t: DateTime<Local>| {
+        if t.naive_local().date() == date {
+            t.format("%H:%M").to_string()
+        } else {
+            t.format("%m-%d %H:%M").to_string()
+        }
+    };
+    format!("{}-{}", time(interval.start()), time(interval.end()))
+}
+
+fn format_deadline(item: &AgendaItem) -> String {
+    let deadline = item
+        .deadline
+        .map_or_else(String::new, |d| d.format("%Y-%m-%d %H:%M").to_string());
+    format!("{:<16}  {} {}", deadline, item.kind, item.name)
+}
+
+fn format_habit(status: &HabitStatus) -> String {
+    let period = match status.habit.per {
+        HabitPeriod::Day => "today",
+        HabitPeriod::Week => "this week",
+    };
+    let mut line = format!(
+        "{}  {}/{} {}  streak {} (best {})",
+        status.habit.name,
+        status.done,
+        status.habit.times,
+        period,
+        status.current_streak,
+        status.best_streak
+    );
+    if let Some(missed) = status.missed.last() {
+        line.push_str(&format!("  last missed {}", missed.format("%Y-%m-%d")));
+    }
+    line
+}
+
+/// `agenda` as text, with its sections in the order of `config`. The daily sections are listed
+/// under a heading for each day, and sections with nothing in them are left out.
+pub fn render_agenda(agenda: &Agenda, config: &AgendaConfig) -> String {
+    let mut out = String::new();
+    let mut days_shown = false;
+    for section in config.sections.iter() {
+        if section.is_daily() {
+            if days_shown {
+                continue;
+            }
+            days_shown = true;
+            for day in agenda.days.iter() {
+                out.push_str(&format!("{}\n", day.date.format("%A %-d %B %Y")));
+                let mut empty = true;
+                for daily in config.sections.iter().filter(|s| s.is_daily()) {
+                    let items = match daily {
+                        AgendaSection::Events => &day.events,
+                        _ => &day.sessions,
+                    };
+                    if items.is_empty() {
+                        continue;
+                    }
+                    empty = false;
+                    out.push_str(&format!("  {}\n", daily.title()));
+                    for item in items {
+                        out.push_str(&format!(
+                            "    {}  {}\n",
+                            format_interval(&item.interval.unwrap(), day.date),
+                            item.name
+                        ));
+                    }
+                }
+                if empty {
+                    out.push_str("  Nothing planned\n");
+                }
+            }
+            continue;
+        }
+        let lines: Vec<String> = match section {
+            AgendaSection::Overdue => agenda.overdue.iter().map(format_deadline).collect(),
+            AgendaSection::Due => agenda.due.iter().map(format_deadline).collect(),
+            _ => agenda.habits.iter().map(format_habit).collect(),
+        };
+        if lines.is_empty() {
+            continue;
+        }
+        out.push_str(&format!("{}\n", section.title()));
+        for line in lines {
+            out.push_str(&format!("  {}\n", line));
+        }
+    }
+    out
+}
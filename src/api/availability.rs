@@ -0,0 +1,181 @@
+//! When the user is available: weekly working hours, holidays and ad-hoc blocked ranges, grouped
+//! into named calendars (say "work" and "personal"). Calendars are defined at startup, usually by
+//! `init.lua`, like log types are.
+use std::collections::HashMap;
+
+use chrono::{Datelike, Duration, Local, NaiveDate, NaiveTime, TimeZone, Weekday};
+
+use super::{error::*, event::*, state::API_STATE};
+
+/// The calendar used when none is named
+pub const DEFAULT_CALENDAR: &str = "default";
+
+/// The hours of the week during which one is available
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WorkingHours {
+    pub days: Vec<Weekday>,
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+impl Default for WorkingHours {
+    /// 9:00 to 17:00, Monday to Friday
+    fn default() -> Self {
+        Self {
+            days: vec![
+                Weekday::Mon,
+                Weekday::Tue,
+                Weekday::Wed,
+                Weekday::Thu,
+                Weekday::Fri,
+            ],
+            start: NaiveTime::from_hms(9, 0, 0),
+            end: NaiveTime::from_hms(17, 0, 0),
+        }
+    }
+}
+
+impl WorkingHours {
+    /// The working hours within `window`, in order
+    pub fn intervals_in(&self, window: &Interval) -> Vec<Interval> {
+        let mut result = Vec::new();
+        let mut date = window.start().naive_local().date();
+        while date <= window.end().naive_local().date() {
+            if self.days.contains(&date.weekday()) {
+                let start = Local
+                    .from_local_datetime(&date.and_time(self.start))
+                    .earliest();
+                let end = Local
+                    .from_local_datetime(&date.and_time(self.end))
+                    .earliest();
+                if let (Some(start), Some(end)) = (start, end) {
+                    let start = start.max(window.start());
+                    let end = end.min(window.end());
+                    if start < end {
+                        result.push(Interval::from_start(start, end - start));
+                    }
+                }
+            }
+            date = date.succ();
+        }
+        result
+    }
+}
+
+/// When one is available: during the working hours, except on holidays and in blocked ranges
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Availability {
+    pub working_hours: WorkingHours,
+    pub holidays: Vec<NaiveDate>,
+    pub blocked: Vec<Interval>,
+}
+
+impl Availability {
+    /// The available time within `window`, in order
+    pub fn available_in(&self, window: &Interval) -> Vec<Interval> {
+        let holidays: Vec<Interval> = self
+            .holidays
+            .iter()
+            .filter_map(|date| {
+                let start = Local
+                    .from_local_datetime(&date.and_hms(0, 0, 0))
+                    .earliest()?;
+                let end = Local
+                    .from_local_datetime(&date.succ().and_hms(0, 0, 0))
+                    .earliest()?;
+                Some(Interval::from_start(start, end - start))
+            })
+            .collect();
+        let available = subtract(self.working_hours.intervals_in(window), &holidays);
+        subtract(available, &self.blocked)
+    }
+}
+
+/// Removes the `busy` time from the `free` intervals, returning what's left in order
+pub fn subtract(free: Vec<Interval>, busy: &[Interval]) -> Vec<Interval> {
+    let mut free = free;
+    for b in busy {
+        free = free
+            .into_iter()
+            .flat_map(|f| {
+                if !f.overlaps(b) {
+                    return vec![f];
+                }
+                let mut parts = Vec::new();
+                if f.start() < b.start() {
+                    parts.push(Interval::from_start(f.start(), b.start() - f.start()));
+                }
+                if b.end() < f.end() {
+                    parts.push(Interval::from_start(b.end(), f.end() - b.end()));
+                }
+                parts
+            })
+            .collect();
+    }
+    free.retain(|f| f.length() > Duration::zero());
+    free.sort_by_key(Interval::start);
+    free
+}
+
+pub fn set_calendar<S: Into<String>>(name: S, availability: Availability) {
+    API_STATE.with(|s| {
+        s.lock()
+            .unwrap()
+            .calendars
+            .insert(name.into(), availability)
+    });
+}
+
+pub fn get_calendar<S: AsRef<str>>(name: S) -> Option<Availability> {
+    API_STATE.with(|s| s.lock().unwrap().calendars.get(name.as_ref()).cloned())
+}
+
+pub fn get_calendars() -> HashMap<String, Availability> {
+    API_STATE.with(|s| s.lock().unwrap().calendars.clone())
+}
+
+/// The calendar `name`, or the default one if `name` is `None`. Without a calendar named
+/// `DEFAULT_CALENDAR`, the default is 9:00 to 17:00 on weekdays.
+pub fn calendar_or_default(method: &str, name: Option<&str>) -> Result<Availability> {
+    match name {
+        Some(name) => get_calendar(name).ok_or_else(|| Error {
+            method: method.into(),
+            kind: ErrorKind::InvalidCalendar(name.into()),
+        }),
+        None => Ok(get_calendar(DEFAULT_CALENDAR).unwrap_or_default()),
+    }
+}
+
+/// Blocks `range` in the calendar `name`, which starts out as the default calendar if it doesn't
+/// exist yet. This only lasts as long as the calendar, which isn't stored.
+pub fn block<S: AsRef<str>>(name: S, range: Interval) {
+    let name = name.as_ref();
+    let mut calendar = get_calendar(name)
+        .or_else(|| get_calendar(DEFAULT_CALENDAR))
+        .unwrap_or_default();
+    calendar.blocked.push(range);
+    set_calendar(name, calendar);
+}
+
+/// The occurrences of the stored events in `window`
+pub fn busy_in(window: &Interval) -> Vec<Interval> {
+    let mut busy: Vec<Interval> = get_events()
+        .iter()
+        .flat_map(|e| e.occurrences(window))
+        .collect();
+    busy.sort_by_key(Interval::start);
+    busy
+}
+
+/// The free time in `window` of the calendar `calendar` (or the default one) that is at least
+/// `length` long, in order. Free time is available time not taken by any event.
+pub fn free_slots(
+    window: &Interval,
+    length: Duration,
+    calendar: Option<&str>,
+) -> Result<Vec<Interval>> {
+    let available = calendar_or_default("free_slots", calendar)?.available_in(window);
+    let mut free = subtract(available, &busy_in(window));
+    free.retain(|f| f.length() >= length);
+    Ok(free)
+}
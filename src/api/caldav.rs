@@ -0,0 +1,915 @@
+//! Two-way sync of events and tasks with a CalDAV collection. Every item is one iCalendar
+//! resource on the server. Changes on the server are detected through the ETags of the
+//! resources, and local changes by comparing the item with what it was at the last sync, so only
+//! what changed on either side is transferred.
+//!
+//! An item deleted on the server is finished here rather than deleted, and is only uploaded
+//! again if it changes afterwards. An item deleted here is deleted on the server too, unless it
+//! changed there since the last sync. Everything a sync does is logged.
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::str::FromStr;
+
+use super::{error::*, event::*, ical, log::*, state::API_STATE};
+use crate::storage::model::SyncItem;
+
+const PROPFIND_BODY: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:propfind xmlns:d="DAV:"><d:prop><d:getetag/></d:prop></d:propfind>"#;
+
+/// Which side wins when an item changed both here and on the server since the last sync
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictResolution {
+    /// Overwrite the server's version
+    Local,
+    /// Overwrite the local version
+    Remote,
+    /// Leave both alone, logging the conflict, until one side changes again
+    Skip,
+}
+
+impl Default for ConflictResolution {
+    fn default() -> Self {
+        ConflictResolution::Skip
+    }
+}
+
+impl ConflictResolution {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ConflictResolution::Local => "local",
+            ConflictResolution::Remote => "remote",
+            ConflictResolution::Skip => "skip",
+        }
+    }
+}
+
+impl fmt::Display for ConflictResolution {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for ConflictResolution {
+    type Err = ErrorKind;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "local" => Ok(ConflictResolution::Local),
+            "remote" => Ok(ConflictResolution::Remote),
+            "skip" => Ok(ConflictResolution::Skip),
+            _ => Err(ErrorKind::InvalidField {
+                field: "on_conflict".into(),
+                message: format!("'{}' is not one of 'local', 'remote' or 'skip'", s),
+            }),
+        }
+    }
+}
+
+/// Where the collection is, and how to sync with it
+#[derive(Debug, Clone, Default)]
+pub struct CalDavConfig {
+    /// The URL of the calendar collection, e.g. `http://localhost:5232/user/calendar/`
+    pub url: String,
+    pub user: Option<String>,
+    pub password: Option<String>,
+    pub on_conflict: ConflictResolution,
+}
+
+/// What a sync did, by item id. Items that failed to sync are in `errors`, with why.
+#[derive(Debug, Clone, Default)]
+pub struct SyncSummary {
+    pub uploaded: Vec<i32>,
+    pub downloaded: Vec<i32>,
+    /// Items deleted on the server, and finished here
+    pub removed: Vec<i32>,
+    /// Items deleted here, and on the server
+    pub deleted: Vec<i32>,
+    /// Items left alone since they changed on both sides
+    pub conflicts: Vec<i32>,
+    pub errors: Vec<String>,
+}
+
+struct Client<'a> {
+    config: &'a CalDavConfig,
+    /// Scheme, host and port of the collection URL
+    origin: String,
+    /// Path of the collection, ending with `/`
+    path: String,
+}
+
+impl<'a> Client<'a> {
+    fn new(config: &'a CalDavConfig) -> Self {
+        let url = config.url.trim_end_matches('/');
+        let host = url.find("://").map_or(0, |i| i + 3);
+        let (origin, path) = match url[host..].find('/') {
+            Some(i) => url.split_at(host + i),
+            None => (url, ""),
+        };
+        Self {
+            config,
+            origin: origin.to_string(),
+            path: format!("{}/", path),
+        }
+    }
+
+    fn url_of(&self, href: &str) -> String {
+        if href.contains("://") {
+            href.to_string()
+        } else if href.starts_with('/') {
+            format!("{}{}", self.origin, href)
+        } else {
+            format!("{}{}{}", self.origin, self.path, href)
+        }
+    }
+
+    /// Where a new item with the UID `uid` goes in the collection
+    fn href_for(&self, uid: &str) -> String {
+        let name: String = uid
+            .chars()
+            .map(|c| match c {
+                'a'..='z' | 'A'..='Z' | '0'..='9' | '.' | '-' | '_' => c,
+                _ => '-',
+            })
+            .collect();
+        format!("{}{}.ics", self.path, name)
+    }
+
+    fn request(&self, method: &str, href: &str) -> ureq::Request {
+        let mut request = ureq::request(method, &self.url_of(href));
+        if let Some(user) = &self.config.user {
+            request.auth(user, self.config.password.as_deref().unwrap_or(""));
+        }
+        request
+    }
+
+    /// The href and ETag of every resource in the collection
+    fn list(&self) -> std::result::Result<HashMap<String, String>, String> {
+        let response = self
+            .request("PROPFIND", &self.path)
+            .set("Depth", "1")
+            .set("Content-Type", "application/xml; charset=utf-8")
+            .send_string(PROPFIND_BODY);
+        let body = self
+            .check(response, "PROPFIND", &self.path)?
+            .into_string()
+            .map_err(|e| e.to_string())?;
+        Ok(parse_multistatus(&body)
+            .into_iter()
+            .filter(|(href, _)| !href.ends_with('/'))
+            .collect())
+    }
+
+    /// The data of the resource `href`, and its ETag if the server sent it
+    fn get(&self, href: &str) -> std::result::Result<(String, Option<String>), String> {
+        let response = self.check(self.request("GET", href).call(), "GET", href)?;
+        let etag = response.header("ETag").map(str::to_string);
+        let body = response.into_string().map_err(|e| e.to_string())?;
+        Ok((body, etag))
+    }
+
+    /// Writes `data` to `href`, as long as the resource still has the ETag `etag`, or doesn't
+    /// exist yet without one. Returns the new ETag if the server sent it.
+    fn put(
+        &self,
+        href: &str,
+        data: &str,
+        etag: Option<&str>,
+    ) -> std::result::Result<Option<String>, String> {
+        let mut request = self.request("PUT", href);
+        request.set("Content-Type", "text/calendar; charset=utf-8");
+        match etag {
+            Some(etag) => request.set("If-Match", etag),
+            None => request.set("If-None-Match", "*"),
+        };
+        let response = self.check(request.send_string(data), "PUT", href)?;
+        Ok(response.header("ETag").map(str::to_string))
+    }
+
+    /// Deletes `href`, as long as the resource still has the ETag `etag`
+    fn delete(&self, href: &str, etag: &str) -> std::result::Result<(), String> {
+        let mut request = self.request("DELETE", href);
+        request.set("If-Match", etag);
+        self.check(request.call(), "DELETE", href)?;
+        Ok(())
+    }
+
+    /// `response` to the `method` request for `href` if it was successful, and why it wasn't
+    /// otherwise
+    fn check(
+        &self,
+        response: ureq::Response,
+        method: &str,
+        href: &str,
+    ) -> std::result::Result<ureq::Response, String> {
+        if let Some(e) = response.synthetic_error() {
+            return Err(format!("{} {}: {}", method, self.url_of(href), e));
+        }
+        if response.error() {
+            return Err(format!(
+                "{} {}: {} {}",
+                method,
+                self.url_of(href),
+                response.status(),
+                response.status_text()
+            ));
+        }
+        Ok(response)
+    }
+}
+
+fn unescape_xml(s: &str) -> String {
+    s.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// The href and ETag of each `response` in a multistatus document. Only the local names of the
+/// elements are looked at, whatever namespace prefix the server uses.
+fn parse_multistatus(xml: &str) -> Vec<(String, String)> {
+    let local_name = |tag: &str| -> String {
+        let name = tag
+            .trim_start_matches('/')
+            .split(|c: char| c.is_whitespace() || c == '/')
+            .next()
+            .unwrap_or("");
+        name.rsplit(':').next().unwrap_or("").to_lowercase()
+    };
+    let mut items = Vec::new();
+    let (mut href, mut etag) = (None, None);
+    let mut open: Option<String> = None;
+    let mut rest = xml;
+    while let Some(start) = rest.find('<') {
+        let text = rest[..start].trim();
+        if !text.is_empty() {
+            match open.as_deref() {
+                Some("href") => href = Some(unescape_xml(text)),
+                Some("getetag") => etag = Some(unescape_xml(text)),
+                _ => {}
+            }
+        }
+        let end = match rest[start..].find('>') {
+            Some(end) => start + end,
+            None => break,
+        };
+        let tag = &rest[start + 1..end];
+        rest = &rest[end + 1..];
+        if tag.starts_with('?') || tag.starts_with('!') {
+            continue;
+        }
+        if tag.starts_with('/') {
+            if local_name(tag) == "response" {
+                if let (Some(h), Some(e)) = (href.take(), etag.take()) {
+                    items.push((h, e));
+                }
+                href = None;
+                etag = None;
+            }
+            open = None;
+        } else if tag.ends_with('/') {
+            open = None;
+        } else {
+            open = Some(local_name(tag));
+        }
+    }
+    items
+}
+
+/// `data` without what changes on every export
+fn fingerprint(data: &str) -> String {
+    data.split("\r\n")
+        .filter(|line| !line.starts_with("DTSTAMP:"))
+        .collect::<Vec<_>>()
+        .join("\r\n")
+}
+
+fn local_data(id: i32) -> Option<String> {
+    get_event(id).and_then(|e| ical::export_item(&e))
+}
+
+fn log_sync(action: &str, id: i32, href: &str) {
+    let name = get_event(id).map_or_else(String::new, |e| e.common().name().to_string());
+    let mut props = HashMap::new();
+    props.insert("id".to_string(), id.to_string());
+    props.insert("href".to_string(), href.to_string());
+    props.insert("action".to_string(), action.to_string());
+    let name = format!("caldav {} {}", action, name);
+    add_log_with_props(name.trim_end(), "", &props);
+}
+
+fn store(id: i32, href: &str, etag: Option<String>) {
+    let item = SyncItem {
+        id,
+        href: href.to_string(),
+        etag,
+        data: local_data(id).map_or_else(String::new, |d| fingerprint(&d)),
+    };
+    API_STATE.with(|s| s.lock().unwrap().storage.set_sync_item(&item));
+}
+
+struct Sync<'a> {
+    client: Client<'a>,
+    summary: SyncSummary,
+}
+
+impl<'a> Sync<'a> {
+    /// Puts `data` of the item `id` at `href` on the server, over the version with the ETag
+    /// `etag` (or where nothing is yet)
+    fn upload(
+        &mut self,
+        id: i32,
+        href: &str,
+        data: &str,
+        etag: Option<&str>,
+    ) -> std::result::Result<(), String> {
+        let etag = match self.client.put(href, data, etag)? {
+            Some(etag) => Some(etag),
+            None => self.client.get(href)?.1,
+        };
+        store(id, href, etag);
+        self.summary.uploaded.push(id);
+        log_sync("uploaded", id, href);
+        Ok(())
+    }
+
+    /// Imports the resource at `href`, whose ETag was `etag` when listed
+    fn download(&mut self, href: &str, etag: &str) -> std::result::Result<(), String> {
+        let (data, sent) = self.client.get(href)?;
+        let result = ical::import(&data).map_err(|e| e.kind.to_string())?;
+        let id = match result.updated.first().or_else(|| result.created.first()) {
+            Some(&id) => id,
+            None if result.skipped.is_empty() => return Err("no event or task in it".into()),
+            None => return Err(result.skipped.join("; ")),
+        };
+        store(id, href, Some(sent.unwrap_or_else(|| etag.to_string())));
+        self.summary.downloaded.push(id);
+        log_sync("downloaded", id, href);
+        Ok(())
+    }
+
+    /// Finishes the item `id`, since its resource was deleted on the server
+    fn remove(&mut self, id: i32, href: &str) {
+        finish_event(id);
+        store(id, href, None);
+        self.summary.removed.push(id);
+        log_sync("removed", id, href);
+    }
+
+    fn conflict(&mut self, id: i32, href: &str) {
+        self.summary.conflicts.push(id);
+        log_sync("skipped conflicting", id, href);
+    }
+
+    /// Deletes the resource of `item`, whose item was deleted here, `etag` being its ETag on the
+    /// server now. If the resource changed since the last sync, that is a conflict.
+    fn delete(&mut self, item: &SyncItem, etag: Option<&str>) -> std::result::Result<(), String> {
+        let clear = || API_STATE.with(|s| s.lock().unwrap().storage.clear_sync_item(item.id));
+        let etag = match etag {
+            Some(etag) => etag,
+            None => {
+                clear();
+                return Ok(());
+            }
+        };
+        let remote_changed = item.etag.as_deref() != Some(etag);
+        match (remote_changed, self.client.config.on_conflict) {
+            (false, _) | (true, ConflictResolution::Local) => {
+                self.client.delete(&item.href, etag)?;
+                clear();
+                self.summary.deleted.push(item.id);
+                log_sync("deleted", item.id, &item.href);
+                Ok(())
+            }
+            // Brought back as a new item
+            (true, ConflictResolution::Remote) => {
+                clear();
+                self.download(&item.href, etag)
+            }
+            (true, ConflictResolution::Skip) => {
+                self.conflict(item.id, &item.href);
+                Ok(())
+            }
+        }
+    }
+
+    /// Brings an item synced before up to date, `etag` being its ETag on the server now
+    fn sync_item(
+        &mut self,
+        item: &SyncItem,
+        etag: Option<&str>,
+    ) -> std::result::Result<(), String> {
+        let data = match local_data(item.id) {
+            Some(data) => data,
+            None => return self.delete(item, etag),
+        };
+        let local_changed = fingerprint(&data) != item.data;
+        let on_conflict = self.client.config.on_conflict;
+        let etag = match (etag, &item.etag) {
+            (Some(etag), _) => etag,
+            // Deleted on the server since the last sync
+            (None, Some(_)) => {
+                return match (local_changed, on_conflict) {
+                    (true, ConflictResolution::Local) => {
+                        self.upload(item.id, &item.href, &data, None)
+                    }
+                    (true, ConflictResolution::Skip) => {
+                        self.conflict(item.id, &item.href);
+                        Ok(())
+                    }
+                    _ => {
+                        self.remove(item.id, &item.href);
+                        Ok(())
+                    }
+                };
+            }
+            // Deleted on the server before, and only brought back by changing it here
+            (None, None) if local_changed => return self.upload(item.id, &item.href, &data, None),
+            (None, None) => return Ok(()),
+        };
+        let remote_changed = item.etag.as_deref() != Some(etag);
+        let upload = match (local_changed, remote_changed, on_conflict) {
+            (false, false, _) => return Ok(()),
+            (true, false, _) | (true, true, ConflictResolution::Local) => true,
+            (false, true, _) | (true, true, ConflictResolution::Remote) => false,
+            (true, true, ConflictResolution::Skip) => {
+                self.conflict(item.id, &item.href);
+                return Ok(());
+            }
+        };
+        if upload {
+            self.upload(item.id, &item.href, &data, Some(etag))
+        } else {
+            self.download(&item.href, etag)
+        }
+    }
+}
+
+/// Syncs all events and tasks with the collection of `config`. Only failing to list the
+/// collection fails the sync; items that fail are left for the next one.
+pub fn sync(config: &CalDavConfig) -> Result<SyncSummary> {
+    let mut sync = Sync {
+        client: Client::new(config),
+        summary: SyncSummary::default(),
+    };
+    let remote = sync.client.list().map_err(|e| Error {
+        method: "sync_caldav".into(),
+        kind: ErrorKind::SyncFailed(e),
+    })?;
+    let mut items = API_STATE.with(|s| s.lock().unwrap().storage.get_sync_items());
+    items.sort_by_key(|i| i.id);
+    let synced: HashSet<i32> = items.iter().map(|i| i.id).collect();
+    let known: HashSet<String> = items.iter().map(|i| i.href.clone()).collect();
+
+    for item in items.iter() {
+        if let Err(e) = sync.sync_item(item, remote.get(&item.href).map(String::as_str)) {
+            sync.summary.errors.push(format!("item {}: {}", item.id, e));
+        }
+    }
+
+    let local: Vec<i32> = get_events()
+        .iter()
+        .filter_map(|e| e.common().id())
+        .chain(get_tasks().iter().filter_map(|t| t.common().id()))
+        .filter(|id| !synced.contains(id))
+        .collect();
+    for id in local {
+        let uid = ical::uid_of(id);
+        let href = sync.client.href_for(&uid);
+        let data = local_data(id).unwrap();
+        if let Err(e) = sync.upload(id, &href, &data, None) {
+            sync.summary.errors.push(format!("item {}: {}", id, e));
+        }
+    }
+
+    let mut new: Vec<(&String, &String)> = remote
+        .iter()
+        .filter(|(href, _)| !known.contains(*href))
+        .collect();
+    new.sort();
+    for (href, etag) in new {
+        if let Err(e) = sync.download(href, etag) {
+            sync.summary.errors.push(format!("{}: {}", href, e));
+        }
+    }
+
+    let summary = sync.summary;
+    add_log(
+        "caldav sync",
+        format!(
+            "{} uploaded, {} downloaded, {} removed, {} deleted, {} conflicts, {} errors",
+            summary.uploaded.len(),
+            summary.downloaded.len(),
+            summary.removed.len(),
+            summary.deleted.len(),
+            summary.conflicts.len(),
+            summary.errors.len()
+        ),
+    );
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+
+    use chrono::{Duration, Local, TimeZone};
+
+    type Resources = Arc<Mutex<HashMap<String, (String, String)>>>;
+
+    /// A collection on a local server, keeping each resource as its ETag and data
+    struct Server {
+        url: String,
+        resources: Resources,
+    }
+
+    impl Server {
+        fn start() -> Self {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let url = format!("http://{}/cal/", listener.local_addr().unwrap());
+            let resources = Resources::default();
+            let served = resources.clone();
+            thread::spawn(move || {
+                let mut next_etag = 0;
+                for stream in listener.incoming() {
+                    respond(stream.unwrap(), &served, &mut next_etag);
+                }
+            });
+            Self { url, resources }
+        }
+
+        fn etag(&self, href: &str) -> Option<String> {
+            let resources = self.resources.lock().unwrap();
+            resources.get(href).map(|(etag, _)| etag.clone())
+        }
+
+        fn data(&self, href: &str) -> Option<String> {
+            let resources = self.resources.lock().unwrap();
+            resources.get(href).map(|(_, data)| data.clone())
+        }
+
+        /// Changes the resource `href` as another client would
+        fn put(&self, href: &str, data: String, etag: &str) {
+            let mut resources = self.resources.lock().unwrap();
+            resources.insert(href.to_string(), (etag.to_string(), data));
+        }
+
+        fn delete(&self, href: &str) {
+            self.resources.lock().unwrap().remove(href);
+        }
+    }
+
+    fn respond(stream: TcpStream, resources: &Resources, next_etag: &mut i32) {
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line).unwrap();
+        let mut parts = request_line.split_whitespace();
+        let (method, href) = (parts.next().unwrap(), parts.next().unwrap().to_string());
+        let mut headers = HashMap::new();
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+            let colon = line.find(':').unwrap();
+            headers.insert(
+                line[..colon].to_lowercase(),
+                line[colon + 1..].trim().to_string(),
+            );
+        }
+        let length = headers
+            .get("content-length")
+            .map_or(0, |l| l.parse().unwrap());
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body).unwrap();
+
+        let mut resources = resources.lock().unwrap();
+        let (status, etag, body) = match method {
+            "PROPFIND" => {
+                let mut xml = format!(
+                    "<d:multistatus xmlns:d=\"DAV:\"><d:response><d:href>{}</d:href>\
+                     <d:propstat><d:prop/></d:propstat></d:response>",
+                    href
+                );
+                for (href, (etag, _)) in resources.iter() {
+                    xml.push_str(&format!(
+                        "<d:response><d:href>{}</d:href><d:propstat><d:prop>\
+                         <d:getetag>{}</d:getetag></d:prop></d:propstat></d:response>",
+                        href,
+                        etag.replace('"', "&quot;")
+                    ));
+                }
+                xml.push_str("</d:multistatus>");
+                ("207 Multi-Status", None, xml)
+            }
+            "GET" => match resources.get(&href) {
+                Some((etag, data)) => ("200 OK", Some(etag.clone()), data.clone()),
+                None => ("404 Not Found", None, String::new()),
+            },
+            "PUT" => {
+                let current = resources.get(&href).map(|(etag, _)| etag);
+                let matches = match (headers.get("if-match"), headers.get("if-none-match")) {
+                    (Some(etag), _) => current == Some(etag),
+                    (None, Some(_)) => current.is_none(),
+                    (None, None) => true,
+                };
+                if matches {
+                    *next_etag += 1;
+                    let etag = format!("\"{}\"", next_etag);
+                    let data = String::from_utf8(body).unwrap();
+                    resources.insert(href, (etag.clone(), data));
+                    ("201 Created", Some(etag), String::new())
+                } else {
+                    ("412 Precondition Failed", None, String::new())
+                }
+            }
+            "DELETE" => {
+                let current = resources.get(&href).map(|(etag, _)| etag);
+                match (current, headers.get("if-match")) {
+                    (None, _) => ("404 Not Found", None, String::new()),
+                    (Some(etag), Some(expected)) if etag != expected => {
+                        ("412 Precondition Failed", None, String::new())
+                    }
+                    _ => {
+                        resources.remove(&href);
+                        ("204 No Content", None, String::new())
+                    }
+                }
+            }
+            _ => ("405 Method Not Allowed", None, String::new()),
+        };
+        let mut response = format!(
+            "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+            status,
+            body.len()
+        );
+        if let Some(etag) = etag {
+            response.push_str(&format!("ETag: {}\r\n", etag));
+        }
+        response.push_str("\r\n");
+        response.push_str(&body);
+        let mut stream = stream;
+        stream.write_all(response.as_bytes()).unwrap();
+    }
+
+    fn config(server: &Server, on_conflict: ConflictResolution) -> CalDavConfig {
+        CalDavConfig {
+            url: server.url.clone(),
+            on_conflict,
+            ..CalDavConfig::default()
+        }
+    }
+
+    fn event(name: &str) -> Event {
+        let start = Local.ymd(2026, 1, 5).and_hms(9, 0, 0);
+        let interval = Interval::builder()
+            .start(start)
+            .length(Duration::hours(1))
+            .build()
+            .unwrap();
+        Event::new(EventCommon::new(name.into(), Priority::Medium), interval)
+    }
+
+    fn sync_item(id: i32) -> SyncItem {
+        let items = API_STATE.with(|s| s.lock().unwrap().storage.get_sync_items());
+        items.into_iter().find(|i| i.id == id).unwrap()
+    }
+
+    fn name_of(id: i32) -> String {
+        get_event(id).unwrap().common().name().to_string()
+    }
+
+    /// Syncs the item `id` once more, with the server in whatever state the test left it
+    fn resync(server: &Server, on_conflict: ConflictResolution, id: i32) -> SyncSummary {
+        let config = config(server, on_conflict);
+        let mut sync = Sync {
+            client: Client::new(&config),
+            summary: SyncSummary::default(),
+        };
+        let item = sync_item(id);
+        sync.sync_item(&item, server.etag(&item.href).as_deref())
+            .unwrap();
+        sync.summary
+    }
+
+    /// An event called "local", as uploaded by a first sync. Returns its id and href.
+    fn synced_event(server: &Server) -> (i32, String) {
+        let (id, _) = add_event(&mut event("local")).unwrap();
+        let config = config(server, ConflictResolution::Skip);
+        let mut sync = Sync {
+            client: Client::new(&config),
+            summary: SyncSummary::default(),
+        };
+        let href = sync.client.href_for(&ical::uid_of(id));
+        sync.upload(id, &href, &local_data(id).unwrap(), None)
+            .unwrap();
+        (id, href)
+    }
+
+    fn change_locally(id: i32) {
+        update_event(id, &event("changed here")).unwrap();
+    }
+
+    fn change_remotely(server: &Server, href: &str) {
+        let data = server.data(href).unwrap();
+        let data = data.replace("SUMMARY:local", "SUMMARY:changed there");
+        server.put(href, data, "\"remote\"");
+    }
+
+    #[test]
+    fn parses_multistatus() {
+        let xml = r#"<?xml version="1.0"?>
+            <D:multistatus xmlns:D="DAV:">
+              <D:response>
+                <D:href>/cal/</D:href>
+                <D:propstat><D:prop><D:resourcetype><D:collection/></D:resourcetype></D:prop></D:propstat>
+              </D:response>
+              <D:response>
+                <D:href>/cal/a%20b.ics</D:href>
+                <D:propstat><D:prop><D:getetag>&quot;1&amp;2&quot;</D:getetag></D:prop></D:propstat>
+              </D:response>
+              <response xmlns="DAV:">
+                <propstat><prop><getetag>W/"3"</getetag></prop></propstat>
+                <href>/cal/c.ics</href>
+              </response>
+              <D:response><D:href>/cal/no-etag.ics</D:href></D:response>
+            </D:multistatus>"#;
+        assert_eq!(
+            parse_multistatus(xml),
+            vec![
+                ("/cal/a%20b.ics".to_string(), "\"1&2\"".to_string()),
+                ("/cal/c.ics".to_string(), "W/\"3\"".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn lists_the_collection() {
+        let server = Server::start();
+        let (_, href) = synced_event(&server);
+        let config = config(&server, ConflictResolution::Skip);
+        let listed = Client::new(&config).list().unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed.get(&href), server.etag(&href).as_ref());
+    }
+
+    #[test]
+    fn leaves_unchanged_items_alone() {
+        let server = Server::start();
+        let (id, href) = synced_event(&server);
+        let etag = server.etag(&href);
+        let summary = resync(&server, ConflictResolution::Local, id);
+        assert!(summary.uploaded.is_empty() && summary.downloaded.is_empty());
+        assert_eq!(server.etag(&href), etag);
+    }
+
+    #[test]
+    fn uploads_local_changes() {
+        let server = Server::start();
+        let (id, href) = synced_event(&server);
+        change_locally(id);
+        let summary = resync(&server, ConflictResolution::Remote, id);
+        assert_eq!(summary.uploaded, vec![id]);
+        assert!(server.data(&href).unwrap().contains("SUMMARY:changed here"));
+        assert_eq!(sync_item(id).etag, server.etag(&href));
+    }
+
+    #[test]
+    fn downloads_remote_changes() {
+        let server = Server::start();
+        let (id, href) = synced_event(&server);
+        change_remotely(&server, &href);
+        let summary = resync(&server, ConflictResolution::Local, id);
+        assert_eq!(summary.downloaded, vec![id]);
+        assert_eq!(name_of(id), "changed there");
+        assert_eq!(sync_item(id).etag.as_deref(), Some("\"remote\""));
+    }
+
+    #[test]
+    fn resolves_conflicts() {
+        let cases = [
+            (ConflictResolution::Local, "changed here"),
+            (ConflictResolution::Remote, "changed there"),
+            (ConflictResolution::Skip, "changed here"),
+        ];
+        for &(on_conflict, expected) in cases.iter() {
+            let server = Server::start();
+            let (id, href) = synced_event(&server);
+            change_locally(id);
+            change_remotely(&server, &href);
+            let summary = resync(&server, on_conflict, id);
+            assert_eq!(name_of(id), expected, "{}", on_conflict);
+            let on_server = server.data(&href).unwrap();
+            match on_conflict {
+                ConflictResolution::Local => {
+                    assert_eq!(summary.uploaded, vec![id]);
+                    assert!(on_server.contains("SUMMARY:changed here"));
+                }
+                ConflictResolution::Remote => assert_eq!(summary.downloaded, vec![id]),
+                ConflictResolution::Skip => {
+                    assert_eq!(summary.conflicts, vec![id]);
+                    assert!(on_server.contains("SUMMARY:changed there"));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn finishes_items_deleted_on_the_server() {
+        let server = Server::start();
+        let (id, href) = synced_event(&server);
+        server.delete(&href);
+        let summary = resync(&server, ConflictResolution::Skip, id);
+        assert_eq!(summary.removed, vec![id]);
+        assert!(get_event(id).unwrap().common().finished());
+        assert_eq!(sync_item(id).etag, None);
+
+        // Only changing it here brings it back
+        let summary = resync(&server, ConflictResolution::Skip, id);
+        assert!(summary.uploaded.is_empty());
+        change_locally(id);
+        let summary = resync(&server, ConflictResolution::Skip, id);
+        assert_eq!(summary.uploaded, vec![id]);
+        assert!(server.data(&href).is_some());
+    }
+
+    #[test]
+    fn deletes_items_deleted_here() {
+        let server = Server::start();
+        let (id, href) = synced_event(&server);
+        delete_event(id).unwrap();
+        let summary = resync(&server, ConflictResolution::Skip, id);
+        assert_eq!(summary.deleted, vec![id]);
+        assert!(server.data(&href).is_none());
+        let items = API_STATE.with(|s| s.lock().unwrap().storage.get_sync_items());
+        assert!(items.iter().all(|i| i.id != id && i.href != href));
+    }
+
+    #[test]
+    fn resolves_local_deletions_of_items_changed_remotely() {
+        let cases = [
+            ConflictResolution::Local,
+            ConflictResolution::Remote,
+            ConflictResolution::Skip,
+        ];
+        for &on_conflict in cases.iter() {
+            let server = Server::start();
+            let (id, href) = synced_event(&server);
+            delete_event(id).unwrap();
+            change_remotely(&server, &href);
+            let summary = resync(&server, on_conflict, id);
+            match on_conflict {
+                ConflictResolution::Local => {
+                    assert_eq!(summary.deleted, vec![id]);
+                    assert!(server.data(&href).is_none());
+                }
+                ConflictResolution::Remote => {
+                    assert_eq!(summary.downloaded.len(), 1);
+                    assert_eq!(name_of(summary.downloaded[0]), "changed there");
+                    assert_eq!(sync_item(summary.downloaded[0]).href, href);
+                }
+                ConflictResolution::Skip => {
+                    assert_eq!(summary.conflicts, vec![id]);
+                    assert!(server.data(&href).is_some());
+                    assert_eq!(sync_item(id).href, href);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn resolves_deletions_of_changed_items() {
+        let cases = [
+            ConflictResolution::Local,
+            ConflictResolution::Remote,
+            ConflictResolution::Skip,
+        ];
+        for &on_conflict in cases.iter() {
+            let server = Server::start();
+            let (id, href) = synced_event(&server);
+            change_locally(id);
+            server.delete(&href);
+            let summary = resync(&server, on_conflict, id);
+            match on_conflict {
+                ConflictResolution::Local => {
+                    assert_eq!(summary.uploaded, vec![id]);
+                    assert!(server.data(&href).is_some());
+                }
+                ConflictResolution::Remote => {
+                    assert_eq!(summary.removed, vec![id]);
+                    assert!(get_event(id).unwrap().common().finished());
+                }
+                ConflictResolution::Skip => {
+                    assert_eq!(summary.conflicts, vec![id]);
+                    assert!(!get_event(id).unwrap().common().finished());
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,307 @@
+//! Text charts for the terminal: bar charts, sparklines and a calendar heatmap of log counts.
+//! They fit the width of the terminal, and fall back to plain tables when the output isn't a
+//! terminal, so that piping them somewhere gives something a program can read.
+use std::collections::BTreeMap;
+
+use chrono::{Datelike, Duration, Local, NaiveDate};
+
+use super::{
+    log::{log_counts, LogQuery},
+    report::Report,
+};
+
+/// The width used when the terminal doesn't tell
+const DEFAULT_WIDTH: usize = 80;
+const BAR_EIGHTHS: [char; 8] = ['▏', '▎', '▍', '▌', '▋', '▊', '▉', '█'];
+const SPARKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+/// Heatmap cells from no logs to the most logs in a day
+const SHADES: [char; 5] = ['·', '░', '▒', '▓', '█'];
+const WEEKDAYS: [&str; 7] = ["Mon", "", "Wed", "", "Fri", "", "Sun"];
+
+/// Where a chart goes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChartOutput {
+    /// A terminal this many columns wide
+    Terminal(usize),
+    /// Anything else, which gets plain tables instead of charts
+    Plain,
+}
+
+impl ChartOutput {
+    /// What standard output is: a terminal of its width, or plain
+    pub fn detect() -> Self {
+        match terminal_size::terminal_size() {
+            Some((terminal_size::Width(w), _)) if w > 0 => ChartOutput::Terminal(w as usize),
+            Some(_) => ChartOutput::Terminal(DEFAULT_WIDTH),
+            None => ChartOutput::Plain,
+        }
+    }
+}
+
+/// `n` without decimals when it is whole, and with two otherwise
+fn format_number(n: f64) -> String {
+    if n.fract() == 0.0 {
+        format!("{}", n)
+    } else {
+        format!("{:.2}", n)
+    }
+}
+
+/// `s` cut to `width` characters, with an ellipsis if anything was cut
+fn truncate(s: &str, width: usize) -> String {
+    if s.chars().count() <= width {
+        s.to_string()
+    } else if width == 0 {
+        String::new()
+    } else {
+        let mut cut: String = s.chars().take(width - 1).collect();
+        cut.push('…');
+        cut
+    }
+}
+
+fn plain_table(items: &[(String, f64)]) -> String {
+    let label_width = items
+        .iter()
+        .map(|(l, _)| l.chars().count())
+        .max()
+        .unwrap_or(0);
+    let mut out = String::new();
+    for (label, value) in items {
+        out.push_str(&format!(
+            "{:<width$}  {}\n",
+            label,
+            format_number(*value),
+            width = label_width
+        ));
+    }
+    out
+}
+
+/// A bar of `eighths` eighths of a column
+fn bar(eighths: usize) -> String {
+    let mut bar: String = std::iter::repeat(BAR_EIGHTHS[7])
+        .take(eighths / 8)
+        .collect();
+    if eighths % 8 != 0 {
+        bar.push(BAR_EIGHTHS[eighths % 8 - 1]);
+    }
+    bar
+}
+
+/// A horizontal bar for each of `items`, labelled on the left and with its value on the right,
+/// scaled so that the largest value fills the width. Negative values get no bar.
+pub fn bar_chart(items: &[(String, f64)], output: ChartOutput) -> String {
+    let width = match output {
+        ChartOutput::Terminal(width) => width,
+        ChartOutput::Plain => return plain_table(items),
+    };
+    let values: Vec<String> = items.iter().map(|(_, v)| format_number(*v)).collect();
+    let value_width = values.iter().map(|v| v.chars().count()).max().unwrap_or(0);
+    let label_width = items
+        .iter()
+        .map(|(l, _)| l.chars().count())
+        .max()
+        .unwrap_or(0)
+        .min(width / 3);
+    let bar_width = width.saturating_sub(label_width + value_width + 2).max(1);
+    let max = items.iter().map(|(_, v)| *v).fold(0.0, f64::max);
+
+    let mut out = String::new();
+    for ((label, value), shown) in items.iter().zip(values) {
+        let eighths = if max > 0.0 && *value > 0.0 {
+            (value / max * (bar_width * 8) as f64).round() as usize
+        } else {
+            0
+        };
+        let bar = bar(eighths);
+        out.push_str(&format!(
+            "{:<label_width$} {:<bar_width$} {:>value_width$}\n",
+            truncate(label, label_width),
+            bar,
+            shown,
+            label_width = label_width,
+            bar_width = bar_width,
+            value_width = value_width
+        ));
+    }
+    out
+}
+
+/// `values` as a line of block characters from lowest to highest. Only the last values that fit
+/// the width are shown.
+pub fn sparkline(values: &[f64], output: ChartOutput) -> String {
+    let width = match output {
+        ChartOutput::Terminal(width) => width,
+        ChartOutput::Plain => {
+            let values: Vec<String> = values.iter().map(|v| format_number(*v)).collect();
+            return values.join(" ");
+        }
+    };
+    let values = &values[values.len().saturating_sub(width)..];
+    let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    values
+        .iter()
+        .map(|v| {
+            if max > min {
+                SPARKS[((v - min) / (max - min) * 7.0).round() as usize]
+            } else {
+                SPARKS[0]
+            }
+        })
+        .collect()
+}
+
+/// The shade of a day with `count` logs, when the busiest day has `max`
+fn shade(count: u32, max: u32) -> char {
+    if count == 0 || max == 0 {
+        SHADES[0]
+    } else {
+        SHADES[((count * 4 + max - 1) / max).min(4) as usize]
+    }
+}
+
+/// A calendar of `counts` with a column per week (from Monday) and a row per weekday, ending
+/// with the week of `end` and going back as many weeks as fit, up to a year. Not on a terminal,
+/// it is a table of the days that have any count instead.
+pub fn heatmap(counts: &BTreeMap<NaiveDate, u32>, end: NaiveDate, output: ChartOutput) -> String {
+    let width = match output {
+        ChartOutput::Terminal(width) => width,
+        ChartOutput::Plain => {
+            let mut out = String::new();
+            for (date, count) in counts.range(..=end) {
+                out.push_str(&format!("{}  {}\n", date.format("%Y-%m-%d"), count));
+            }
+            return out;
+        }
+    };
+    // Each week takes two columns, after the weekday names
+    let weeks = (width.saturating_sub(4) / 2).clamp(1, 53) as i64;
+    let last_week = end - Duration::days(end.weekday().num_days_from_monday() as i64);
+    let first_week = last_week - Duration::weeks(weeks - 1);
+    let shown = counts.range(first_week..=end);
+    let max = shown.clone().map(|(_, c)| *c).max().unwrap_or(0);
+    let total: u32 = shown.map(|(_, c)| *c).sum();
+
+    let mut months = String::from("    ");
+    let mut month_end = 0;
+    for week in 0..weeks {
+        let monday = first_week + Duration::weeks(week);
+        let column = 4 + week as usize * 2;
+        let first_of_month = (0..7)
+            .map(|d| monday + Duration::days(d))
+            .find(|d| d.day() == 1);
+        if (week == 0 || first_of_month.is_some()) && column >= month_end {
+            let name = first_of_month.unwrap_or(monday).format("%b").to_string();
+            months.push_str(&" ".repeat(column - months.chars().count()));
+            months.push_str(&name);
+            month_end = column + name.len() + 1;
+        }
+    }
+    let mut out = months.trim_end().to_string();
+    out.push('\n');
+    for (day, name) in WEEKDAYS.iter().enumerate() {
+        let mut line = format!("{:<4}", name);
+        for week in 0..weeks {
+            let date = first_week + Duration::weeks(week) + Duration::days(day as i64);
+            if date > end {
+                break;
+            }
+            line.push(shade(counts.get(&date).copied().unwrap_or(0), max));
+            line.push(' ');
+        }
+        out.push_str(line.trim_end());
+        out.push('\n');
+    }
+    let mut legend = format!("{} logs in {} weeks", total, weeks);
+    let scale: Vec<String> = SHADES.iter().map(char::to_string).collect();
+    let scale = format!("Less {} More", scale.join(" "));
+    let used = legend.chars().count() + scale.chars().count();
+    if used + 2 > width {
+        legend.push('\n');
+    } else {
+        legend.push_str(&" ".repeat((4 + weeks as usize * 2).saturating_sub(used).max(2)));
+    }
+    legend.push_str(&scale);
+    out.push_str(&legend);
+    out.push('\n');
+    out
+}
+
+/// The heatmap of the logs matching `query`, up to the end of its range or today
+pub fn log_heatmap(query: &LogQuery, output: ChartOutput) -> String {
+    let end = query
+        .range
+        .map_or_else(Local::now, |r| r.end())
+        .naive_local()
+        .date();
+    heatmap(&log_counts(query), end, output)
+}
+
+/// `report` as charts of hours: a bar per row, or when the report is split by period, a
+/// sparkline over the periods for each key along with its total
+pub fn report_chart(report: &Report, output: ChartOutput) -> String {
+    let hours = |d: Duration| d.num_seconds() as f64 / 3600.0;
+    let label = |key: &str| {
+        if key.is_empty() {
+            "-".to_string()
+        } else {
+            key.to_string()
+        }
+    };
+    let period = match report.period {
+        Some(period) => period,
+        None => {
+            let items: Vec<(String, f64)> = report
+                .rows
+                .iter()
+                .map(|r| (label(&r.key), (hours(r.total) * 100.0).round() / 100.0))
+                .collect();
+            return bar_chart(&items, output);
+        }
+    };
+
+    // Every period of the range, so that the ones without any time show up as gaps
+    let mut periods = Vec::new();
+    let mut date = period.start_of(report.range.start().naive_local().date());
+    let last = (report.range.end() - Duration::nanoseconds(1))
+        .naive_local()
+        .date();
+    while date <= last {
+        periods.push(date);
+        date = period.next(date);
+    }
+    let mut keys: BTreeMap<(&str, Option<i32>), Vec<f64>> = BTreeMap::new();
+    for row in report.rows.iter() {
+        let series = keys
+            .entry((&row.key, row.id))
+            .or_insert_with(|| vec![0.0; periods.len()]);
+        if let Some(i) = periods.iter().position(|p| Some(*p) == row.period) {
+            series[i] += hours(row.total);
+        }
+    }
+    let label_width = keys
+        .keys()
+        .map(|(k, _)| label(k).chars().count())
+        .max()
+        .unwrap_or(0);
+    let mut out = String::new();
+    for ((key, _), series) in keys {
+        let total = format_number((series.iter().sum::<f64>() * 100.0).round() / 100.0);
+        let spark_output = match output {
+            ChartOutput::Terminal(width) => {
+                ChartOutput::Terminal(width.saturating_sub(label_width + total.len() + 4).max(1))
+            }
+            ChartOutput::Plain => ChartOutput::Plain,
+        };
+        out.push_str(&format!(
+            "{:<width$}  {}  {}\n",
+            label(key),
+            total,
+            sparkline(&series, spark_output),
+            width = label_width
+        ));
+    }
+    out
+}
@@ -0,0 +1,201 @@
+//! Detection of events that overlap each other, including the occurrences of recurring events.
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::Duration;
+
+use super::{error::*, event::*, state::API_STATE, time};
+
+/// How far ahead the occurrences of a new recurring event are checked for conflicts
+const CHECK_HORIZON_WEEKS: i64 = 52;
+
+/// What to do when adding an event that overlaps stored ones
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    /// Add it silently
+    Allow,
+    /// Add it, and give the conflicts back to the caller to warn about
+    Warn,
+    /// Don't add it, and fail with `ErrorKind::Conflict`
+    Reject,
+}
+
+impl Default for ConflictPolicy {
+    fn default() -> Self {
+        ConflictPolicy::Warn
+    }
+}
+
+impl ConflictPolicy {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ConflictPolicy::Allow => "allow",
+            ConflictPolicy::Warn => "warn",
+            ConflictPolicy::Reject => "reject",
+        }
+    }
+}
+
+impl FromStr for ConflictPolicy {
+    type Err = ErrorKind;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "allow" => Ok(ConflictPolicy::Allow),
+            "warn" => Ok(ConflictPolicy::Warn),
+            "reject" => Ok(ConflictPolicy::Reject),
+            _ => Err(ErrorKind::InvalidField {
+                field: "conflict policy".into(),
+                message: format!("'{}' is not one of 'allow', 'warn' or 'reject'", s),
+            }),
+        }
+    }
+}
+
+/// Two occurrences of different events that overlap. `a` never starts after `b`. The ids are
+/// `None` for an event that isn't stored yet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    pub a: (Option<i32>, Interval),
+    pub b: (Option<i32>, Interval),
+}
+
+impl Conflict {
+    /// The time both occurrences share
+    pub fn overlap(&self) -> Interval {
+        let start = self.a.1.start().max(self.b.1.start());
+        let end = self.a.1.end().min(self.b.1.end());
+        Interval::from_start(start, (end - start).max(Duration::zero()))
+    }
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = |id: Option<i32>| match id {
+            Some(id) => format!("event {}", id),
+            None => "the new event".into(),
+        };
+        let overlap = self.overlap();
+        write!(
+            f,
+            "{} and {} overlap from {} for {}",
+            name(self.a.0),
+            name(self.b.0),
+            time::format_datetime(&overlap.start()),
+            time::format_duration(&overlap.length())
+        )
+    }
+}
+
+/// All the overlapping pairs among `occurrences` of different events
+pub fn find_overlaps(occurrences: &[(Option<i32>, Interval)]) -> Vec<Conflict> {
+    let mut sorted = occurrences.to_vec();
+    sorted.sort_by_key(|(_, i)| i.start());
+    let mut active: Vec<(Option<i32>, Interval)> = Vec::new();
+    let mut conflicts = Vec::new();
+    for x in sorted {
+        active.retain(|(_, a)| a.end() > x.1.start() || a.start() == x.1.start());
+        for a in active.iter() {
+            if a.0 != x.0 && a.1.overlaps(&x.1) {
+                conflicts.push(Conflict { a: *a, b: x });
+            }
+        }
+        active.push(x);
+    }
+    conflicts
+}
+
+fn stored_occurrences(window: &Interval) -> Vec<(Option<i32>, Interval)> {
+    get_events()
+        .iter()
+        .flat_map(|e| {
+            let id = e.common().id();
+            e.occurrences(window).into_iter().map(move |i| (id, i))
+        })
+        .collect()
+}
+
+/// The overlapping pairs of stored events in `window`
+pub fn conflicts(window: &Interval) -> Vec<Conflict> {
+    find_overlaps(&stored_occurrences(window))
+}
+
+/// The stored events `event` would overlap. Recurring events are checked for a year.
+pub fn conflicts_with(event: &Event) -> Vec<Conflict> {
+    let first = event.interval();
+    let window = if event.recurrence().is_some() {
+        Interval::from_start(
+            first.start(),
+            first.length().max(Duration::weeks(CHECK_HORIZON_WEEKS)),
+        )
+    } else {
+        first
+    };
+    // A stored event being changed is checked as it will be, not as it was
+    let mut occurrences = stored_occurrences(&window);
+    occurrences.retain(|(id, _)| *id != event.common().id());
+    occurrences.extend(
+        event
+            .occurrences(&window)
+            .into_iter()
+            .map(|i| (event.common().id(), i)),
+    );
+    find_overlaps(&occurrences)
+        .into_iter()
+        .filter(|c| c.a.0 == event.common().id() || c.b.0 == event.common().id())
+        .collect()
+}
+
+pub fn get_conflict_policy() -> ConflictPolicy {
+    API_STATE.with(|s| s.lock().unwrap().conflict_policy)
+}
+
+pub fn set_conflict_policy(policy: ConflictPolicy) {
+    API_STATE.with(|s| s.lock().unwrap().conflict_policy = policy);
+}
+
+/// Applies the conflict policy to an event about to be stored by `method`, giving back the
+/// conflicts to warn about
+pub(crate) fn check_new_event(method: &str, event: &Event) -> Result<Vec<Conflict>> {
+    let policy = get_conflict_policy();
+    if policy == ConflictPolicy::Allow {
+        return Ok(Vec::new());
+    }
+    let conflicts = conflicts_with(event);
+    match (policy, conflicts.first()) {
+        (ConflictPolicy::Reject, Some(c)) => Err(Error {
+            method: method.into(),
+            kind: ErrorKind::Conflict(c.to_string()),
+        }),
+        _ => Ok(conflicts),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::recurrence::{Frequency, Recurrence, RecurrenceRule};
+
+    fn daily_at(name: &str, start: &str) -> Event {
+        let interval =
+            Interval::from_start(time::parse_datetime(start).unwrap(), Duration::hours(1));
+        let mut event = Event::new(EventCommon::new(name.into(), Priority::Medium), interval);
+        event.set_recurrence(Some(Recurrence::new(RecurrenceRule::new(Frequency::Daily))));
+        event
+    }
+
+    #[test]
+    fn ignores_the_old_occurrences_of_a_moved_event() {
+        let (standup, _) = add_event(&mut daily_at("standup", "2045-03-06 09:00")).unwrap();
+        let (review, conflicts) = add_event(&mut daily_at("review", "2045-03-06 09:30")).unwrap();
+        assert_eq!(conflicts.len(), CHECK_HORIZON_WEEKS as usize * 7);
+        assert!(conflicts
+            .iter()
+            .all(|c| c.a.0 == Some(standup) && c.b.0.is_none()));
+
+        let conflicts = update_event(review, &daily_at("review", "2045-03-06 11:00")).unwrap();
+        assert_eq!(conflicts, vec![]);
+        let conflicts = set_recurrence(review, None).unwrap();
+        assert_eq!(conflicts, vec![]);
+    }
+}
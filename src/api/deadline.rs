@@ -0,0 +1,44 @@
+//! Deadlines of tasks and projects: which ones are overdue, and how much time there is to spare
+//! for the others.
+use chrono::{DateTime, Duration, Local};
+
+use super::{availability::*, dependency::critical_path, event::*};
+
+/// The unfinished tasks and projects whose deadline passed by `now`, the longest overdue first
+pub fn get_overdue(now: DateTime<Local>) -> Vec<EventType> {
+    let mut overdue: Vec<(DateTime<Local>, EventType)> = Vec::new();
+    for task in get_tasks().into_iter().filter(|t| t.is_overdue(now)) {
+        overdue.push((task.deadline().unwrap(), EventType::Task(task)));
+    }
+    for project in get_projects().into_iter().filter(|p| p.is_overdue(now)) {
+        overdue.push((project.deadline().unwrap(), EventType::Project(project)));
+    }
+    overdue.sort_by_key(|(due, _)| *due);
+    overdue.into_iter().map(|(_, e)| e).collect()
+}
+
+/// How much working time `item` has to spare before its deadline if work on it goes on from
+/// `now`, counting only the available time of the default calendar. For a task that's after its
+/// remaining estimate (none without one), and for a project after the critical path of its
+/// subtasks. Negative once it can't be done in time anymore, and `None` for events and anything
+/// without a deadline.
+pub fn slack(item: &EventType, now: DateTime<Local>) -> Option<Duration> {
+    let (deadline, work) = match item {
+        EventType::Event(_) => return None,
+        EventType::Task(t) => (t.deadline()?, t.remaining().unwrap_or_else(Duration::zero)),
+        EventType::Project(p) => (p.deadline()?, critical_path(p).length),
+    };
+    Some(working_time(now, deadline) - work)
+}
+
+/// The available time of the default calendar from `from` to `to`, negative if `to` is earlier
+fn working_time(from: DateTime<Local>, to: DateTime<Local>) -> Duration {
+    if to < from {
+        return -working_time(to, from);
+    }
+    get_calendar(DEFAULT_CALENDAR)
+        .unwrap_or_default()
+        .available_in(&Interval::from_start(from, to - from))
+        .iter()
+        .fold(Duration::zero(), |acc, i| acc + i.length())
+}
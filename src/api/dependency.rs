@@ -0,0 +1,262 @@
+//! Ordering constraints between tasks. A dependency is finish-to-start: the dependent task can
+//! only start once the task it depends on is done, plus an optional lag. Dependencies always form
+//! a DAG, since adding one that would close a cycle fails.
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+use chrono::Duration;
+
+use super::{error::*, event::*, state::API_STATE};
+
+/// `task` can't start before `lag` after `depends_on` is done
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Dependency {
+    pub task: i32,
+    pub depends_on: i32,
+    pub lag: Duration,
+}
+
+/// The chain of tasks that determines how long a project takes at least
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CriticalPath {
+    pub tasks: Vec<i32>,
+    pub length: Duration,
+}
+
+/// A cycle among `dependencies`, as the tasks along it with the first one repeated at the end
+pub fn find_cycle(dependencies: &[Dependency]) -> Option<Vec<i32>> {
+    let mut edges: HashMap<i32, Vec<i32>> = HashMap::new();
+    for d in dependencies {
+        edges.entry(d.task).or_default().push(d.depends_on);
+    }
+    let mut starts: Vec<i32> = edges.keys().copied().collect();
+    starts.sort();
+
+    // Iterative depth first search, keeping the current path to report the cycle
+    let mut done = HashSet::new();
+    for start in starts {
+        if done.contains(&start) {
+            continue;
+        }
+        let mut path = vec![start];
+        let mut next = vec![0];
+        while let Some(&node) = path.last() {
+            let i = *next.last().unwrap();
+            match edges.get(&node).and_then(|e| e.get(i)) {
+                Some(&to) => {
+                    *next.last_mut().unwrap() += 1;
+                    if let Some(pos) = path.iter().position(|&n| n == to) {
+                        let mut cycle = path[pos..].to_vec();
+                        cycle.push(to);
+                        return Some(cycle);
+                    }
+                    if !done.contains(&to) {
+                        path.push(to);
+                        next.push(0);
+                    }
+                }
+                None => {
+                    done.insert(node);
+                    path.pop();
+                    next.pop();
+                }
+            }
+        }
+    }
+    None
+}
+
+/// `tasks` ordered so that every task comes after the ones it depends on, keeping the given order
+/// otherwise. Fails with the cycle if there is one among them.
+pub fn topological_order(
+    tasks: &[i32],
+    dependencies: &[Dependency],
+) -> std::result::Result<Vec<i32>, Vec<i32>> {
+    let index: HashMap<i32, usize> = tasks.iter().enumerate().map(|(i, &t)| (t, i)).collect();
+    let relevant: Vec<Dependency> = dependencies
+        .iter()
+        .filter(|d| index.contains_key(&d.task) && index.contains_key(&d.depends_on))
+        .copied()
+        .collect();
+
+    // Kahn's algorithm, always taking the earliest of the tasks that are ready
+    let mut waiting_for = vec![0; tasks.len()];
+    let mut dependents: Vec<Vec<usize>> = vec![Vec::new(); tasks.len()];
+    for d in relevant.iter() {
+        waiting_for[index[&d.task]] += 1;
+        dependents[index[&d.depends_on]].push(index[&d.task]);
+    }
+    let mut ready: BinaryHeap<Reverse<usize>> = (0..tasks.len())
+        .filter(|&i| waiting_for[i] == 0)
+        .map(Reverse)
+        .collect();
+    let mut order = Vec::with_capacity(tasks.len());
+    while let Some(Reverse(i)) = ready.pop() {
+        order.push(tasks[i]);
+        for &j in dependents[i].iter() {
+            waiting_for[j] -= 1;
+            if waiting_for[j] == 0 {
+                ready.push(Reverse(j));
+            }
+        }
+    }
+    if order.len() < tasks.len() {
+        return Err(find_cycle(&relevant).unwrap_or_default());
+    }
+    Ok(order)
+}
+
+/// The longest chain of dependent tasks in `project` (at any depth), counting the work left in
+/// each task (nothing for finished tasks or ones without an estimate) and the lags between them
+pub fn critical_path(project: &Project) -> CriticalPath {
+    let tasks = project.tasks();
+    let ids: Vec<i32> = tasks.iter().filter_map(|t| t.common().id()).collect();
+    let length_of = |id: i32| {
+        tasks
+            .iter()
+            .find(|t| t.common().id() == Some(id))
+            .and_then(|t| t.remaining())
+            .unwrap_or_else(Duration::zero)
+    };
+    let dependencies = get_dependencies();
+    // Stored dependencies are acyclic
+    let order = topological_order(&ids, &dependencies).unwrap_or(ids);
+
+    // Earliest finish of each task, relative to the start of the project, and the task before it
+    // on the longest chain leading to it
+    let mut finish: HashMap<i32, (Duration, Option<i32>)> = HashMap::new();
+    for &id in order.iter() {
+        let (start, before) = dependencies
+            .iter()
+            .filter(|d| d.task == id)
+            .filter_map(|d| {
+                finish
+                    .get(&d.depends_on)
+                    .map(|f| (f.0 + d.lag, d.depends_on))
+            })
+            .max_by_key(|(start, _)| *start)
+            .map_or((Duration::zero(), None), |(start, before)| {
+                (start, Some(before))
+            });
+        finish.insert(id, (start + length_of(id), before));
+    }
+
+    let last = order.iter().copied().max_by_key(|id| finish[id].0);
+    let mut tasks = Vec::new();
+    let mut current = last;
+    while let Some(id) = current {
+        tasks.push(id);
+        current = finish[&id].1;
+    }
+    tasks.reverse();
+    CriticalPath {
+        length: last.map_or_else(Duration::zero, |id| finish[&id].0),
+        tasks,
+    }
+}
+
+pub fn get_dependencies() -> Vec<Dependency> {
+    API_STATE.with(|s| {
+        s.lock()
+            .unwrap()
+            .storage
+            .get_dependencies()
+            .into_iter()
+            .map(|r| Dependency {
+                task: r.id,
+                depends_on: r.depends_on,
+                lag: Duration::seconds(r.lag),
+            })
+            .collect()
+    })
+}
+
+/// The dependencies of the task `task` on other tasks
+pub fn dependencies_of(task: i32) -> Vec<Dependency> {
+    get_dependencies()
+        .into_iter()
+        .filter(|d| d.task == task)
+        .collect()
+}
+
+/// The dependencies of other tasks on the task `task`
+pub fn dependents_of(task: i32) -> Vec<Dependency> {
+    get_dependencies()
+        .into_iter()
+        .filter(|d| d.depends_on == task)
+        .collect()
+}
+
+/// Makes the task `task` depend on the task `depends_on`, or changes the lag if it already does.
+/// Fails with `ErrorKind::DependencyCycle` if `depends_on` already depends on `task`, even
+/// indirectly.
+pub fn add_dependency(task: i32, depends_on: i32, lag: Duration) -> Result<()> {
+    get_task_checked("add_dependency", task)?;
+    get_task_checked("add_dependency", depends_on)?;
+    let mut dependencies = get_dependencies();
+    dependencies.retain(|d| !(d.task == task && d.depends_on == depends_on));
+    dependencies.push(Dependency {
+        task,
+        depends_on,
+        lag,
+    });
+    if let Some(cycle) = find_cycle(&dependencies) {
+        return Err(Error {
+            method: "add_dependency".into(),
+            kind: ErrorKind::DependencyCycle(cycle),
+        });
+    }
+    API_STATE.with(|s| {
+        s.lock()
+            .unwrap()
+            .storage
+            .add_dependency(task, depends_on, lag.num_seconds())
+    });
+    Ok(())
+}
+
+/// Makes the task `task` stop depending on the task `depends_on`, if it did
+pub fn remove_dependency(task: i32, depends_on: i32) -> Result<()> {
+    get_task_checked("remove_dependency", task)?;
+    get_task_checked("remove_dependency", depends_on)?;
+    API_STATE.with(|s| {
+        s.lock()
+            .unwrap()
+            .storage
+            .remove_dependency(task, depends_on)
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn on(task: i32, depends_on: i32) -> Dependency {
+        Dependency {
+            task,
+            depends_on,
+            lag: Duration::zero(),
+        }
+    }
+
+    #[test]
+    fn orders_dependencies_first() {
+        let dependencies = [on(1, 3), on(3, 4), on(2, 4), on(5, 6)];
+        assert_eq!(
+            topological_order(&[1, 2, 3, 4, 5], &dependencies),
+            Ok(vec![4, 2, 3, 1, 5])
+        );
+        assert_eq!(topological_order(&[5, 4, 3], &[]), Ok(vec![5, 4, 3]));
+    }
+
+    #[test]
+    fn reports_cycles() {
+        let dependencies = [on(1, 2), on(2, 3), on(3, 1), on(4, 1)];
+        assert_eq!(
+            topological_order(&[4, 1, 2, 3], &dependencies),
+            Err(vec![1, 2, 3, 1])
+        );
+        assert_eq!(find_cycle(&dependencies[..2]), None);
+    }
+}
@@ -1,6 +1,10 @@
 use rlua::prelude::*;
 use std::fmt;
 
+use chrono::{DateTime, Local};
+
+use super::{event::IntervalBuildError, time};
+
 #[derive(Debug)]
 pub struct APIError {
     function: String,
@@ -27,8 +31,50 @@ impl fmt::Display for Error {
 
 #[derive(Debug)]
 pub enum ErrorKind {
-    MissingField { typ: String, field: String },
+    MissingField {
+        typ: String,
+        field: String,
+    },
+    MissingFields {
+        typ: String,
+        fields: Vec<String>,
+    },
     InvalidLogType(String),
+    InvalidLogId(i32),
+    InvalidInterval(IntervalBuildError),
+    InvalidEventId(i32),
+    WrongEventKind {
+        id: i32,
+        expected: String,
+        found: String,
+    },
+    InvalidLifecycle(String),
+    InvalidPriority(String),
+    InvalidRecurrence(String),
+    NotRecurring(i32),
+    NotAnOccurrence(DateTime<Local>),
+    Conflict(String),
+    TimerNotRunning(i32),
+    TaskFinished(i32),
+    FocusNotRunning,
+    DependencyCycle(Vec<i32>),
+    InvalidCalendar(String),
+    InvalidICalendar(String),
+    InvalidJson(String),
+    SyncFailed(String),
+    CyclicHierarchy {
+        id: i32,
+        parent: i32,
+    },
+    InvalidField {
+        field: String,
+        message: String,
+    },
+    HandlerFailed {
+        id: i32,
+        lifecycle: String,
+        cause: LuaError,
+    },
     LuaError(LuaError),
 }
 
@@ -38,12 +84,73 @@ impl fmt::Display for ErrorKind {
             ErrorKind::MissingField { typ, field } => {
                 write!(f, "Missing field '{}' in type '{}'", field, typ)
             }
+            ErrorKind::MissingFields { typ, fields } => {
+                let fields: Vec<String> = fields.iter().map(|f| format!("'{}'", f)).collect();
+                let plural = if fields.len() == 1 { "" } else { "s" };
+                write!(
+                    f,
+                    "Missing field{} {} in type '{}'",
+                    plural,
+                    fields.join(", "),
+                    typ
+                )
+            }
             ErrorKind::InvalidLogType(s) => write!(f, "Invalid log type: '{}'", s),
+            ErrorKind::InvalidLogId(id) => write!(f, "Invalid log id: {}", id),
+            ErrorKind::InvalidInterval(e) => write!(f, "Invalid interval: {}", e),
+            ErrorKind::InvalidEventId(id) => write!(f, "Invalid event id: {}", id),
+            ErrorKind::WrongEventKind {
+                id,
+                expected,
+                found,
+            } => write!(f, "Event {} is a {}, expected a {}", id, found, expected),
+            ErrorKind::InvalidLifecycle(s) => write!(f, "Invalid lifecycle: '{}'", s),
+            ErrorKind::InvalidPriority(s) => write!(f, "Invalid priority: '{}'", s),
+            ErrorKind::InvalidRecurrence(s) => write!(f, "Invalid recurrence: {}", s),
+            ErrorKind::NotRecurring(id) => write!(f, "Event {} doesn't recur", id),
+            ErrorKind::NotAnOccurrence(t) => {
+                write!(f, "No occurrence starts at {}", time::format_datetime(t))
+            }
+            ErrorKind::Conflict(s) => write!(f, "Conflicting events: {}", s),
+            ErrorKind::TimerNotRunning(id) => write!(f, "No timer is running for task {}", id),
+            ErrorKind::TaskFinished(id) => write!(f, "Task {} is already finished", id),
+            ErrorKind::FocusNotRunning => write!(f, "No focus session is running"),
+            ErrorKind::DependencyCycle(cycle) => {
+                let cycle: Vec<String> = cycle.iter().map(i32::to_string).collect();
+                write!(f, "Dependency cycle between tasks {}", cycle.join(" -> "))
+            }
+            ErrorKind::InvalidCalendar(name) => write!(f, "No calendar named '{}'", name),
+            ErrorKind::InvalidICalendar(s) => write!(f, "Invalid iCalendar data: {}", s),
+            ErrorKind::InvalidJson(s) => write!(f, "Invalid JSON: {}", s),
+            ErrorKind::SyncFailed(s) => write!(f, "CalDAV sync failed: {}", s),
+            ErrorKind::CyclicHierarchy { id, parent } => write!(
+                f,
+                "Can't move {} into {}, which is {} itself or inside it",
+                id, parent, id
+            ),
+            ErrorKind::InvalidField { field, message } => {
+                write!(f, "Invalid value for '{}': {}", field, message)
+            }
+            ErrorKind::HandlerFailed {
+                id,
+                lifecycle,
+                cause,
+            } => write!(
+                f,
+                "Handler for '{}' of event {} failed: {}",
+                lifecycle, id, cause
+            ),
             ErrorKind::LuaError(e) => e.fmt(f),
         }
     }
 }
 
+impl From<IntervalBuildError> for ErrorKind {
+    fn from(e: IntervalBuildError) -> Self {
+        ErrorKind::InvalidInterval(e)
+    }
+}
+
 impl std::error::Error for Error {}
 
 pub type Result<T> = std::result::Result<T, Error>;
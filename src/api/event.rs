@@ -1,21 +1,184 @@
+use std::cmp::Reverse;
 use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::ops::RangeBounds;
+use std::str::FromStr;
 use std::sync::{Arc, Mutex};
 
-use chrono::{DateTime, Duration, Local};
-use rlua::prelude::*;
+use chrono::{DateTime, Duration, Local, TimeZone};
+use serde::de::{self, Deserialize, Deserializer, Visitor};
+use serde::{Serialize, Serializer};
 
+use super::{
+    conflict::{check_new_event, Conflict},
+    error::{self, ErrorKind},
+    lifecycle::{notify, Lifecycle},
+    recurrence::Recurrence,
+    state::API_STATE,
+    time,
+    tree::{self, subtree},
+};
+use crate::storage::{model::EventRecord, LogStorage};
+
+/// How important something is, from `VeryLow` (1) to `VeryHigh` (9). Priorities compare by
+/// importance, so sorting in reverse puts the most important first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum Priority {
-    VeryHigh,
-    High,
-    NotSoHigh,
-    MediumHigh,
-    Medium,
+    VeryLow = 1,
+    Low,
     MediumLow,
     NotSoLow,
-    Low,
-    VeryLow,
+    Medium,
+    NotSoHigh,
+    MediumHigh,
+    High,
+    VeryHigh,
+}
+
+const PRIORITIES: [Priority; 9] = [
+    Priority::VeryLow,
+    Priority::Low,
+    Priority::MediumLow,
+    Priority::NotSoLow,
+    Priority::Medium,
+    Priority::NotSoHigh,
+    Priority::MediumHigh,
+    Priority::High,
+    Priority::VeryHigh,
+];
+
+impl Priority {
+    pub fn all() -> &'static [Priority] {
+        &PRIORITIES
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Priority::VeryLow => "very_low",
+            Priority::Low => "low",
+            Priority::MediumLow => "medium_low",
+            Priority::NotSoLow => "not_so_low",
+            Priority::Medium => "medium",
+            Priority::NotSoHigh => "not_so_high",
+            Priority::MediumHigh => "medium_high",
+            Priority::High => "high",
+            Priority::VeryHigh => "very_high",
+        }
+    }
+
+    pub fn level(&self) -> i32 {
+        *self as i32
+    }
+}
+
+impl Default for Priority {
+    fn default() -> Self {
+        Priority::Medium
+    }
+}
+
+impl fmt::Display for Priority {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl From<Priority> for i32 {
+    fn from(p: Priority) -> i32 {
+        p.level()
+    }
+}
+
+impl TryFrom<i32> for Priority {
+    type Error = ErrorKind;
+
+    fn try_from(level: i32) -> Result<Self, Self::Error> {
+        PRIORITIES
+            .iter()
+            .find(|p| p.level() == level)
+            .copied()
+            .ok_or_else(|| ErrorKind::InvalidPriority(level.to_string()))
+    }
+}
+
+impl FromStr for Priority {
+    type Err = ErrorKind;
+
+    /// Accepts the level (`"1"` to `"9"`) or the name, ignoring case and any `_`, `-` or space,
+    /// so `"very_high"`, `"VeryHigh"` and `"very high"` are all the same.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(level) = s.trim().parse::<i32>() {
+            return Priority::try_from(level);
+        }
+        let normalize = |s: &str| {
+            s.chars()
+                .filter(|c| !matches!(c, '_' | '-' | ' '))
+                .flat_map(char::to_lowercase)
+                .collect::<String>()
+        };
+        let wanted = normalize(s);
+        PRIORITIES
+            .iter()
+            .find(|p| normalize(p.name()) == wanted)
+            .copied()
+            .ok_or_else(|| ErrorKind::InvalidPriority(s.into()))
+    }
+}
+
+impl Serialize for Priority {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name())
+    }
+}
+
+impl<'de> Deserialize<'de> for Priority {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct PriorityVisitor;
+
+        impl<'de> Visitor<'de> for PriorityVisitor {
+            type Value = Priority;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a priority name or a level from 1 to 9")
+            }
+
+            fn visit_str<E: de::Error>(self, s: &str) -> Result<Priority, E> {
+                s.parse().map_err(E::custom)
+            }
+
+            fn visit_i64<E: de::Error>(self, i: i64) -> Result<Priority, E> {
+                Priority::try_from(i as i32).map_err(E::custom)
+            }
+
+            fn visit_u64<E: de::Error>(self, i: u64) -> Result<Priority, E> {
+                Priority::try_from(i as i32).map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_any(PriorityVisitor)
+    }
+}
+
+/// Sorts `items` so that the most important ones come first, keeping the order of equally
+/// important ones
+pub fn sort_by_priority<T: AsRef<EventCommon>>(items: &mut [T]) {
+    items.sort_by_key(|i| Reverse(i.as_ref().priority()));
 }
 
+/// Keeps only the items whose priority is in `range`, e.g. `Priority::High..`
+pub fn filter_by_priority<T, R>(items: Vec<T>, range: R) -> Vec<T>
+where
+    T: AsRef<EventCommon>,
+    R: RangeBounds<Priority>,
+{
+    items
+        .into_iter()
+        .filter(|i| range.contains(&i.as_ref().priority()))
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Interval {
     start: DateTime<Local>,
     length: Duration,
@@ -29,15 +192,80 @@ impl Interval {
             end: None,
         }
     }
+
+    pub fn start(&self) -> DateTime<Local> {
+        self.start
+    }
+
+    pub fn length(&self) -> Duration {
+        self.length
+    }
+
+    pub fn end(&self) -> DateTime<Local> {
+        self.start + self.length
+    }
+
+    /// For when the length is already known to be non-negative
+    pub(crate) fn from_start(start: DateTime<Local>, length: Duration) -> Self {
+        Self { start, length }
+    }
+
+    /// Whether `t` is in `[start, end)`
+    pub fn contains(&self, t: DateTime<Local>) -> bool {
+        self.start <= t && t < self.end()
+    }
+
+    /// Whether the two intervals share any time. Touching intervals don't overlap, but an empty
+    /// interval overlaps an interval it is inside of, or another empty one at the same time.
+    pub fn overlaps(&self, other: &Interval) -> bool {
+        (self.start < other.end() && other.start < self.end()) || self.start == other.start
+    }
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum IntervalBuildError {
     /// When the caller specifies all 3 constraints, and they don't match
-    TooManyConstraints,
+    InconsistentConstraints {
+        start: DateTime<Local>,
+        length: Duration,
+        end: DateTime<Local>,
+    },
+    /// When the resulting interval would end before it starts. Zero lengths are fine.
+    NegativeLength(Duration),
     /// When the caller specifies only 1 of the 3 constraints
     NotEnoughConstraints,
+    /// When the start or end would be past the range of dates that can be represented
+    OutOfRange(Duration),
 }
 
+impl fmt::Display for IntervalBuildError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IntervalBuildError::InconsistentConstraints { start, length, end } => write!(
+                f,
+                "Start {} plus length {} is not end {}",
+                time::format_datetime(start),
+                time::format_duration(length),
+                time::format_datetime(end)
+            ),
+            IntervalBuildError::NegativeLength(l) => {
+                write!(f, "Negative interval length: {}", time::format_duration(l))
+            }
+            IntervalBuildError::NotEnoughConstraints => {
+                write!(f, "Need at least 2 of `start`, `length` and `end`")
+            }
+            IntervalBuildError::OutOfRange(l) => write!(
+                f,
+                "Interval length {} is out of range",
+                time::format_duration(l)
+            ),
+        }
+    }
+}
+
+impl std::error::Error for IntervalBuildError {}
+
+#[derive(Debug, Clone, Default)]
 pub struct IntervalBuilder {
     start: Option<DateTime<Local>>,
     length: Option<Duration>,
@@ -61,55 +289,77 @@ impl IntervalBuilder {
     }
 
     pub fn build(&self) -> Result<Interval, IntervalBuildError> {
-        match (self.start, self.length, self.end) {
+        let (start, length) = match (self.start, self.length, self.end) {
             (Some(s), Some(l), Some(e)) => {
-                if s + l != e {
-                    Err(IntervalBuildError::TooManyConstraints)
-                } else {
-                    Ok(Interval {
+                if s.checked_add_signed(l) != Some(e) {
+                    return Err(IntervalBuildError::InconsistentConstraints {
                         start: s,
                         length: l,
-                    })
+                        end: e,
+                    });
                 }
+                (s, l)
+            }
+            (Some(s), None, Some(e)) => (s, e - s),
+            (Some(s), Some(l), None) => (s, l),
+            (None, Some(l), Some(e)) => {
+                let s = e
+                    .checked_sub_signed(l)
+                    .ok_or(IntervalBuildError::OutOfRange(l))?;
+                (s, l)
             }
-            (Some(s), None, Some(e)) => Ok(Interval {
-                start: s,
-                length: e - s,
-            }),
-            (Some(s), Some(l), None) => Ok(Interval {
-                start: s,
-                length: l,
-            }),
-            (None, Some(l), Some(e)) => Ok(Interval {
-                start: e - l,
-                length: l,
-            }),
-            _ => Err(IntervalBuildError::NotEnoughConstraints),
+            _ => return Err(IntervalBuildError::NotEnoughConstraints),
+        };
+        if length < Duration::zero() {
+            return Err(IntervalBuildError::NegativeLength(length));
         }
+        // `end` adds the length back on, so that has to stay in range too
+        start
+            .checked_add_signed(length)
+            .ok_or(IntervalBuildError::OutOfRange(length))?;
+        Ok(Interval { start, length })
     }
 }
 
-pub struct EventCommon<'lua> {
+#[derive(Debug, Clone)]
+pub struct EventCommon {
+    id: Option<i32>,
     name: String,
     priority: Priority,
-    handlers: HashMap<String, LuaFunction<'lua>>,
     props: HashMap<String, String>,
     finished: bool,
 }
 
-impl<'lua> EventCommon<'lua> {
+impl EventCommon {
     pub fn new(name: String, priority: Priority) -> Self {
         Self {
+            id: None,
             name,
             priority,
-            handlers: HashMap::new(),
             props: HashMap::new(),
             finished: false,
         }
     }
 
-    pub fn set_handler<S: AsRef<str>>(&mut self, key: S, f: LuaFunction<'lua>) {
-        self.handlers.insert(key.as_ref().into(), f);
+    /// The id in storage, or `None` if this hasn't been stored yet
+    pub fn id(&self) -> Option<i32> {
+        self.id
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn priority(&self) -> Priority {
+        self.priority
+    }
+
+    pub fn props(&self) -> &HashMap<String, String> {
+        &self.props
+    }
+
+    pub fn finished(&self) -> bool {
+        self.finished
     }
 
     pub fn set_prop<S: AsRef<str>>(&mut self, key: S, val: String) {
@@ -121,47 +371,795 @@ impl<'lua> EventCommon<'lua> {
     }
 }
 
-pub struct Event<'lua> {
-    inner: EventCommon<'lua>,
+#[derive(Debug, Clone)]
+pub struct Event {
+    inner: EventCommon,
     interval: Interval,
+    recurrence: Option<Recurrence>,
 }
 
-impl<'lua> Event<'lua> {
-    pub fn new(inner: EventCommon<'lua>, interval: Interval) -> Self {
-        Self { inner, interval }
+impl Event {
+    pub fn new(inner: EventCommon, interval: Interval) -> Self {
+        Self {
+            inner,
+            interval,
+            recurrence: None,
+        }
+    }
+
+    pub fn common(&self) -> &EventCommon {
+        &self.inner
+    }
+
+    /// The interval of the first occurrence
+    pub fn interval(&self) -> Interval {
+        self.interval
+    }
+
+    pub fn recurrence(&self) -> Option<&Recurrence> {
+        self.recurrence.as_ref()
+    }
+
+    pub fn set_recurrence(&mut self, recurrence: Option<Recurrence>) {
+        self.recurrence = recurrence;
+    }
+
+    /// Whether an occurrence of this event starts at `original`, before skipping or moving any
+    pub fn is_occurrence(&self, original: DateTime<Local>) -> bool {
+        match &self.recurrence {
+            Some(r) => r.is_occurrence(&self.interval, original),
+            None => self.interval.start == original,
+        }
+    }
+
+    /// The occurrences of this event overlapping `window`, in order
+    pub fn occurrences(&self, window: &Interval) -> Vec<Interval> {
+        match &self.recurrence {
+            Some(r) => r.expand(&self.interval, window),
+            None if self.interval.overlaps(window) => vec![self.interval],
+            None => Vec::new(),
+        }
     }
 }
 
-pub struct Task<'lua> {
-    inner: EventCommon<'lua>,
+#[derive(Debug, Clone)]
+pub struct Task {
+    inner: EventCommon,
     sessions: Vec<Interval>,
+    estimate: Option<Duration>,
+    deadline: Option<DateTime<Local>>,
 }
 
-impl<'lua> Task<'lua> {
-    pub fn new(inner: EventCommon<'lua>) -> Self {
+impl Task {
+    pub fn new(inner: EventCommon) -> Self {
         Self {
             inner,
             sessions: Vec::new(),
+            estimate: None,
+            deadline: None,
+        }
+    }
+
+    pub fn common(&self) -> &EventCommon {
+        &self.inner
+    }
+
+    pub fn sessions(&self) -> &[Interval] {
+        &self.sessions
+    }
+
+    pub fn add_session(&mut self, session: Interval) {
+        self.sessions.push(session);
+    }
+
+    /// How much work the task was expected to take
+    pub fn estimate(&self) -> Option<Duration> {
+        self.estimate
+    }
+
+    pub fn set_estimate(&mut self, estimate: Option<Duration>) {
+        self.estimate = estimate;
+    }
+
+    /// The time spent on the task so far, the total of its sessions
+    pub fn actual(&self) -> Duration {
+        self.sessions
+            .iter()
+            .fold(Duration::zero(), |acc, s| acc + s.length())
+    }
+
+    /// The work left according to the estimate: none once finished or over the estimate
+    pub fn remaining(&self) -> Option<Duration> {
+        let estimate = self.estimate?;
+        if self.inner.finished {
+            return Some(Duration::zero());
+        }
+        Some((estimate - self.actual()).max(Duration::zero()))
+    }
+
+    /// How far along the task is, from 0 to 1. Finished tasks are complete, and unfinished ones
+    /// are as complete as the share of their estimate spent on them.
+    pub fn progress(&self) -> f64 {
+        if self.inner.finished {
+            return 1.0;
+        }
+        match self.estimate {
+            Some(e) if e > Duration::zero() => {
+                (self.actual().num_seconds() as f64 / e.num_seconds() as f64).min(1.0)
+            }
+            _ => 0.0,
         }
     }
+
+    pub fn deadline(&self) -> Option<DateTime<Local>> {
+        self.deadline
+    }
+
+    pub fn set_deadline(&mut self, deadline: Option<DateTime<Local>>) {
+        self.deadline = deadline;
+    }
+
+    /// Whether the deadline passed by `now` without the task being finished
+    pub fn is_overdue(&self, now: DateTime<Local>) -> bool {
+        !self.inner.finished && self.deadline.map_or(false, |d| d < now)
+    }
 }
 
-pub struct Project<'lua> {
-    inner: EventCommon<'lua>,
-    subtasks: Vec<Task<'lua>>,
+/// A group of tasks, which can also contain other projects to any depth
+#[derive(Debug, Clone)]
+pub struct Project {
+    inner: EventCommon,
+    subtasks: Vec<Task>,
+    subprojects: Vec<Project>,
+    deadline: Option<DateTime<Local>>,
 }
 
-impl<'lua> Project<'lua> {
-    pub fn new(inner: EventCommon<'lua>) -> Self {
+impl Project {
+    pub fn new(inner: EventCommon) -> Self {
         Self {
             inner,
             subtasks: Vec::new(),
+            subprojects: Vec::new(),
+            deadline: None,
         }
     }
+
+    pub fn common(&self) -> &EventCommon {
+        &self.inner
+    }
+
+    /// The tasks directly in this project
+    pub fn subtasks(&self) -> &[Task] {
+        &self.subtasks
+    }
+
+    pub fn add_subtask(&mut self, task: Task) {
+        self.subtasks.push(task);
+    }
+
+    /// The projects directly in this project
+    pub fn subprojects(&self) -> &[Project] {
+        &self.subprojects
+    }
+
+    pub fn add_subproject(&mut self, project: Project) {
+        self.subprojects.push(project);
+    }
+
+    /// All the tasks in this project and in the projects it contains, at any depth
+    pub fn tasks(&self) -> Vec<&Task> {
+        let mut tasks: Vec<&Task> = self.subtasks.iter().collect();
+        for project in self.subprojects.iter() {
+            tasks.extend(project.tasks());
+        }
+        tasks
+    }
+
+    /// The total estimate of all the tasks in the project, or `None` if none of them has one
+    pub fn estimate(&self) -> Option<Duration> {
+        self.tasks()
+            .into_iter()
+            .filter_map(Task::estimate)
+            .fold(None, |acc, e| Some(acc.unwrap_or_else(Duration::zero) + e))
+    }
+
+    pub fn actual(&self) -> Duration {
+        self.tasks()
+            .into_iter()
+            .fold(Duration::zero(), |acc, t| acc + t.actual())
+    }
+
+    /// The work left in the tasks of the project that have an estimate
+    pub fn remaining(&self) -> Option<Duration> {
+        self.tasks()
+            .into_iter()
+            .filter_map(Task::remaining)
+            .fold(None, |acc, r| Some(acc.unwrap_or_else(Duration::zero) + r))
+    }
+
+    /// The progress of the subtasks and subprojects, weighted by their estimates. Those without
+    /// an estimate weigh as much as the average of the others, or all weigh the same if none has
+    /// one. An empty project is complete once finished.
+    pub fn progress(&self) -> f64 {
+        if self.subtasks.is_empty() && self.subprojects.is_empty() {
+            return if self.inner.finished { 1.0 } else { 0.0 };
+        }
+        let parts: Vec<(Option<Duration>, f64)> = self
+            .subtasks
+            .iter()
+            .map(|t| (t.estimate(), t.progress()))
+            .chain(
+                self.subprojects
+                    .iter()
+                    .map(|p| (p.estimate(), p.progress())),
+            )
+            .collect();
+        let estimates: Vec<f64> = parts
+            .iter()
+            .filter_map(|(e, _)| e.map(|e| e.num_seconds() as f64))
+            .collect();
+        let default_weight = if estimates.is_empty() {
+            1.0
+        } else {
+            estimates.iter().sum::<f64>() / estimates.len() as f64
+        };
+        let weight = |estimate: Option<Duration>| {
+            estimate.map_or(default_weight, |e| e.num_seconds() as f64)
+        };
+        let total: f64 = parts.iter().map(|(e, _)| weight(*e)).sum();
+        if total <= 0.0 {
+            return 0.0;
+        }
+        parts.iter().map(|(e, p)| weight(*e) * p).sum::<f64>() / total
+    }
+
+    /// Whether everything in the project is finished. Empty projects are complete once finished
+    /// themselves.
+    pub fn is_complete(&self) -> bool {
+        if self.subtasks.is_empty() && self.subprojects.is_empty() {
+            return self.inner.finished;
+        }
+        self.subtasks.iter().all(|t| t.inner.finished)
+            && self.subprojects.iter().all(Project::is_complete)
+    }
+
+    pub fn deadline(&self) -> Option<DateTime<Local>> {
+        self.deadline
+    }
+
+    pub fn set_deadline(&mut self, deadline: Option<DateTime<Local>>) {
+        self.deadline = deadline;
+    }
+
+    pub fn is_overdue(&self, now: DateTime<Local>) -> bool {
+        !self.inner.finished && self.deadline.map_or(false, |d| d < now)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum EventType {
+    Event(Event),
+    Task(Task),
+    Project(Project),
+}
+
+impl EventType {
+    /// `"event"`, `"task"` or `"project"`
+    pub fn kind(&self) -> &'static str {
+        match self {
+            EventType::Event(_) => EVENT_KIND,
+            EventType::Task(_) => TASK_KIND,
+            EventType::Project(_) => PROJECT_KIND,
+        }
+    }
+
+    pub fn common(&self) -> &EventCommon {
+        match self {
+            EventType::Event(e) => e.common(),
+            EventType::Task(t) => t.common(),
+            EventType::Project(p) => p.common(),
+        }
+    }
+}
+
+impl AsRef<EventCommon> for EventCommon {
+    fn as_ref(&self) -> &EventCommon {
+        self
+    }
+}
+
+impl AsRef<EventCommon> for Event {
+    fn as_ref(&self) -> &EventCommon {
+        &self.inner
+    }
+}
+
+impl AsRef<EventCommon> for Task {
+    fn as_ref(&self) -> &EventCommon {
+        &self.inner
+    }
+}
+
+impl AsRef<EventCommon> for Project {
+    fn as_ref(&self) -> &EventCommon {
+        &self.inner
+    }
+}
+
+impl AsRef<EventCommon> for EventType {
+    fn as_ref(&self) -> &EventCommon {
+        self.common()
+    }
+}
+
+const EVENT_KIND: &str = "event";
+const TASK_KIND: &str = "task";
+const PROJECT_KIND: &str = "project";
+
+fn store_common(
+    storage: &mut LogStorage,
+    kind: &str,
+    common: &mut EventCommon,
+    parent: Option<i32>,
+) -> i32 {
+    let id = storage.add_event(kind, &common.name, common.priority.into(), parent);
+    for (key, val) in common.props.iter() {
+        storage.set_event_prop(id, key, val);
+    }
+    if common.finished {
+        storage.set_finished(id, true);
+    }
+    common.id = Some(id);
+    id
+}
+
+fn store_interval(storage: &mut LogStorage, id: i32, interval: &Interval) {
+    storage.add_interval(
+        id,
+        interval.start.naive_utc(),
+        interval.length.num_seconds(),
+    );
+}
+
+fn store_recurrence(storage: &mut LogStorage, id: i32, recurrence: Option<&Recurrence>) {
+    storage.set_recurrence(id, recurrence.map(|r| r.rule.to_string()).as_deref());
+    if let Some(r) = recurrence {
+        for original in r.exceptions.iter() {
+            storage.set_recurrence_exception(id, original.naive_utc(), None);
+        }
+        for (original, interval) in r.overrides.iter() {
+            storage.set_recurrence_exception(
+                id,
+                original.naive_utc(),
+                Some((interval.start.naive_utc(), interval.length.num_seconds())),
+            );
+        }
+    }
+}
+
+fn store_task(storage: &mut LogStorage, task: &mut Task, parent: Option<i32>) -> i32 {
+    let id = store_common(storage, TASK_KIND, &mut task.inner, parent);
+    storage.set_estimate(id, task.estimate.map(|e| e.num_seconds()));
+    storage.set_deadline(id, task.deadline.map(|d| d.naive_utc()));
+    for session in task.sessions.iter() {
+        store_interval(storage, id, session);
+    }
+    id
+}
+
+/// Stores `project` and everything in it, returning the ids of all of them with the project first
+fn store_project(storage: &mut LogStorage, project: &mut Project, parent: Option<i32>) -> Vec<i32> {
+    let id = store_common(storage, PROJECT_KIND, &mut project.inner, parent);
+    storage.set_deadline(id, project.deadline.map(|d| d.naive_utc()));
+    let mut ids = vec![id];
+    for task in project.subtasks.iter_mut() {
+        ids.push(store_task(storage, task, Some(id)));
+    }
+    for subproject in project.subprojects.iter_mut() {
+        ids.extend(store_project(storage, subproject, Some(id)));
+    }
+    ids
+}
+
+fn load_common(storage: &LogStorage, record: &EventRecord) -> EventCommon {
+    EventCommon {
+        id: Some(record.id),
+        name: record.name.clone(),
+        priority: Priority::try_from(record.priority).unwrap_or_default(),
+        props: storage.get_event_props_for(record.id),
+        finished: record.finished,
+    }
+}
+
+fn load_intervals(storage: &LogStorage, id: i32) -> Vec<Interval> {
+    storage
+        .get_intervals_for(id)
+        .into_iter()
+        .map(|r| Interval {
+            start: Local.from_utc_datetime(&r.start),
+            length: Duration::seconds(r.length),
+        })
+        .collect()
+}
+
+fn load_recurrence(storage: &LogStorage, id: i32) -> Option<Recurrence> {
+    // Rules are only stored after being parsed, so they should always parse again
+    let rule = storage.get_recurrence(id)?.parse().ok()?;
+    let mut recurrence = Recurrence::new(rule);
+    for e in storage.get_recurrence_exceptions(id) {
+        let original = Local.from_utc_datetime(&e.original);
+        match (e.start, e.length) {
+            (Some(start), Some(length)) => recurrence.reschedule(
+                original,
+                Interval {
+                    start: Local.from_utc_datetime(&start),
+                    length: Duration::seconds(length),
+                },
+            ),
+            _ => recurrence.skip(original),
+        }
+    }
+    Some(recurrence)
+}
+
+fn load_deadline(storage: &LogStorage, id: i32) -> Option<DateTime<Local>> {
+    storage
+        .get_deadline(id)
+        .map(|d| Local.from_utc_datetime(&d))
+}
+
+fn load(storage: &LogStorage, record: &EventRecord) -> EventType {
+    let inner = load_common(storage, record);
+    match record.kind.as_str() {
+        EVENT_KIND => {
+            let interval = load_intervals(storage, record.id)
+                .into_iter()
+                .next()
+                .unwrap_or(Interval {
+                    start: Local.from_utc_datetime(&record.created),
+                    length: Duration::zero(),
+                });
+            EventType::Event(Event {
+                inner,
+                interval,
+                recurrence: load_recurrence(storage, record.id),
+            })
+        }
+        PROJECT_KIND => {
+            let mut project = Project::new(inner);
+            project.deadline = load_deadline(storage, record.id);
+            for child in storage.get_children(record.id).iter() {
+                match load(storage, child) {
+                    EventType::Task(t) => project.subtasks.push(t),
+                    EventType::Project(p) => project.subprojects.push(p),
+                    EventType::Event(_) => {}
+                }
+            }
+            EventType::Project(project)
+        }
+        _ => EventType::Task(Task {
+            inner,
+            sessions: load_intervals(storage, record.id),
+            estimate: storage.get_estimate(record.id).map(Duration::seconds),
+            deadline: load_deadline(storage, record.id),
+        }),
+    }
+}
+
+/// Stores a new event, returning its id and the stored events it overlaps. Whether it may
+/// overlap them depends on the conflict policy.
+pub fn add_event(event: &mut Event) -> error::Result<(i32, Vec<Conflict>)> {
+    let conflicts = check_new_event("add_event", event)?;
+    let id = API_STATE.with(|s| {
+        let storage = &mut s.lock().unwrap().storage;
+        let id = store_common(storage, EVENT_KIND, &mut event.inner, None);
+        store_interval(storage, id, &event.interval);
+        store_recurrence(storage, id, event.recurrence.as_ref());
+        id
+    });
+    notify(id, Lifecycle::Created);
+    Ok((id, conflicts))
+}
+
+/// Stores a new task along with its sessions, returning its id
+pub fn add_task(task: &mut Task) -> i32 {
+    let id = API_STATE.with(|s| store_task(&mut s.lock().unwrap().storage, task, None));
+    notify(id, Lifecycle::Created);
+    id
+}
+
+/// Stores a new project along with everything it contains, returning its id
+pub fn add_project(project: &mut Project) -> i32 {
+    let ids = API_STATE.with(|s| store_project(&mut s.lock().unwrap().storage, project, None));
+    for id in ids.iter() {
+        notify(*id, Lifecycle::Created);
+    }
+    ids[0]
+}
+
+/// Stores a new project inside the project `parent`, returning its id. Unfinished work
+/// unfinishes the projects it is added to.
+pub fn add_subproject(parent: i32, project: &mut Project) -> i32 {
+    let ids =
+        API_STATE.with(|s| store_project(&mut s.lock().unwrap().storage, project, Some(parent)));
+    for id in ids.iter() {
+        notify(*id, Lifecycle::Created);
+    }
+    if !project.is_complete() {
+        tree::roll_up(parent);
+    }
+    ids[0]
+}
+
+/// Stores a new task as a subtask of the project `project`, returning its id. An unfinished task
+/// unfinishes the projects it is added to.
+pub fn add_subtask(project: i32, task: &mut Task) -> i32 {
+    let id = API_STATE.with(|s| store_task(&mut s.lock().unwrap().storage, task, Some(project)));
+    notify(id, Lifecycle::Created);
+    if !task.inner.finished() {
+        tree::roll_up(project);
+    }
+    id
+}
+
+pub fn get_event(id: i32) -> Option<EventType> {
+    API_STATE.with(|s| {
+        let storage = &s.lock().unwrap().storage;
+        storage.get_event(id).map(|r| load(storage, &r))
+    })
+}
+
+/// The task `id`, failing for `method` if there is no such event or it isn't a task
+pub(crate) fn get_task_checked(method: &str, id: i32) -> error::Result<Task> {
+    match get_event(id) {
+        Some(EventType::Task(t)) => Ok(t),
+        Some(e) => Err(error::Error {
+            method: method.into(),
+            kind: ErrorKind::WrongEventKind {
+                id,
+                expected: TASK_KIND.into(),
+                found: e.kind().into(),
+            },
+        }),
+        None => Err(error::Error {
+            method: method.into(),
+            kind: ErrorKind::InvalidEventId(id),
+        }),
+    }
+}
+
+fn get_all_of_kind(kind: &str) -> Vec<EventType> {
+    API_STATE.with(|s| {
+        let storage = &s.lock().unwrap().storage;
+        storage
+            .get_events_of_kind(kind)
+            .iter()
+            .map(|r| load(storage, r))
+            .collect()
+    })
+}
+
+pub fn get_events() -> Vec<Event> {
+    get_all_of_kind(EVENT_KIND)
+        .into_iter()
+        .filter_map(|e| match e {
+            EventType::Event(e) => Some(e),
+            _ => None,
+        })
+        .collect()
+}
+
+/// All tasks, including the ones belonging to projects
+pub fn get_tasks() -> Vec<Task> {
+    get_all_of_kind(TASK_KIND)
+        .into_iter()
+        .filter_map(|e| match e {
+            EventType::Task(t) => Some(t),
+            _ => None,
+        })
+        .collect()
+}
+
+pub fn get_projects() -> Vec<Project> {
+    get_all_of_kind(PROJECT_KIND)
+        .into_iter()
+        .filter_map(|e| match e {
+            EventType::Project(p) => Some(p),
+            _ => None,
+        })
+        .collect()
+}
+
+pub fn set_event_prop<S1, S2>(id: i32, key: S1, val: S2)
+where
+    S1: AsRef<str>,
+    S2: AsRef<str>,
+{
+    API_STATE.with(|s| s.lock().unwrap().storage.set_event_prop(id, key, val));
+}
+
+/// Removes the event, task or project `id` from storage, along with everything inside it
+pub fn delete_event(id: i32) -> error::Result<()> {
+    if get_event(id).is_none() {
+        return Err(error::Error {
+            method: "delete_event".into(),
+            kind: ErrorKind::InvalidEventId(id),
+        });
+    }
+    let mut ids = subtree(id);
+    ids.push(id);
+    API_STATE.with(|s| {
+        let storage = &mut s.lock().unwrap().storage;
+        for id in ids {
+            storage.delete_event(id);
+        }
+    });
+    Ok(())
+}
+
+/// Marks the event `id` as finished. Finishing it again does nothing. Projects whose contents
+/// are all finished by this are finished along with it, up the hierarchy.
+pub fn finish_event(id: i32) {
+    let record = API_STATE.with(|s| {
+        let storage = &mut s.lock().unwrap().storage;
+        let record = storage.get_event(id);
+        storage.set_finished(id, true);
+        record
+    });
+    let record = match record {
+        Some(r) if !r.finished => r,
+        _ => return,
+    };
+    notify(id, Lifecycle::Finish);
+    if let Some(parent) = record.parent {
+        tree::roll_up(parent);
+    }
+}
+
+/// Sets or removes the recurrence of the event `id`, replacing any earlier exceptions, and gives
+/// back the other events it now overlaps, subject to the conflict policy
+pub fn set_recurrence(id: i32, recurrence: Option<&Recurrence>) -> error::Result<Vec<Conflict>> {
+    let conflicts = match get_event(id) {
+        Some(EventType::Event(mut event)) => {
+            event.set_recurrence(recurrence.cloned());
+            check_new_event("set_recurrence", &event)?
+        }
+        _ => Vec::new(),
+    };
+    API_STATE.with(|s| {
+        let storage = &mut s.lock().unwrap().storage;
+        storage.clear_recurrence_exceptions(id);
+        store_recurrence(storage, id, recurrence);
+    });
+    Ok(conflicts)
+}
+
+/// Replaces the name, priority, time and recurrence of the stored event `id` by those of `event`,
+/// giving back the other events it now overlaps, subject to the conflict policy
+pub fn update_event(id: i32, event: &Event) -> error::Result<Vec<Conflict>> {
+    let mut updated = event.clone();
+    updated.inner.id = Some(id);
+    let conflicts = check_new_event("update_event", &updated)?;
+    API_STATE.with(|s| {
+        let storage = &mut s.lock().unwrap().storage;
+        storage.update_event(id, event.inner.name(), event.inner.priority().into());
+        storage.clear_intervals(id);
+        store_interval(storage, id, &event.interval);
+        storage.clear_recurrence_exceptions(id);
+        store_recurrence(storage, id, event.recurrence.as_ref());
+    });
+    Ok(conflicts)
+}
+
+/// Replaces the name, priority, deadline and finished state of the stored task `id` by those of
+/// `task`. Its sessions and estimate are kept, and the projects containing it are finished or
+/// unfinished to match.
+pub fn update_task(id: i32, task: &Task) {
+    let parent = API_STATE.with(|s| {
+        let storage = &mut s.lock().unwrap().storage;
+        storage.update_event(id, task.inner.name(), task.inner.priority().into());
+        storage.set_deadline(id, task.deadline.map(|d| d.naive_utc()));
+        if !task.inner.finished() {
+            storage.set_finished(id, false);
+        }
+        storage.get_event(id).and_then(|r| r.parent)
+    });
+    if task.inner.finished() {
+        finish_event(id);
+    } else if let Some(parent) = parent {
+        tree::roll_up(parent);
+    }
+}
+
+/// Removes the occurrence of the recurring event `id` that would start at `original`
+pub fn skip_occurrence(id: i32, original: DateTime<Local>) -> error::Result<()> {
+    check_occurrence("skip", id, original)?;
+    API_STATE.with(|s| {
+        s.lock()
+            .unwrap()
+            .storage
+            .set_recurrence_exception(id, original.naive_utc(), None)
+    });
+    Ok(())
+}
+
+/// Moves the occurrence of the recurring event `id` that would start at `original` to `interval`,
+/// giving back the other events it now overlaps, subject to the conflict policy
+pub fn reschedule_occurrence(
+    id: i32,
+    original: DateTime<Local>,
+    interval: &Interval,
+) -> error::Result<Vec<Conflict>> {
+    let mut moved = check_occurrence("reschedule", id, original)?;
+    moved.set_recurrence(None);
+    moved.interval = *interval;
+    let conflicts = check_new_event("reschedule", &moved)?;
+    API_STATE.with(|s| {
+        s.lock().unwrap().storage.set_recurrence_exception(
+            id,
+            original.naive_utc(),
+            Some((interval.start.naive_utc(), interval.length.num_seconds())),
+        )
+    });
+    Ok(conflicts)
+}
+
+/// The recurring event `id`, failing for `method` unless one of its occurrences starts at
+/// `original`
+fn check_occurrence(method: &str, id: i32, original: DateTime<Local>) -> error::Result<Event> {
+    let kind = match get_event(id) {
+        None => ErrorKind::InvalidEventId(id),
+        Some(EventType::Event(e)) if e.recurrence().is_some() => {
+            if e.is_occurrence(original) {
+                return Ok(e);
+            }
+            ErrorKind::NotAnOccurrence(original)
+        }
+        Some(_) => ErrorKind::NotRecurring(id),
+    };
+    Err(error::Error {
+        method: method.into(),
+        kind,
+    })
+}
+
+/// Records a finished work session on the task `id`
+pub fn add_session(id: i32, session: &Interval) {
+    API_STATE.with(|s| store_interval(&mut s.lock().unwrap().storage, id, session));
+}
+
+/// Fails for `method` if `estimate` is negative
+pub(crate) fn check_estimate(method: &str, estimate: Option<Duration>) -> error::Result<()> {
+    match estimate {
+        Some(e) if e < Duration::zero() => Err(error::Error {
+            method: method.into(),
+            kind: ErrorKind::InvalidField {
+                field: "estimate".into(),
+                message: format!("{} is negative", time::format_duration(&e)),
+            },
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// Sets or removes the estimated effort of the task `id`
+pub fn set_estimate(id: i32, estimate: Option<Duration>) -> error::Result<()> {
+    check_estimate("set_estimate", estimate)?;
+    API_STATE.with(|s| {
+        s.lock()
+            .unwrap()
+            .storage
+            .set_estimate(id, estimate.map(|e| e.num_seconds()))
+    });
+    Ok(())
 }
 
-pub enum EventType<'lua> {
-    Event(Event<'lua>),
-    Task(Task<'lua>),
-    Project(Project<'lua>),
+/// Sets or removes the deadline of the task or project `id`
+pub fn set_deadline(id: i32, deadline: Option<DateTime<Local>>) {
+    API_STATE.with(|s| {
+        s.lock()
+            .unwrap()
+            .storage
+            .set_deadline(id, deadline.map(|d| d.naive_utc()))
+    });
 }
@@ -0,0 +1,75 @@
+//! Logs as JSON, to back them up or move them to another database. Events and tasks have
+//! iCalendar for that instead (see `ical`).
+use std::collections::{HashMap, HashSet};
+
+use chrono::{DateTime, Local, TimeZone};
+
+use super::{
+    error::*,
+    log::{get_props_for, query_logs, LogQuery},
+    state::API_STATE,
+};
+
+/// A log as it is exported. Only the name is needed to import one: the time defaults to the time
+/// of the import, and the rest to nothing.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogRecord {
+    pub name: String,
+    #[serde(default)]
+    pub desc: String,
+    #[serde(default)]
+    pub time: Option<DateTime<Local>>,
+    #[serde(default)]
+    pub props: HashMap<String, String>,
+}
+
+/// The logs matching `query` as a JSON array of `LogRecord`s, oldest first
+pub fn export_logs(query: &LogQuery) -> String {
+    let mut logs = query_logs(query);
+    logs.sort_by_key(|log| log.time);
+    let records: Vec<LogRecord> = logs
+        .into_iter()
+        .map(|log| LogRecord {
+            props: get_props_for(log.id),
+            time: Some(Local.from_utc_datetime(&log.time)),
+            name: log.name,
+            desc: log.desc,
+        })
+        .collect();
+    serde_json::to_string_pretty(&records).unwrap()
+}
+
+/// Adds the logs of `text`, a JSON array of `LogRecord`s, returning the ids of the ones added.
+/// Logs with the same time and name as one already stored are skipped, so importing the same
+/// file twice adds nothing the second time. Nothing is added if any of them is invalid.
+pub fn import_logs(text: &str) -> Result<Vec<i32>> {
+    let records: Vec<LogRecord> = serde_json::from_str(text).map_err(|e| Error {
+        method: "import_logs".into(),
+        kind: ErrorKind::InvalidJson(e.to_string()),
+    })?;
+    API_STATE.with(|s| {
+        let storage = &mut s.lock().unwrap().storage;
+        let mut stored: HashSet<_> = storage
+            .get_logs()
+            .into_iter()
+            .map(|log| (log.time, log.name))
+            .collect();
+        let mut ids = Vec::new();
+        for record in records.iter() {
+            if let Some(time) = record.time {
+                if !stored.insert((time.naive_utc(), record.name.clone())) {
+                    continue;
+                }
+            }
+            let id = storage.add_log(&record.name, &record.desc);
+            if let Some(time) = record.time {
+                storage.set_log_time(id, time.naive_utc());
+            }
+            for (key, val) in record.props.iter() {
+                storage.set_prop(id, key, val);
+            }
+            ids.push(id);
+        }
+        Ok(ids)
+    })
+}
@@ -0,0 +1,490 @@
+//! Focus sessions: cycles of work blocks and breaks on a task, pomodoro style. Each work block
+//! that ends is recorded as a session of the task, and interruptions during a session are logged
+//! with their own log type, so that focus can be reported on per day.
+//!
+//! The session is driven by the clock: `advance_focus` moves it through the phases that ended
+//! since it was last called, so it doesn't matter how often that happens. The running session
+//! lives in storage, so it carries on from one command to the next. A session nobody advances
+//! for a while, say one left running overnight, ends on its own instead of recording work blocks
+//! that never happened.
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
+use std::fmt;
+
+use chrono::{DateTime, Duration, Local, NaiveDate, TimeZone};
+
+use super::{error::*, event::*, log::*, state::API_STATE, time};
+use crate::storage::model::FocusRecord;
+
+/// Log type of a finished work block
+pub const FOCUS_LOG_TYPE: &str = "focus";
+/// Log type of an interruption during a focus session
+pub const INTERRUPTION_LOG_TYPE: &str = "interruption";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FocusConfig {
+    pub work: Duration,
+    pub short_break: Duration,
+    pub long_break: Duration,
+    /// A long break follows every this many work blocks, at least 1
+    pub long_break_every: u32,
+    /// The session ends once more than this many work blocks ended since it was last advanced.
+    /// `None` to let it run until stopped.
+    pub max_idle_blocks: Option<u32>,
+}
+
+impl Default for FocusConfig {
+    fn default() -> Self {
+        Self {
+            work: Duration::minutes(25),
+            short_break: Duration::minutes(5),
+            long_break: Duration::minutes(15),
+            long_break_every: 4,
+            max_idle_blocks: Some(4),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FocusPhase {
+    Work,
+    ShortBreak,
+    LongBreak,
+}
+
+impl FocusPhase {
+    pub fn name(&self) -> &'static str {
+        match self {
+            FocusPhase::Work => "work",
+            FocusPhase::ShortBreak => "short break",
+            FocusPhase::LongBreak => "long break",
+        }
+    }
+}
+
+impl TryFrom<&str> for FocusPhase {
+    type Error = ();
+
+    fn try_from(name: &str) -> std::result::Result<Self, Self::Error> {
+        match name {
+            "work" => Ok(FocusPhase::Work),
+            "short break" => Ok(FocusPhase::ShortBreak),
+            "long break" => Ok(FocusPhase::LongBreak),
+            _ => Err(()),
+        }
+    }
+}
+
+impl fmt::Display for FocusPhase {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// A running focus session on the task `task`
+#[derive(Debug, Clone)]
+pub struct FocusSession {
+    pub task: i32,
+    pub config: FocusConfig,
+    pub phase: FocusPhase,
+    pub phase_start: DateTime<Local>,
+    /// Work blocks finished so far
+    pub blocks: u32,
+    pub interruptions: u32,
+}
+
+impl FocusSession {
+    pub fn phase_length(&self) -> Duration {
+        match self.phase {
+            FocusPhase::Work => self.config.work,
+            FocusPhase::ShortBreak => self.config.short_break,
+            FocusPhase::LongBreak => self.config.long_break,
+        }
+    }
+
+    pub fn phase_end(&self) -> DateTime<Local> {
+        self.phase_start + self.phase_length()
+    }
+
+    /// How much of the current phase is left at `now`
+    pub fn remaining(&self, now: DateTime<Local>) -> Duration {
+        (self.phase_end() - now).max(Duration::zero())
+    }
+}
+
+/// A phase that started, and the work block that ended with it, if any
+#[derive(Debug, Clone)]
+pub struct FocusTransition {
+    pub phase: FocusPhase,
+    pub start: DateTime<Local>,
+    pub block: Option<Interval>,
+}
+
+/// The focus on one day
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FocusDay {
+    pub date: NaiveDate,
+    /// Work blocks that ran their full length
+    pub blocks: u32,
+    /// Time spent in work blocks, including ones cut short
+    pub focused: Duration,
+    pub interruptions: u32,
+    /// Time in work blocks by task
+    pub tasks: HashMap<i32, Duration>,
+}
+
+impl FocusDay {
+    fn new(date: NaiveDate) -> Self {
+        Self {
+            date,
+            blocks: 0,
+            focused: Duration::zero(),
+            interruptions: 0,
+            tasks: HashMap::new(),
+        }
+    }
+}
+
+fn not_running(method: &str) -> Error {
+    Error {
+        method: method.into(),
+        kind: ErrorKind::FocusNotRunning,
+    }
+}
+
+/// Defines the log types of focus sessions, unless a script defined them already
+fn add_focus_log_types() {
+    let attr = |default: Option<&str>| LogAttr::new(false, default.map(str::to_string));
+    if get_log_type(FOCUS_LOG_TYPE).is_none() {
+        let mut attrs = LogAttrs::new();
+        for key in ["task", "start", "end", "length", "complete"].iter() {
+            attrs.insert(key.to_string(), attr(None));
+        }
+        add_log_type(LogType::new(FOCUS_LOG_TYPE, attrs));
+    }
+    if get_log_type(INTERRUPTION_LOG_TYPE).is_none() {
+        let mut attrs = LogAttrs::new();
+        attrs.insert("task".into(), attr(None));
+        attrs.insert("time".into(), attr(None));
+        attrs.insert("phase".into(), attr(None));
+        attrs.insert("reason".into(), attr(Some("")));
+        add_log_type(LogType::new(INTERRUPTION_LOG_TYPE, attrs));
+    }
+}
+
+fn task_name(id: i32) -> String {
+    get_event(id).map_or_else(String::new, |e| e.common().name().to_string())
+}
+
+/// Logs `name` with the log type `typ`. If a script redefined the type with attributes that
+/// aren't filled in here, it is logged with just `props` and the type.
+fn log_with_type(name: String, desc: &str, typ: &str, mut props: HashMap<String, String>) {
+    if add_log_with_type(&name, desc, Some(typ.into()), props.clone(), false).is_err() {
+        props.insert(TYPE_PROP.to_string(), typ.to_string());
+        add_log_with_props(name, desc, &props);
+    }
+}
+
+/// Records the work block `block` of `session` as a session of its task and logs it
+fn record_block(session: &FocusSession, block: &Interval, complete: bool) {
+    add_session(session.task, block);
+    let mut props = HashMap::new();
+    props.insert("task".to_string(), session.task.to_string());
+    props.insert("start".to_string(), time::format_datetime(&block.start()));
+    props.insert("end".to_string(), time::format_datetime(&block.end()));
+    props.insert("length".to_string(), time::format_duration(&block.length()));
+    props.insert("complete".to_string(), complete.to_string());
+    log_with_type(
+        format!("focused on task {}", task_name(session.task)),
+        "",
+        FOCUS_LOG_TYPE,
+        props,
+    );
+}
+
+fn to_record(session: &FocusSession) -> FocusRecord {
+    FocusRecord {
+        id: session.task,
+        work: session.config.work.num_seconds(),
+        short_break: session.config.short_break.num_seconds(),
+        long_break: session.config.long_break.num_seconds(),
+        long_break_every: session.config.long_break_every as i32,
+        max_idle_blocks: session.config.max_idle_blocks.map(|n| n as i32),
+        phase: session.phase.name().to_string(),
+        phase_start: session.phase_start.naive_utc(),
+        blocks: session.blocks as i32,
+        interruptions: session.interruptions as i32,
+    }
+}
+
+fn from_record(record: FocusRecord) -> FocusSession {
+    FocusSession {
+        task: record.id,
+        config: FocusConfig {
+            work: Duration::seconds(record.work),
+            short_break: Duration::seconds(record.short_break),
+            long_break: Duration::seconds(record.long_break),
+            long_break_every: record.long_break_every as u32,
+            max_idle_blocks: record.max_idle_blocks.map(|n| n as u32),
+        },
+        phase: FocusPhase::try_from(record.phase.as_str()).unwrap_or(FocusPhase::Work),
+        phase_start: Local.from_utc_datetime(&record.phase_start),
+        blocks: record.blocks as u32,
+        interruptions: record.interruptions as u32,
+    }
+}
+
+fn set_focus(session: &FocusSession) {
+    API_STATE.with(|s| s.lock().unwrap().storage.set_focus(&to_record(session)));
+}
+
+pub fn get_focus() -> Option<FocusSession> {
+    API_STATE.with(|s| s.lock().unwrap().storage.get_focus().map(from_record))
+}
+
+/// Clears the running `session` and logs that it ended, `why` it did if not stopped
+fn end_focus(session: &FocusSession, why: Option<&str>) {
+    API_STATE.with(|s| s.lock().unwrap().storage.clear_focus());
+    let mut desc = format!(
+        "{} blocks, {} interruptions",
+        session.blocks, session.interruptions
+    );
+    if let Some(why) = why {
+        desc = format!("{}; {}", why, desc);
+    }
+    add_log(
+        format!("stopped focusing on task {}", task_name(session.task)),
+        desc,
+    );
+}
+
+/// Starts a focus session on the task `task` at `now`, beginning with a work block. A session
+/// that was running is stopped first.
+pub fn start_focus(task: i32, config: FocusConfig, now: DateTime<Local>) -> Result<FocusSession> {
+    get_task_checked("start_focus", task)?;
+    for (field, length) in [
+        ("work", config.work),
+        ("short_break", config.short_break),
+        ("long_break", config.long_break),
+    ]
+    .iter()
+    {
+        if *length <= Duration::zero() {
+            return Err(Error {
+                method: "start_focus".into(),
+                kind: ErrorKind::InvalidField {
+                    field: field.to_string(),
+                    message: "must be longer than 0s".into(),
+                },
+            });
+        }
+    }
+    if config.long_break_every == 0 {
+        return Err(Error {
+            method: "start_focus".into(),
+            kind: ErrorKind::InvalidField {
+                field: "long_break_every".into(),
+                message: "must be at least 1".into(),
+            },
+        });
+    }
+    if get_focus().is_some() {
+        stop_focus(now)?;
+    }
+    add_focus_log_types();
+    let session = FocusSession {
+        task,
+        config,
+        phase: FocusPhase::Work,
+        phase_start: now,
+        blocks: 0,
+        interruptions: 0,
+    };
+    set_focus(&session);
+    add_log(format!("started focusing on task {}", task_name(task)), "");
+    Ok(session)
+}
+
+/// Moves the running session through the phases that ended by `now`, recording the work blocks
+/// that ended. Returns the phases that started, in order. If more work blocks than the session's
+/// `max_idle_blocks` ended, only that many are recorded and the session ends after the last of
+/// them.
+pub fn advance_focus(now: DateTime<Local>) -> Vec<FocusTransition> {
+    let mut session = match get_focus() {
+        Some(session) => session,
+        None => return Vec::new(),
+    };
+    let mut transitions = Vec::new();
+    let mut idle_blocks = 0;
+    while session.phase_end() <= now {
+        let end = session.phase_end();
+        let block = match session.phase {
+            FocusPhase::Work => {
+                if session.config.max_idle_blocks == Some(idle_blocks) {
+                    end_focus(&session, Some("left running"));
+                    return transitions;
+                }
+                idle_blocks += 1;
+                let block = Interval::from_start(session.phase_start, session.config.work);
+                record_block(&session, &block, true);
+                session.blocks += 1;
+                session.phase = if session.blocks % session.config.long_break_every == 0 {
+                    FocusPhase::LongBreak
+                } else {
+                    FocusPhase::ShortBreak
+                };
+                Some(block)
+            }
+            FocusPhase::ShortBreak | FocusPhase::LongBreak => {
+                session.phase = FocusPhase::Work;
+                None
+            }
+        };
+        session.phase_start = end;
+        transitions.push(FocusTransition {
+            phase: session.phase,
+            start: end,
+            block,
+        });
+    }
+    set_focus(&session);
+    transitions
+}
+
+/// Logs an interruption of the running session at `now`, for `reason`
+pub fn interrupt_focus<S: AsRef<str>>(reason: S, now: DateTime<Local>) -> Result<()> {
+    advance_focus(now);
+    let session = get_focus().ok_or_else(|| not_running("interrupt_focus"))?;
+    let mut props = HashMap::new();
+    props.insert("task".to_string(), session.task.to_string());
+    props.insert("time".to_string(), time::format_datetime(&now));
+    props.insert("phase".to_string(), session.phase.name().to_string());
+    props.insert("reason".to_string(), reason.as_ref().to_string());
+    log_with_type(
+        format!("interrupted focus on task {}", task_name(session.task)),
+        reason.as_ref(),
+        INTERRUPTION_LOG_TYPE,
+        props,
+    );
+    set_focus(&FocusSession {
+        interruptions: session.interruptions + 1,
+        ..session
+    });
+    Ok(())
+}
+
+/// Stops the running session at `now`. A work block that was cut short is still recorded, and
+/// returned.
+pub fn stop_focus(now: DateTime<Local>) -> Result<Option<Interval>> {
+    advance_focus(now);
+    let session = get_focus().ok_or_else(|| not_running("stop_focus"))?;
+    let partial = match session.phase {
+        FocusPhase::Work if now > session.phase_start => {
+            let block = Interval::from_start(session.phase_start, now - session.phase_start);
+            record_block(&session, &block, false);
+            Some(block)
+        }
+        _ => None,
+    };
+    end_focus(&session, None);
+    Ok(partial)
+}
+
+/// The focus of every day in `window` that had any, from the logged work blocks and
+/// interruptions, in order of the days
+pub fn focus_stats(window: &Interval) -> Vec<FocusDay> {
+    let mut days: BTreeMap<NaiveDate, FocusDay> = BTreeMap::new();
+    for log in get_logs() {
+        let props = get_props_for(log.id);
+        let task = props.get("task").and_then(|t| t.parse::<i32>().ok());
+        match props.get(TYPE_PROP).map(String::as_str) {
+            Some(FOCUS_LOG_TYPE) => {
+                let start = props.get("start").and_then(time::parse_datetime);
+                let end = props.get("end").and_then(time::parse_datetime);
+                let (start, end, task) = match (start, end, task) {
+                    (Some(start), Some(end), Some(task)) => (start, end, task),
+                    _ => continue,
+                };
+                if !window.contains(start) {
+                    continue;
+                }
+                let date = start.naive_local().date();
+                let day = days.entry(date).or_insert_with(|| FocusDay::new(date));
+                if props.get("complete").map(String::as_str) == Some("true") {
+                    day.blocks += 1;
+                }
+                day.focused = day.focused + (end - start);
+                let spent = day.tasks.entry(task).or_insert_with(Duration::zero);
+                *spent = *spent + (end - start);
+            }
+            Some(INTERRUPTION_LOG_TYPE) => {
+                let at = match props.get("time").and_then(time::parse_datetime) {
+                    Some(at) if window.contains(at) => at,
+                    _ => continue,
+                };
+                let date = at.naive_local().date();
+                days.entry(date)
+                    .or_insert_with(|| FocusDay::new(date))
+                    .interruptions += 1;
+            }
+            _ => {}
+        }
+    }
+    days.into_values().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task() -> i32 {
+        add_task(&mut Task::new(EventCommon::new(
+            "write".into(),
+            Priority::Medium,
+        )))
+    }
+
+    fn sessions(task: i32) -> Vec<Interval> {
+        get_task_checked("test", task).unwrap().sessions().to_vec()
+    }
+
+    #[test]
+    fn stores_the_running_session() {
+        let (task, t0) = (task(), Local::now());
+        let config = FocusConfig {
+            long_break_every: 2,
+            max_idle_blocks: None,
+            ..FocusConfig::default()
+        };
+        start_focus(task, config, t0).unwrap();
+        interrupt_focus("phone", t0 + Duration::minutes(10)).unwrap();
+        advance_focus(t0 + Duration::minutes(56));
+
+        let session = get_focus().unwrap();
+        assert_eq!(session.task, task);
+        assert_eq!(session.config, config);
+        assert_eq!(session.phase, FocusPhase::LongBreak);
+        assert_eq!(session.phase_start, t0 + Duration::minutes(55));
+        assert_eq!(session.blocks, 2);
+        assert_eq!(session.interruptions, 1);
+
+        stop_focus(t0 + Duration::minutes(60)).unwrap();
+        assert!(get_focus().is_none());
+        assert_eq!(sessions(task).len(), 2);
+    }
+
+    #[test]
+    fn ends_sessions_left_running() {
+        let (task, t0) = (task(), Local::now());
+        start_focus(task, FocusConfig::default(), t0).unwrap();
+        advance_focus(t0 + Duration::minutes(30));
+        assert_eq!(sessions(task).len(), 1);
+
+        // Only four of the blocks that would have ended overnight are recorded
+        let transitions = advance_focus(t0 + Duration::hours(12));
+        assert_eq!(transitions.iter().filter(|t| t.block.is_some()).count(), 4);
+        assert!(get_focus().is_none());
+        assert_eq!(sessions(task).len(), 5);
+        assert!(stop_focus(t0 + Duration::hours(12)).is_err());
+    }
+}
@@ -0,0 +1,258 @@
+//! Habits: things to do a number of times a day or a week, done by adding logs of a given log
+//! type. A habit is kept in a period (a day or a week starting on Monday) when enough matching
+//! logs fall into it, and a streak is a run of kept periods.
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{Datelike, Duration, Local, NaiveDate, TimeZone};
+
+use super::{error::*, log::*, state::API_STATE};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HabitPeriod {
+    Day,
+    /// From Monday to Sunday
+    Week,
+}
+
+impl HabitPeriod {
+    pub fn name(&self) -> &'static str {
+        match self {
+            HabitPeriod::Day => "day",
+            HabitPeriod::Week => "week",
+        }
+    }
+
+    /// The first day of the period containing `date`
+    pub fn start_of(&self, date: NaiveDate) -> NaiveDate {
+        match self {
+            HabitPeriod::Day => date,
+            HabitPeriod::Week => {
+                date - Duration::days(date.weekday().num_days_from_monday() as i64)
+            }
+        }
+    }
+
+    pub fn length(&self) -> Duration {
+        match self {
+            HabitPeriod::Day => Duration::days(1),
+            HabitPeriod::Week => Duration::weeks(1),
+        }
+    }
+}
+
+impl fmt::Display for HabitPeriod {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for HabitPeriod {
+    type Err = ErrorKind;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "day" => Ok(HabitPeriod::Day),
+            "week" => Ok(HabitPeriod::Week),
+            _ => Err(ErrorKind::InvalidField {
+                field: "per".into(),
+                message: format!("'{}' is not one of 'day' or 'week'", s),
+            }),
+        }
+    }
+}
+
+/// Doing what `log_type` logs `times` times per `per`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Habit {
+    pub name: String,
+    pub log_type: String,
+    pub times: u32,
+    pub per: HabitPeriod,
+}
+
+/// How a habit is going on some day
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HabitStatus {
+    pub habit: Habit,
+    /// Matching logs in the current period
+    pub done: u32,
+    /// Kept periods in a row up to the current one. The current period only counts once it's
+    /// kept, and doesn't break the streak before it's over.
+    pub current_streak: u32,
+    pub best_streak: u32,
+    /// The first day of every period that wasn't kept, from the first matching log on, not
+    /// counting the current one
+    pub missed: Vec<NaiveDate>,
+}
+
+/// How `habit` is going on `today`, given the days of its matching logs
+pub fn habit_status(habit: &Habit, done: &[NaiveDate], today: NaiveDate) -> HabitStatus {
+    let mut counts: BTreeMap<NaiveDate, u32> = BTreeMap::new();
+    for date in done.iter().filter(|d| **d <= today) {
+        *counts.entry(habit.per.start_of(*date)).or_default() += 1;
+    }
+    let kept = |period: NaiveDate| counts.get(&period).copied().unwrap_or(0) >= habit.times;
+    let current = habit.per.start_of(today);
+
+    let mut run = 0;
+    let mut best = 0;
+    let mut missed = Vec::new();
+    if let Some(&first) = counts.keys().next() {
+        let mut period = first;
+        while period < current {
+            if kept(period) {
+                run += 1;
+                best = best.max(run);
+            } else {
+                run = 0;
+                missed.push(period);
+            }
+            period += habit.per.length();
+        }
+    }
+    let current_streak = if kept(current) { run + 1 } else { run };
+    HabitStatus {
+        habit: habit.clone(),
+        done: counts.get(&current).copied().unwrap_or(0),
+        current_streak,
+        best_streak: best.max(current_streak),
+        missed,
+    }
+}
+
+/// Defines `habit`, replacing the habit of the same name. Fails if its log type isn't defined.
+pub fn add_habit(habit: Habit) -> Result<()> {
+    if habit.times == 0 {
+        return Err(Error {
+            method: "add_habit".into(),
+            kind: ErrorKind::InvalidField {
+                field: "times".into(),
+                message: "must be at least 1".into(),
+            },
+        });
+    }
+    if get_log_type(&habit.log_type).is_none() {
+        return Err(Error {
+            method: "add_habit".into(),
+            kind: ErrorKind::InvalidLogType(habit.log_type),
+        });
+    }
+    API_STATE.with(|s| s.lock().unwrap().habits.insert(habit.name.clone(), habit));
+    Ok(())
+}
+
+pub fn get_habit<S: AsRef<str>>(name: S) -> Option<Habit> {
+    API_STATE.with(|s| s.lock().unwrap().habits.get(name.as_ref()).cloned())
+}
+
+/// All habits, by name
+pub fn get_habits() -> Vec<Habit> {
+    let mut habits: Vec<Habit> =
+        API_STATE.with(|s| s.lock().unwrap().habits.values().cloned().collect());
+    habits.sort_by(|a, b| a.name.cmp(&b.name));
+    habits
+}
+
+/// The local days of the logs added with the type `log_type`
+pub fn log_days<S: AsRef<str>>(log_type: S) -> Vec<NaiveDate> {
+    let query = LogQuery {
+        log_type: Some(log_type.as_ref().to_string()),
+        ..LogQuery::default()
+    };
+    query_logs(&query)
+        .into_iter()
+        .map(|log| Local.from_utc_datetime(&log.time).naive_local().date())
+        .collect()
+}
+
+/// How every habit is going today, by name
+pub fn habits_today() -> Vec<HabitStatus> {
+    let today = Local::today().naive_local();
+    get_habits()
+        .iter()
+        .map(|h| habit_status(h, &log_days(&h.log_type), today))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    fn dates(days: &[&str]) -> Vec<NaiveDate> {
+        days.iter().map(|d| date(d)).collect()
+    }
+
+    fn habit(times: u32, per: HabitPeriod) -> Habit {
+        Habit {
+            name: "run".into(),
+            log_type: "run".into(),
+            times,
+            per,
+        }
+    }
+
+    #[test]
+    fn counts_daily_streaks() {
+        let habit = habit(1, HabitPeriod::Day);
+        let done = dates(&[
+            "2026-01-01",
+            "2026-01-02",
+            "2026-01-03",
+            "2026-01-05",
+            "2026-01-06",
+        ]);
+        let status = habit_status(&habit, &done, date("2026-01-07"));
+        assert_eq!(status.done, 0);
+        // Today isn't over, so it doesn't break the streak yet
+        assert_eq!(status.current_streak, 2);
+        assert_eq!(status.best_streak, 3);
+        assert_eq!(status.missed, dates(&["2026-01-04"]));
+
+        let status = habit_status(&habit, &done, date("2026-01-06"));
+        assert_eq!(status.done, 1);
+        assert_eq!(status.current_streak, 2);
+
+        let status = habit_status(&habit, &done, date("2026-01-08"));
+        assert_eq!(status.current_streak, 0);
+        assert_eq!(status.missed, dates(&["2026-01-04", "2026-01-07"]));
+    }
+
+    #[test]
+    fn counts_weekly_periods_from_monday() {
+        let habit = habit(2, HabitPeriod::Week);
+        // 2026-01-05 and 2026-01-19 are Mondays
+        let done = dates(&[
+            "2026-01-05",
+            "2026-01-11",
+            "2026-01-12",
+            "2026-01-19",
+            "2026-01-20",
+            "2026-01-21",
+        ]);
+        let status = habit_status(&habit, &done, date("2026-01-21"));
+        assert_eq!(status.done, 3);
+        assert_eq!(status.current_streak, 1);
+        assert_eq!(status.best_streak, 1);
+        assert_eq!(status.missed, dates(&["2026-01-12"]));
+    }
+
+    #[test]
+    fn ignores_logs_after_today() {
+        let habit = habit(1, HabitPeriod::Day);
+        let done = dates(&["2026-01-02", "2026-01-03"]);
+        let status = habit_status(&habit, &done, date("2026-01-02"));
+        assert_eq!(status.done, 1);
+        assert_eq!(status.current_streak, 1);
+        assert_eq!(status.best_streak, 1);
+
+        let status = habit_status(&habit, &[], date("2026-01-02"));
+        assert_eq!(status.current_streak, 0);
+        assert!(status.missed.is_empty());
+    }
+}
@@ -0,0 +1,823 @@
+//! Exchange of events and tasks with other calendar programs as iCalendar (RFC 5545) data.
+//! Events become VEVENTs, with their recurrence as an RRULE plus EXDATEs and overriding VEVENTs
+//! for the occurrences that were removed or moved, and tasks become VTODOs. Projects have no
+//! iCalendar counterpart and are left out.
+//!
+//! Items keep the UID they were imported with, so importing the same data again updates them
+//! instead of adding copies. Items exported for the first time are given a random UID, which is
+//! stored so that later exports use the same one.
+use std::collections::HashMap;
+use std::convert::TryFrom;
+
+use chrono::{DateTime, Duration, Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use uuid::Uuid;
+
+use super::{error::*, event::*, recurrence::*, state::API_STATE, time};
+
+const PRODID: &str = "-//sched//sched//EN";
+const UID_SUFFIX: &str = "@sched";
+/// Content lines longer than this many octets are folded
+const MAX_LINE: usize = 75;
+
+/// What an import did: the ids of the created and updated items, and why the others were skipped
+#[derive(Debug, Clone, Default)]
+pub struct ImportSummary {
+    pub created: Vec<i32>,
+    pub updated: Vec<i32>,
+    pub skipped: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+struct Property {
+    name: String,
+    params: Vec<(String, String)>,
+    value: String,
+}
+
+impl Property {
+    fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Component {
+    name: String,
+    props: Vec<Property>,
+    children: Vec<Component>,
+}
+
+impl Component {
+    fn new(name: String) -> Self {
+        Self {
+            name,
+            props: Vec::new(),
+            children: Vec::new(),
+        }
+    }
+
+    fn get(&self, name: &str) -> Option<&Property> {
+        self.props.iter().find(|p| p.name == name)
+    }
+
+    fn text(&self, name: &str) -> Option<String> {
+        self.get(name).map(|p| unescape(&p.value))
+    }
+}
+
+/// The content lines of `text`, with folded lines joined back together
+fn unfold(text: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.split('\n') {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        if line.starts_with(' ') || line.starts_with('\t') {
+            if let Some(last) = lines.last_mut() {
+                last.push_str(&line[1..]);
+                continue;
+            }
+        }
+        if !line.is_empty() {
+            lines.push(line.to_string());
+        }
+    }
+    lines
+}
+
+/// The parts of `s` between the `sep`s outside of double quotes
+fn split_unquoted(s: &str, sep: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut in_quotes = false;
+    let mut start = 0;
+    for (i, c) in s.char_indices() {
+        if c == '"' {
+            in_quotes = !in_quotes;
+        } else if c == sep && !in_quotes {
+            parts.push(&s[start..i]);
+            start = i + 1;
+        }
+    }
+    parts.push(&s[start..]);
+    parts
+}
+
+/// Parses a content line such as `DTSTART;TZID=Europe/Paris:20200810T090000`
+fn parse_property(line: &str) -> Option<Property> {
+    let mut in_quotes = false;
+    let colon = line
+        .char_indices()
+        .find(|&(_, c)| {
+            if c == '"' {
+                in_quotes = !in_quotes;
+            }
+            c == ':' && !in_quotes
+        })?
+        .0;
+    let mut parts = split_unquoted(&line[..colon], ';').into_iter();
+    let name = parts.next()?.trim().to_uppercase();
+    if name.is_empty() {
+        return None;
+    }
+    let params = parts
+        .filter_map(|p| {
+            let eq = p.find('=')?;
+            Some((
+                p[..eq].trim().to_uppercase(),
+                p[eq + 1..].trim_matches('"').to_string(),
+            ))
+        })
+        .collect();
+    Some(Property {
+        name,
+        params,
+        value: line[colon + 1..].to_string(),
+    })
+}
+
+/// The top level components of `text`
+fn parse(text: &str) -> std::result::Result<Vec<Component>, String> {
+    let mut stack = vec![Component::new(String::new())];
+    for line in unfold(text) {
+        let prop = parse_property(&line).ok_or_else(|| format!("invalid line '{}'", line))?;
+        match prop.name.as_str() {
+            "BEGIN" => stack.push(Component::new(prop.value.trim().to_uppercase())),
+            "END" => {
+                let name = prop.value.trim().to_uppercase();
+                if stack.len() < 2 || stack.last().unwrap().name != name {
+                    return Err(format!("unexpected END:{}", name));
+                }
+                let component = stack.pop().unwrap();
+                stack.last_mut().unwrap().children.push(component);
+            }
+            _ => stack.last_mut().unwrap().props.push(prop),
+        }
+    }
+    if stack.len() > 1 {
+        return Err(format!("missing END:{}", stack.last().unwrap().name));
+    }
+    Ok(stack.pop().unwrap().children)
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+fn unescape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') | Some('N') => out.push('\n'),
+            Some(c) => out.push(c),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+/// Parses a DATE-TIME or DATE value. Times in UTC end with `Z`; floating times and times with a
+/// TZID are taken as local time. Dates are midnight of that day.
+fn parse_datetime(value: &str) -> Option<DateTime<Local>> {
+    let value = value.trim();
+    if let Some(utc) = value.strip_suffix('Z') {
+        return NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S")
+            .ok()
+            .map(|t| Local.from_utc_datetime(&t));
+    }
+    let t = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S")
+        .ok()
+        .or_else(|| {
+            NaiveDate::parse_from_str(value, "%Y%m%d")
+                .ok()
+                .map(|d| d.and_hms(0, 0, 0))
+        })?;
+    Local.from_local_datetime(&t).earliest()
+}
+
+fn is_date(prop: &Property) -> bool {
+    prop.param("VALUE") == Some("DATE") || !prop.value.contains('T')
+}
+
+fn format_datetime(t: &DateTime<Local>) -> String {
+    t.with_timezone(&Utc).format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// Parses a DURATION value such as `PT1H30M` or `-P1W`. Durations too long to represent are
+/// rejected.
+fn parse_duration(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    let (negative, rest) = match value.chars().next()? {
+        '-' => (true, &value[1..]),
+        '+' => (false, &value[1..]),
+        _ => (false, value),
+    };
+    let rest = rest.strip_prefix('P')?;
+    let mut total = Duration::zero();
+    let mut number = String::new();
+    let mut in_time = false;
+    let mut any = false;
+    for c in rest.chars() {
+        match c {
+            '0'..='9' => number.push(c),
+            'T' if number.is_empty() && !in_time => in_time = true,
+            _ => {
+                let n: i64 = number.parse().ok()?;
+                number.clear();
+                any = true;
+                let unit = match (c, in_time) {
+                    ('W', false) => 7 * 86400,
+                    ('D', false) => 86400,
+                    ('H', true) => 3600,
+                    ('M', true) => 60,
+                    ('S', true) => 1,
+                    _ => return None,
+                };
+                total = total.checked_add(&time::seconds(n, unit)?)?;
+            }
+        }
+    }
+    if !any || !number.is_empty() {
+        return None;
+    }
+    Some(if negative { -total } else { total })
+}
+
+/// iCalendar priorities go from 1 (highest) to 9 (lowest), with 0 for undefined
+fn to_ical_priority(priority: Priority) -> i32 {
+    10 - priority.level()
+}
+
+fn from_ical_priority(value: Option<&Property>) -> Priority {
+    value
+        .and_then(|p| p.value.trim().parse::<i32>().ok())
+        .filter(|n| (1..=9).contains(n))
+        .and_then(|n| Priority::try_from(10 - n).ok())
+        .unwrap_or_default()
+}
+
+/// Appends the content line `line`, folded so no line is longer than `MAX_LINE` octets
+fn push_line(out: &mut String, line: &str) {
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > MAX_LINE {
+            out.push_str("\r\n ");
+            width = 1;
+        }
+        out.push(c);
+        width += c.len_utf8();
+    }
+    out.push_str("\r\n");
+}
+
+/// The UID the item `id` is exported with. Items without one are given a new random UID, which
+/// is stored
+pub fn uid_of(id: i32) -> String {
+    API_STATE.with(|s| {
+        let mut api_state = s.lock().unwrap();
+        api_state.storage.get_uid(id).unwrap_or_else(|| {
+            let uid = format!("{}{}", Uuid::new_v4(), UID_SUFFIX);
+            api_state.storage.set_uid(id, &uid);
+            uid
+        })
+    })
+}
+
+/// The stored item with the UID `uid`, if any
+fn id_for_uid(uid: &str) -> Option<i32> {
+    API_STATE.with(|s| s.lock().unwrap().storage.get_id_for_uid(uid))
+}
+
+fn write_event(out: &mut String, event: &Event, stamp: &str) {
+    let id = event.common().id().unwrap();
+    let uid = uid_of(id);
+    let interval = event.interval();
+    let header = |out: &mut String| {
+        push_line(out, "BEGIN:VEVENT");
+        push_line(out, &format!("UID:{}", escape(&uid)));
+        push_line(out, &format!("DTSTAMP:{}", stamp));
+        push_line(out, &format!("SUMMARY:{}", escape(event.common().name())));
+        push_line(
+            out,
+            &format!("PRIORITY:{}", to_ical_priority(event.common().priority())),
+        );
+    };
+    header(out);
+    push_line(
+        out,
+        &format!("DTSTART:{}", format_datetime(&interval.start())),
+    );
+    push_line(out, &format!("DTEND:{}", format_datetime(&interval.end())));
+    if let Some(recurrence) = event.recurrence() {
+        push_line(out, &format!("RRULE:{}", recurrence.rule));
+        if !recurrence.exceptions.is_empty() {
+            let dates: Vec<_> = recurrence.exceptions.iter().map(format_datetime).collect();
+            push_line(out, &format!("EXDATE:{}", dates.join(",")));
+        }
+    }
+    push_line(out, "END:VEVENT");
+
+    if let Some(recurrence) = event.recurrence() {
+        let mut overrides: Vec<_> = recurrence.overrides.iter().collect();
+        overrides.sort_by_key(|(original, _)| **original);
+        for (original, moved) in overrides {
+            header(out);
+            push_line(out, &format!("RECURRENCE-ID:{}", format_datetime(original)));
+            push_line(out, &format!("DTSTART:{}", format_datetime(&moved.start())));
+            push_line(out, &format!("DTEND:{}", format_datetime(&moved.end())));
+            push_line(out, "END:VEVENT");
+        }
+    }
+}
+
+fn write_task(out: &mut String, task: &Task, stamp: &str) {
+    let id = task.common().id().unwrap();
+    push_line(out, "BEGIN:VTODO");
+    push_line(out, &format!("UID:{}", escape(&uid_of(id))));
+    push_line(out, &format!("DTSTAMP:{}", stamp));
+    push_line(out, &format!("SUMMARY:{}", escape(task.common().name())));
+    push_line(
+        out,
+        &format!("PRIORITY:{}", to_ical_priority(task.common().priority())),
+    );
+    if let Some(due) = task.deadline() {
+        push_line(out, &format!("DUE:{}", format_datetime(&due)));
+    }
+    if task.common().finished() {
+        push_line(out, "STATUS:COMPLETED");
+        push_line(out, "PERCENT-COMPLETE:100");
+    } else {
+        push_line(out, "STATUS:NEEDS-ACTION");
+        push_line(
+            out,
+            &format!(
+                "PERCENT-COMPLETE:{}",
+                (task.progress() * 100.0).round() as i32
+            ),
+        );
+    }
+    push_line(out, "END:VTODO");
+}
+
+/// A VCALENDAR with `events` and `tasks`
+pub fn export_items(events: &[Event], tasks: &[Task]) -> String {
+    let stamp = format_datetime(&Local::now());
+    let mut out = String::new();
+    push_line(&mut out, "BEGIN:VCALENDAR");
+    push_line(&mut out, "VERSION:2.0");
+    push_line(&mut out, &format!("PRODID:{}", PRODID));
+    for event in events {
+        write_event(&mut out, event, &stamp);
+    }
+    for task in tasks {
+        write_task(&mut out, task, &stamp);
+    }
+    push_line(&mut out, "END:VCALENDAR");
+    out
+}
+
+/// A VCALENDAR with just `item`, or `None` for a project
+pub fn export_item(item: &EventType) -> Option<String> {
+    match item {
+        EventType::Event(e) => Some(export_items(std::slice::from_ref(e), &[])),
+        EventType::Task(t) => Some(export_items(&[], std::slice::from_ref(t))),
+        EventType::Project(_) => None,
+    }
+}
+
+/// All stored events and tasks as a VCALENDAR
+pub fn export() -> String {
+    export_items(&get_events(), &get_tasks())
+}
+
+/// The time of a VEVENT. Without an end it lasts a day if it starts on a date, and no time
+/// otherwise.
+fn interval_of(c: &Component) -> std::result::Result<Interval, String> {
+    let dtstart = c.get("DTSTART").ok_or("missing DTSTART")?;
+    let start = parse_datetime(&dtstart.value)
+        .ok_or_else(|| format!("invalid DTSTART '{}'", dtstart.value))?;
+    let end = match (c.get("DTEND"), c.get("DURATION")) {
+        (Some(p), _) => {
+            parse_datetime(&p.value).ok_or_else(|| format!("invalid DTEND '{}'", p.value))?
+        }
+        (None, Some(p)) => parse_duration(&p.value)
+            .and_then(|d| start.checked_add_signed(d))
+            .ok_or_else(|| format!("invalid DURATION '{}'", p.value))?,
+        (None, None) if is_date(dtstart) => start + Duration::days(1),
+        (None, None) => start,
+    };
+    Interval::builder()
+        .start(start)
+        .end(end)
+        .build()
+        .map_err(|e| e.to_string())
+}
+
+fn common_of(c: &Component) -> EventCommon {
+    EventCommon::new(
+        c.text("SUMMARY").unwrap_or_default(),
+        from_ical_priority(c.get("PRIORITY")),
+    )
+}
+
+/// The event described by the VEVENT `c`, with the occurrences in `overrides` removed or moved
+fn event_of(c: &Component, overrides: &[&Component]) -> std::result::Result<Event, String> {
+    let mut event = Event::new(common_of(c), interval_of(c)?);
+    let rule = match c.get("RRULE") {
+        Some(p) => p
+            .value
+            .parse::<RecurrenceRule>()
+            .map_err(|e| e.to_string())?,
+        None => return Ok(event),
+    };
+    let mut recurrence = Recurrence::new(rule);
+    for p in c.props.iter().filter(|p| p.name == "EXDATE") {
+        for value in p.value.split(',') {
+            let original =
+                parse_datetime(value).ok_or_else(|| format!("invalid EXDATE '{}'", value))?;
+            recurrence.skip(original);
+        }
+    }
+    for o in overrides {
+        let id = o.get("RECURRENCE-ID").unwrap();
+        let original = parse_datetime(&id.value)
+            .ok_or_else(|| format!("invalid RECURRENCE-ID '{}'", id.value))?;
+        if o.get("STATUS").map(|p| p.value.trim()) == Some("CANCELLED") {
+            recurrence.skip(original);
+        } else {
+            recurrence.reschedule(original, interval_of(o)?);
+        }
+    }
+    event.set_recurrence(Some(recurrence));
+    Ok(event)
+}
+
+/// The task described by the VTODO `c`. A due date is the end of that day.
+fn task_of(c: &Component) -> std::result::Result<Task, String> {
+    let mut common = common_of(c);
+    if c.get("STATUS").map(|p| p.value.trim()) == Some("COMPLETED") || c.get("COMPLETED").is_some()
+    {
+        common.finish();
+    }
+    let mut task = Task::new(common);
+    if let Some(p) = c.get("DUE") {
+        let due = parse_datetime(&p.value).ok_or_else(|| format!("invalid DUE '{}'", p.value))?;
+        task.set_deadline(Some(if is_date(p) {
+            due + Duration::days(1)
+        } else {
+            due
+        }));
+    }
+    Ok(task)
+}
+
+/// Where an imported item goes: the stored item of the same kind with its UID if there is one,
+/// and a new item otherwise
+fn target_of(uid: Option<&str>, kind: &str) -> std::result::Result<Option<i32>, String> {
+    let id = match uid.and_then(id_for_uid) {
+        Some(id) => id,
+        None => return Ok(None),
+    };
+    match get_event(id) {
+        Some(e) if e.kind() == kind => Ok(Some(id)),
+        Some(e) => Err(format!("its UID belongs to {} {}", e.kind(), id)),
+        None => Ok(None),
+    }
+}
+
+fn import_event(
+    uid: Option<&str>,
+    mut event: Event,
+    summary: &mut ImportSummary,
+) -> std::result::Result<(), String> {
+    match target_of(uid, "event")? {
+        Some(id) => {
+            update_event(id, &event).map_err(|e| e.kind.to_string())?;
+            summary.updated.push(id);
+        }
+        None => {
+            let (id, _) = add_event(&mut event).map_err(|e| e.kind.to_string())?;
+            if let Some(uid) = uid {
+                API_STATE.with(|s| s.lock().unwrap().storage.set_uid(id, uid));
+            }
+            summary.created.push(id);
+        }
+    }
+    Ok(())
+}
+
+fn import_task(
+    uid: Option<&str>,
+    mut task: Task,
+    summary: &mut ImportSummary,
+) -> std::result::Result<(), String> {
+    match target_of(uid, "task")? {
+        Some(id) => {
+            update_task(id, &task);
+            summary.updated.push(id);
+        }
+        None => {
+            let id = add_task(&mut task);
+            if let Some(uid) = uid {
+                API_STATE.with(|s| s.lock().unwrap().storage.set_uid(id, uid));
+            }
+            summary.created.push(id);
+        }
+    }
+    Ok(())
+}
+
+/// Adds the VEVENTs and VTODOs of `text` as events and tasks, updating the ones imported before.
+/// Items that can't be imported (say, an event the conflict policy rejects) are skipped; only
+/// data that isn't iCalendar at all fails the whole import.
+pub fn import(text: &str) -> Result<ImportSummary> {
+    let components = parse(text).map_err(|e| Error {
+        method: "import_ical".into(),
+        kind: ErrorKind::InvalidICalendar(e),
+    })?;
+    let items: Vec<&Component> = components
+        .iter()
+        .flat_map(|c| match c.name.as_str() {
+            "VCALENDAR" => c.children.iter().collect(),
+            _ => vec![c],
+        })
+        .collect();
+
+    // Occurrences of recurring events that were moved or cancelled come as separate VEVENTs
+    // with the UID of the event and a RECURRENCE-ID
+    let mut overrides: HashMap<String, Vec<&Component>> = HashMap::new();
+    for c in items.iter() {
+        if c.name == "VEVENT" && c.get("RECURRENCE-ID").is_some() {
+            if let Some(uid) = c.text("UID") {
+                overrides.entry(uid).or_default().push(c);
+            }
+        }
+    }
+
+    let mut summary = ImportSummary::default();
+    for c in items {
+        let uid = c.text("UID");
+        let result = match c.name.as_str() {
+            "VEVENT" if c.get("RECURRENCE-ID").is_some() => continue,
+            "VEVENT" => {
+                let overrides = uid
+                    .as_ref()
+                    .and_then(|u| overrides.get(u))
+                    .map_or(&[][..], Vec::as_slice);
+                event_of(c, overrides).and_then(|e| import_event(uid.as_deref(), e, &mut summary))
+            }
+            "VTODO" => task_of(c).and_then(|t| import_task(uid.as_deref(), t, &mut summary)),
+            _ => continue,
+        };
+        if let Err(reason) = result {
+            let name = c.text("SUMMARY").unwrap_or_default();
+            summary
+                .skipped
+                .push(format!("{} '{}': {}", c.name, name, reason));
+        }
+    }
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(s: &str) -> DateTime<Local> {
+        time::parse_datetime(s).unwrap()
+    }
+
+    fn between(start: &str, end: &str) -> Interval {
+        Interval::builder()
+            .start(at(start))
+            .end(at(end))
+            .build()
+            .unwrap()
+    }
+
+    fn only(text: &str) -> Component {
+        let mut components = parse(text).unwrap();
+        assert_eq!(components.len(), 1);
+        components.pop().unwrap()
+    }
+
+    #[test]
+    fn unfolds_lines() {
+        assert_eq!(
+            unfold("BEGIN:VEVENT\r\nSUMMARY:a long\r\n  line\r\n\t again\r\n\r\nEND:VEVENT"),
+            vec!["BEGIN:VEVENT", "SUMMARY:a long line again", "END:VEVENT"]
+        );
+        assert_eq!(unfold("A:1\nB:2\n"), vec!["A:1", "B:2"]);
+    }
+
+    #[test]
+    fn folds_long_lines() {
+        let summary = format!("SUMMARY:{}", "é".repeat(60));
+        let mut out = String::new();
+        push_line(&mut out, &summary);
+        assert!(out.split("\r\n").all(|line| line.len() <= MAX_LINE));
+        assert_eq!(unfold(&out), vec![summary]);
+    }
+
+    #[test]
+    fn parses_components() {
+        let c = only(concat!(
+            "BEGIN:VCALENDAR\r\n",
+            "BEGIN:VEVENT\r\n",
+            "DTSTART;TZID=\"Europe/Paris; France\":20200810T090000\r\n",
+            "SUMMARY:Lunch\\, then a walk\r\n",
+            "END:VEVENT\r\n",
+            "END:VCALENDAR\r\n",
+        ));
+        assert_eq!(c.name, "VCALENDAR");
+        let event = &c.children[0];
+        assert_eq!(event.name, "VEVENT");
+        let start = event.get("DTSTART").unwrap();
+        assert_eq!(start.param("TZID"), Some("Europe/Paris; France"));
+        assert_eq!(start.value, "20200810T090000");
+        assert_eq!(event.text("SUMMARY").unwrap(), "Lunch, then a walk");
+    }
+
+    #[test]
+    fn rejects_unbalanced_components() {
+        assert!(parse("BEGIN:VEVENT\r\n").is_err());
+        assert!(parse("BEGIN:VEVENT\r\nEND:VTODO\r\n").is_err());
+        assert!(parse("END:VEVENT\r\n").is_err());
+        assert!(parse("no colon\r\n").is_err());
+    }
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse_duration("PT1H30M"), Some(Duration::minutes(90)));
+        assert_eq!(parse_duration("-P1W"), Some(Duration::weeks(-1)));
+        assert_eq!(
+            parse_duration("+P1DT2S"),
+            Some(Duration::days(1) + Duration::seconds(2))
+        );
+        for s in &[
+            "",
+            "P",
+            "PT",
+            "P1H",
+            "PT1D",
+            "PT1",
+            "1H",
+            "P99999999999999W",
+        ] {
+            assert_eq!(parse_duration(s), None, "{}", s);
+        }
+    }
+
+    #[test]
+    fn reads_events() {
+        let c = only(concat!(
+            "BEGIN:VEVENT\r\n",
+            "SUMMARY:Standup\r\n",
+            "PRIORITY:1\r\n",
+            "DTSTART:20260105T090000\r\n",
+            "DURATION:PT15M\r\n",
+            "RRULE:FREQ=DAILY\r\n",
+            "EXDATE:20260106T090000,20260107T090000\r\n",
+            "END:VEVENT\r\n",
+        ));
+        let moved = only(concat!(
+            "BEGIN:VEVENT\r\n",
+            "RECURRENCE-ID:20260108T090000\r\n",
+            "DTSTART:20260108T100000\r\n",
+            "DTEND:20260108T101500\r\n",
+            "END:VEVENT\r\n",
+        ));
+        let cancelled = only(concat!(
+            "BEGIN:VEVENT\r\n",
+            "RECURRENCE-ID:20260109T090000\r\n",
+            "STATUS:CANCELLED\r\n",
+            "END:VEVENT\r\n",
+        ));
+        let event = event_of(&c, &[&moved, &cancelled]).unwrap();
+        assert_eq!(event.common().name(), "Standup");
+        assert_eq!(event.common().priority(), Priority::VeryHigh);
+        assert_eq!(
+            event.interval(),
+            between("2026-01-05 09:00", "2026-01-05 09:15")
+        );
+        assert_eq!(
+            event.occurrences(&between("2026-01-05", "2026-01-11")),
+            vec![
+                between("2026-01-05 09:00", "2026-01-05 09:15"),
+                between("2026-01-08 10:00", "2026-01-08 10:15"),
+                between("2026-01-10 09:00", "2026-01-10 09:15"),
+            ]
+        );
+    }
+
+    #[test]
+    fn reads_all_day_events() {
+        let c = only("BEGIN:VEVENT\r\nDTSTART;VALUE=DATE:20260105\r\nEND:VEVENT\r\n");
+        let event = event_of(&c, &[]).unwrap();
+        assert_eq!(event.interval(), between("2026-01-05", "2026-01-06"));
+        assert!(event.recurrence().is_none());
+
+        let c = only(
+            "BEGIN:VEVENT\r\nDTSTART:20260105T090000\r\nDTEND:20260105T080000\r\nEND:VEVENT\r\n",
+        );
+        assert!(event_of(&c, &[]).is_err());
+        let c = only(
+            "BEGIN:VEVENT\r\nDTSTART:20260105T090000\r\nDURATION:P99999999W\r\nEND:VEVENT\r\n",
+        );
+        assert!(event_of(&c, &[]).is_err());
+    }
+
+    #[test]
+    fn round_trips_events() {
+        let mut recurrence = Recurrence::new("FREQ=WEEKLY;BYDAY=MO,WE".parse().unwrap());
+        recurrence.skip(at("2026-01-07 09:00"));
+        recurrence.reschedule(
+            at("2026-01-12 09:00"),
+            between("2026-01-12 13:00", "2026-01-12 14:00"),
+        );
+        let mut event = Event::new(
+            EventCommon::new("Gym, then; a \\ shower".into(), Priority::High),
+            between("2026-01-05 09:00", "2026-01-05 10:00"),
+        );
+        event.set_recurrence(Some(recurrence));
+        let (id, _) = add_event(&mut event).unwrap();
+        let event = get_event(id).unwrap();
+        let text = export_item(&event).unwrap();
+        let uid = uid_of(id);
+        assert_eq!(text.matches(&format!("UID:{}", uid)).count(), 2);
+
+        let summary = import(&text).unwrap();
+        assert_eq!(summary.created, Vec::<i32>::new());
+        assert_eq!(summary.updated, vec![id]);
+        assert!(summary.skipped.is_empty());
+        assert_eq!(id_for_uid(&uid), Some(id));
+        let imported = match get_event(id).unwrap() {
+            EventType::Event(e) => e,
+            _ => panic!("{} is not an event", id),
+        };
+        let original = match event {
+            EventType::Event(e) => e,
+            _ => unreachable!(),
+        };
+        assert_eq!(imported.common().name(), original.common().name());
+        assert_eq!(imported.common().priority(), original.common().priority());
+        assert_eq!(imported.interval(), original.interval());
+        assert_eq!(imported.recurrence(), original.recurrence());
+        assert_eq!(uid_of(id), uid);
+    }
+
+    #[test]
+    fn round_trips_tasks() {
+        let mut task = Task::new(EventCommon::new("Taxes".into(), Priority::Low));
+        task.set_deadline(Some(at("2026-04-15 17:00")));
+        let id = add_task(&mut task);
+        let text = export_items(&[], &[get_task_checked("test", id).unwrap()]);
+        let summary = import(&text).unwrap();
+        assert_eq!(summary.created, Vec::<i32>::new());
+        assert_eq!(summary.updated, vec![id]);
+        assert_eq!(id_for_uid(&uid_of(id)), Some(id));
+        let imported = get_task_checked("test", id).unwrap();
+        assert_eq!(imported.common().name(), "Taxes");
+        assert_eq!(imported.common().priority(), Priority::Low);
+        assert_eq!(imported.deadline(), Some(at("2026-04-15 17:00")));
+        assert!(!imported.common().finished());
+    }
+
+    #[test]
+    fn only_matches_stored_uids() {
+        let mut task = Task::new(EventCommon::new("Mine".into(), Priority::Medium));
+        let id = add_task(&mut task);
+        let uid = uid_of(id);
+        assert!(uid.ends_with(UID_SUFFIX));
+        assert_ne!(uid, format!("{}{}", id, UID_SUFFIX));
+        assert_eq!(id_for_uid(&uid), Some(id));
+
+        // An item exported by another calendar with the same id is a different item
+        let theirs = format!("{}{}", id, UID_SUFFIX);
+        let text = format!(
+            "BEGIN:VTODO\r\nUID:{}\r\nSUMMARY:Theirs\r\nEND:VTODO\r\n",
+            theirs
+        );
+        let summary = import(&text).unwrap();
+        assert_eq!(summary.updated, Vec::<i32>::new());
+        let created = id_for_uid(&theirs).unwrap();
+        assert_eq!(summary.created, vec![created]);
+        assert_ne!(created, id);
+        assert_eq!(
+            get_task_checked("test", created).unwrap().common().name(),
+            "Theirs"
+        );
+        assert_eq!(
+            get_task_checked("test", id).unwrap().common().name(),
+            "Mine"
+        );
+    }
+}
@@ -0,0 +1,173 @@
+//! The lifecycle of events, tasks and projects. API calls that cause a transition queue it up in
+//! the API state; whoever drives the API (usually the script context) then takes the pending
+//! transitions and runs the handlers for them.
+use std::fmt;
+
+use chrono::{DateTime, Duration, Local, TimeZone};
+
+use super::{event::*, state::API_STATE, time};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Lifecycle {
+    /// The event was stored
+    Created,
+    /// The start time of an event was reached
+    Start,
+    /// The end time of an event was reached
+    End,
+    /// The event was marked as finished
+    Finish,
+    /// A deadline passed while the event still wasn't finished
+    Overdue,
+    /// The time given by the `reminder` prop (a duration before the start) was reached
+    Reminder,
+}
+
+const LIFECYCLES: [Lifecycle; 6] = [
+    Lifecycle::Created,
+    Lifecycle::Start,
+    Lifecycle::End,
+    Lifecycle::Finish,
+    Lifecycle::Overdue,
+    Lifecycle::Reminder,
+];
+
+impl Lifecycle {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Lifecycle::Created => "created",
+            Lifecycle::Start => "start",
+            Lifecycle::End => "end",
+            Lifecycle::Finish => "finish",
+            Lifecycle::Overdue => "overdue",
+            Lifecycle::Reminder => "reminder",
+        }
+    }
+
+    pub fn from_name<S: AsRef<str>>(name: S) -> Option<Lifecycle> {
+        LIFECYCLES
+            .iter()
+            .find(|l| l.name() == name.as_ref())
+            .copied()
+    }
+}
+
+impl fmt::Display for Lifecycle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Queues up a transition of the event `id`
+pub(crate) fn notify(id: i32, lifecycle: Lifecycle) {
+    API_STATE.with(|s| s.lock().unwrap().pending.push((id, lifecycle)));
+}
+
+/// Takes all the transitions that happened since the last call, in the order they happened
+pub fn take_pending() -> Vec<(i32, Lifecycle)> {
+    API_STATE.with(|s| s.lock().unwrap().pending.drain(..).collect())
+}
+
+/// The timed transitions `event` goes through in `(from, to]`, in chronological order. Recurring
+/// events go through them once per occurrence.
+pub fn transitions_between(
+    event: &Event,
+    from: DateTime<Local>,
+    to: DateTime<Local>,
+) -> Vec<(DateTime<Local>, Lifecycle)> {
+    let reminder = event
+        .common()
+        .props()
+        .get("reminder")
+        .and_then(time::parse_duration)
+        .filter(|r| *r > Duration::zero());
+    // Any occurrence with a transition in the range overlaps this window
+    let window = Interval::builder()
+        .start(from - event.interval().length())
+        .end(to + reminder.unwrap_or_else(Duration::zero))
+        .build();
+    let window = match window {
+        Ok(window) => window,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut transitions = Vec::new();
+    for occurrence in event.occurrences(&window) {
+        if let Some(before) = reminder {
+            transitions.push((occurrence.start() - before, Lifecycle::Reminder));
+        }
+        transitions.push((occurrence.start(), Lifecycle::Start));
+        transitions.push((occurrence.end(), Lifecycle::End));
+    }
+    transitions.retain(|(t, _)| from < *t && *t <= to);
+    transitions.sort_by_key(|(t, _)| *t);
+    transitions
+}
+
+/// Queues up the timed transitions of unfinished events that happened after the last check and no
+/// later than `now`, along with the tasks and projects that became overdue meanwhile. The time of
+/// the last check is stored, so what happened while nothing was checking fires on the next check.
+/// The very first check only sets the starting point, so nothing further in the past fires.
+pub fn check_transitions(now: DateTime<Local>) {
+    let last = API_STATE.with(|s| {
+        let storage = &mut s.lock().unwrap().storage;
+        let last = storage.get_last_check();
+        storage.set_last_check(now.naive_utc());
+        last
+    });
+    let last = match last {
+        Some(last) => Local.from_utc_datetime(&last),
+        None => return,
+    };
+    let mut due = Vec::new();
+    for event in get_events() {
+        if event.common().finished() {
+            continue;
+        }
+        let id = event.common().id().unwrap();
+        for (t, lifecycle) in transitions_between(&event, last, now) {
+            due.push((t, id, lifecycle));
+        }
+    }
+    let deadlines = get_tasks()
+        .into_iter()
+        .filter(|t| !t.common().finished())
+        .map(|t| (t.common().id(), t.deadline()))
+        .chain(
+            get_projects()
+                .into_iter()
+                .filter(|p| !p.common().finished())
+                .map(|p| (p.common().id(), p.deadline())),
+        );
+    for (id, deadline) in deadlines {
+        if let (Some(id), Some(deadline)) = (id, deadline) {
+            if last < deadline && deadline <= now {
+                due.push((deadline, id, Lifecycle::Overdue));
+            }
+        }
+    }
+    due.sort_by_key(|(t, _, _)| *t);
+    for (_, id, lifecycle) in due {
+        notify(id, lifecycle);
+    }
+}
+
+/// When the next timed transition after `now` and no later than `now + horizon` happens, if any
+pub fn next_transition(now: DateTime<Local>, horizon: Duration) -> Option<DateTime<Local>> {
+    let until = now + horizon;
+    let events = get_events()
+        .into_iter()
+        .filter(|e| !e.common().finished())
+        .filter_map(|e| transitions_between(&e, now, until).first().map(|t| t.0));
+    let tasks = get_tasks()
+        .into_iter()
+        .filter(|t| !t.common().finished())
+        .filter_map(|t| t.deadline());
+    let projects = get_projects()
+        .into_iter()
+        .filter(|p| !p.common().finished())
+        .filter_map(|p| p.deadline());
+    events
+        .chain(tasks.chain(projects).filter(|d| now < *d && *d <= until))
+        .min()
+}
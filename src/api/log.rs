@@ -1,10 +1,15 @@
 //! This module contains all the Rust side of the logging API, that is in Rust types and can be
 //! easily used by Rust code
-use std::{collections::HashMap, sync::Mutex};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Mutex,
+};
+
+use chrono::{Local, NaiveDate, TimeZone};
 
 use crate::storage::{model::Log, LogStorage};
 
-use super::{error::*, state::API_STATE};
+use super::{error::*, event::Interval, state::API_STATE};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LogAttr {
@@ -13,6 +18,12 @@ pub struct LogAttr {
     default: Option<String>,
 }
 
+impl LogAttr {
+    pub fn new(hidden: bool, default: Option<String>) -> Self {
+        Self { hidden, default }
+    }
+}
+
 pub type LogAttrs = HashMap<String, LogAttr>;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -21,17 +32,30 @@ pub struct LogType {
     attrs: LogAttrs,
 }
 
+impl LogType {
+    pub fn new<S: Into<String>>(name: S, attrs: LogAttrs) -> Self {
+        Self {
+            name: name.into(),
+            attrs,
+        }
+    }
+}
+
 pub type LogTypes = HashMap<String, HashMap<String, LogAttr>>;
 
-pub fn add_log<S1, S2>(name: S1, desc: S2)
+/// The prop recording the type of a log added with a type
+pub const TYPE_PROP: &str = "type";
+
+/// Adds a log, returning its id
+pub fn add_log<S1, S2>(name: S1, desc: S2) -> i32
 where
     S1: AsRef<str>,
     S2: AsRef<str>,
 {
-    API_STATE.with(|s| s.lock().unwrap().storage.add_log(name, desc));
+    API_STATE.with(|s| s.lock().unwrap().storage.add_log(name, desc))
 }
 
-pub fn add_log_with_props<S1, S2>(name: S1, desc: S2, props: &HashMap<String, String>)
+pub fn add_log_with_props<S1, S2>(name: S1, desc: S2, props: &HashMap<String, String>) -> i32
 where
     S1: AsRef<str>,
     S2: AsRef<str>,
@@ -41,7 +65,7 @@ where
             .unwrap()
             .storage
             .add_log_with_props(name, desc, props)
-    });
+    })
 }
 
 pub fn set_prop<S1, S2>(id: i32, key: S1, val: S2)
@@ -52,13 +76,110 @@ where
     API_STATE.with(|s| s.lock().unwrap().storage.set_prop(id, key, val));
 }
 
+/// Replaces the name, description and props of the log `id`. Its time is kept.
+pub fn update_log<S1, S2>(id: i32, name: S1, desc: S2, props: &HashMap<String, String>)
+where
+    S1: AsRef<str>,
+    S2: AsRef<str>,
+{
+    API_STATE.with(|s| {
+        let storage = &mut s.lock().unwrap().storage;
+        storage.update_log(id, name, desc);
+        storage.clear_props(id);
+        for (key, val) in props.iter() {
+            storage.set_prop(id, key, val);
+        }
+    });
+}
+
+pub fn delete_log(id: i32) {
+    API_STATE.with(|s| s.lock().unwrap().storage.delete_log(id));
+}
+
 pub fn get_logs() -> Vec<Log> {
     API_STATE.with(|s| s.lock().unwrap().storage.get_logs())
 }
+pub fn get_log(id: i32) -> Option<Log> {
+    API_STATE.with(|s| s.lock().unwrap().storage.get_log(id))
+}
+
 pub fn get_props_for(id: i32) -> HashMap<String, String> {
     API_STATE.with(|s| s.lock().unwrap().storage.get_props_for(id))
 }
 
+/// Which logs to pick. Every field that is set has to match.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LogQuery {
+    /// Logs added with this type
+    pub log_type: Option<String>,
+    /// Logs whose name contains this
+    pub name: Option<String>,
+    /// Logs with these props
+    pub props: HashMap<String, String>,
+    pub range: Option<Interval>,
+}
+
+impl LogQuery {
+    /// Reads a filter like `type:work meeting project=sched`: `type:` picks the log type, each
+    /// `key=value` a prop, and the rest of the words are looked for in the names
+    pub fn parse(filter: &str) -> Self {
+        let mut query = LogQuery::default();
+        let mut name = Vec::new();
+        for word in filter.split_whitespace() {
+            if let Some(typ) = word.strip_prefix("type:") {
+                query.log_type = Some(typ.to_string());
+            } else if let Some(eq) = word.find('=') {
+                query
+                    .props
+                    .insert(word[..eq].to_string(), word[eq + 1..].to_string());
+            } else {
+                name.push(word);
+            }
+        }
+        if !name.is_empty() {
+            query.name = Some(name.join(" "));
+        }
+        query
+    }
+
+    pub fn matches(&self, log: &Log, props: &HashMap<String, String>) -> bool {
+        if let Some(typ) = &self.log_type {
+            if props.get(TYPE_PROP) != Some(typ) {
+                return false;
+            }
+        }
+        if let Some(name) = &self.name {
+            if !log.name.contains(name.as_str()) {
+                return false;
+            }
+        }
+        if let Some(range) = &self.range {
+            if !range.contains(Local.from_utc_datetime(&log.time)) {
+                return false;
+            }
+        }
+        self.props.iter().all(|(k, v)| props.get(k) == Some(v))
+    }
+}
+
+/// The logs matching `query`
+pub fn query_logs(query: &LogQuery) -> Vec<Log> {
+    get_logs()
+        .into_iter()
+        .filter(|log| query.matches(log, &get_props_for(log.id)))
+        .collect()
+}
+
+/// How many logs match `query` on each local day that has any
+pub fn log_counts(query: &LogQuery) -> BTreeMap<NaiveDate, u32> {
+    let mut counts = BTreeMap::new();
+    for log in query_logs(query) {
+        let date = Local.from_utc_datetime(&log.time).naive_local().date();
+        *counts.entry(date).or_default() += 1;
+    }
+    counts
+}
+
 pub fn add_log_type(typ: LogType) {
     API_STATE.with(|s| s.lock().unwrap().log_types.insert(typ.name, typ.attrs));
 }
@@ -81,7 +202,7 @@ pub fn add_log_with_type<S1, S2>(
     typ: Option<String>,
     props: HashMap<String, String>,
     conform_type: bool,
-) -> Result<()>
+) -> Result<i32>
 where
     S1: AsRef<str>,
     S2: AsRef<str>,
@@ -89,7 +210,7 @@ where
     let mut final_props: HashMap<String, Option<String>> = HashMap::new();
     let conform_type = typ.is_some() && conform_type;
     if let Some(ref typ) = typ {
-        let res = API_STATE.with(|s| {
+        API_STATE.with(|s| {
             let api_state = s.lock().unwrap();
             match api_state.log_types.get(typ) {
                 Some(type_attrs) => {
@@ -103,10 +224,7 @@ where
                     kind: ErrorKind::InvalidLogType(typ.into()),
                 }),
             }
-        });
-        if res.is_err() {
-            return res;
-        }
+        })?;
     }
 
     if conform_type {
@@ -120,22 +238,28 @@ where
     }
 
     if let Some(ref typ) = typ {
-        for (key, val) in final_props.iter() {
-            if val.is_none() {
-                return Err(Error {
-                    method: "add_log_with_type".into(),
-                    kind: ErrorKind::MissingField {
-                        typ: typ.into(),
-                        field: key.into(),
-                    },
-                });
-            }
+        let mut missing: Vec<String> = final_props
+            .iter()
+            .filter(|(_, val)| val.is_none())
+            .map(|(key, _)| key.clone())
+            .collect();
+        if !missing.is_empty() {
+            missing.sort();
+            return Err(Error {
+                method: "add_log_with_type".into(),
+                kind: ErrorKind::MissingFields {
+                    typ: typ.into(),
+                    fields: missing,
+                },
+            });
         }
     }
-    let final_props = final_props
+    let mut final_props: HashMap<String, String> = final_props
         .into_iter()
         .map(|(k, v)| (k, v.unwrap()))
         .collect();
-    add_log_with_props(name, desc, &final_props);
-    Ok(())
+    if let Some(typ) = typ {
+        final_props.entry(TYPE_PROP.into()).or_insert(typ);
+    }
+    Ok(add_log_with_props(name, desc, &final_props))
 }
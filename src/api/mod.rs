@@ -1,7 +1,36 @@
+pub mod agenda;
+pub mod availability;
+pub mod caldav;
+pub mod chart;
+pub mod conflict;
+pub mod deadline;
+pub mod dependency;
 pub mod error;
 pub mod event;
+pub mod export;
+pub mod focus;
+pub mod habit;
+pub mod ical;
+pub mod lifecycle;
 pub mod log;
+pub mod quick;
+pub mod recurrence;
+pub mod report;
+pub mod schedule;
 pub mod state;
+pub mod time;
+pub mod timer;
+pub mod tree;
 
+pub use availability::*;
+pub use conflict::*;
+pub use deadline::*;
+pub use dependency::*;
 pub use event::*;
+pub use focus::*;
+pub use habit::*;
+pub use lifecycle::*;
 pub use log::*;
+pub use recurrence::*;
+pub use timer::*;
+pub use tree::*;
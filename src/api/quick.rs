@@ -0,0 +1,200 @@
+//! The compact syntax for adding a log in one go, as in `"fixed CI" +ci @work finished=yes`:
+//! `+tag` adds a tag, `@context` (or `context=context`, but only one of them) sets the context,
+//! `key=value` sets any other prop, and the rest of the words are text, the first being the name
+//! and the others the description. Words with spaces in them are always text, so that a quoted
+//! name can hold anything, and a leading `\` makes any other word text.
+use std::collections::HashMap;
+
+use super::{error::*, log::add_log_with_type, report::TAGS_PROP};
+
+/// The prop set by `@context`
+pub const CONTEXT_PROP: &str = "context";
+
+/// A log as read from the compact syntax
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct QuickLog {
+    pub name: String,
+    pub desc: String,
+    pub log_type: Option<String>,
+    pub props: HashMap<String, String>,
+}
+
+fn invalid(field: &str, message: String) -> Error {
+    Error {
+        method: "parse_quick_log".into(),
+        kind: ErrorKind::InvalidField {
+            field: field.into(),
+            message,
+        },
+    }
+}
+
+/// Whether `key` can be the key of a `key=value` word
+fn is_key(key: &str) -> bool {
+    !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.'))
+}
+
+impl QuickLog {
+    /// Reads `words`, which are usually the arguments of a command. The log type isn't part of
+    /// the syntax; it is left for the caller to set.
+    pub fn parse<S: AsRef<str>>(words: &[S]) -> Result<Self> {
+        let mut log = QuickLog::default();
+        let mut tags: Vec<&str> = Vec::new();
+        let mut text: Vec<&str> = Vec::new();
+        for word in words.iter().map(AsRef::as_ref) {
+            if word.contains(char::is_whitespace) {
+                text.push(word);
+            } else if let Some(word) = word.strip_prefix('\\') {
+                text.push(word);
+            } else if let Some(tag) = word.strip_prefix('+').filter(|t| !t.is_empty()) {
+                if tag.contains(',') {
+                    return Err(invalid("tag", format!("Tag '{}' has a comma", tag)));
+                }
+                if !tags.contains(&tag) {
+                    tags.push(tag);
+                }
+            } else if let Some(context) = word.strip_prefix('@').filter(|c| !c.is_empty()) {
+                log.set_context(context)?;
+            } else if let Some(eq) = word.find('=').filter(|eq| is_key(&word[..*eq])) {
+                let (key, value) = (&word[..eq], &word[eq + 1..]);
+                if key == CONTEXT_PROP {
+                    log.set_context(value)?;
+                } else {
+                    log.props.insert(key.to_string(), value.to_string());
+                }
+            } else {
+                text.push(word);
+            }
+        }
+
+        let mut text = text.into_iter();
+        log.name = match text.next() {
+            Some(name) => name.to_string(),
+            None => return Err(invalid("name", "A log needs a name".into())),
+        };
+        log.desc = text.collect::<Vec<_>>().join(" ");
+        if !tags.is_empty() {
+            // Added to the tags given as a prop, if any
+            let mut all: Vec<String> = log
+                .props
+                .get(TAGS_PROP)
+                .map(|t| t.split(',').map(str::trim).filter(|t| !t.is_empty()))
+                .into_iter()
+                .flatten()
+                .map(String::from)
+                .collect();
+            for tag in tags {
+                if !all.iter().any(|t| t == tag) {
+                    all.push(tag.to_string());
+                }
+            }
+            log.props.insert(TAGS_PROP.into(), all.join(","));
+        }
+        Ok(log)
+    }
+
+    /// Sets the context, which `@context` and `context=` both give, and only once
+    fn set_context(&mut self, context: &str) -> Result<()> {
+        match self.props.insert(CONTEXT_PROP.into(), context.into()) {
+            Some(old) => Err(invalid(
+                CONTEXT_PROP,
+                format!(
+                    "Only one context is allowed, got '{}' and '{}'",
+                    old, context
+                ),
+            )),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Adds `log` like `add_log_with_type` does, so that its type fills in the defaults and the
+/// attrs without one have to be given. Returns the id of the new log.
+pub fn add_quick_log(log: QuickLog) -> Result<i32> {
+    add_log_with_type(log.name, log.desc, log.log_type, log.props, false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(words: &[&str]) -> Result<QuickLog> {
+        QuickLog::parse(words)
+    }
+
+    fn props(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn reads_text_tags_context_and_props() {
+        let log = parse(&["fixed", "+ci", "the", "build", "@work", "finished=yes"]).unwrap();
+        assert_eq!(log.name, "fixed");
+        assert_eq!(log.desc, "the build");
+        assert_eq!(log.log_type, None);
+        assert_eq!(
+            log.props,
+            props(&[("tags", "ci"), ("context", "work"), ("finished", "yes")])
+        );
+    }
+
+    #[test]
+    fn keeps_quoted_words_as_text() {
+        let log = parse(&["fixed CI", "+a b", "x=y z", "@home"]).unwrap();
+        assert_eq!(log.name, "fixed CI");
+        assert_eq!(log.desc, "+a b x=y z");
+        assert_eq!(log.props, props(&[("context", "home")]));
+    }
+
+    #[test]
+    fn escapes_words() {
+        let log = parse(&["\\+1", "\\@home", "\\a=b", "\\\\x"]).unwrap();
+        assert_eq!(log.name, "+1");
+        assert_eq!(log.desc, "@home a=b \\x");
+        assert!(log.props.is_empty());
+    }
+
+    #[test]
+    fn treats_bare_markers_as_text() {
+        let log = parse(&["+", "@", "=x", "a b=c"]).unwrap();
+        assert_eq!(log.name, "+");
+        assert_eq!(log.desc, "@ =x a b=c");
+        assert!(log.props.is_empty());
+    }
+
+    #[test]
+    fn rejects_a_second_context() {
+        let e = parse(&["call", "@work", "@home"]).unwrap_err();
+        match e.kind {
+            ErrorKind::InvalidField { field, .. } => assert_eq!(field, CONTEXT_PROP),
+            kind => panic!("unexpected error {}", kind),
+        }
+        assert!(parse(&["call", "@work", "@work"]).is_err());
+        assert!(parse(&["call", "@work", "context=home"]).is_err());
+        assert!(parse(&["call", "context=home", "@work"]).is_err());
+        let log = parse(&["call", "context=home"]).unwrap();
+        assert_eq!(log.props, props(&[("context", "home")]));
+    }
+
+    #[test]
+    fn merges_tags_with_the_tags_prop() {
+        let log = parse(&["run", "+b", "tags=a,b,,c", "+d", "+b"]).unwrap();
+        assert_eq!(log.props.get(TAGS_PROP).unwrap(), "a,b,c,d");
+
+        let log = parse(&["run", "tags=a"]).unwrap();
+        assert_eq!(log.props.get(TAGS_PROP).unwrap(), "a");
+    }
+
+    #[test]
+    fn rejects_bad_words() {
+        assert!(parse(&["run", "+a,b"]).is_err());
+        assert!(parse(&["+a", "@b", "c=d"]).is_err());
+        assert!(parse(&[]).is_err());
+    }
+}
@@ -0,0 +1,606 @@
+//! Recurring events, described by a subset of the iCalendar RRULE (RFC 5545): `FREQ`, `INTERVAL`,
+//! `BYDAY`, `COUNT` and `UNTIL`, plus exceptions and per-occurrence overrides.
+use std::cmp::max;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{
+    DateTime, Datelike, Duration, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc,
+    Weekday,
+};
+
+use super::{error::ErrorKind, event::Interval, time};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+impl Frequency {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Frequency::Daily => "DAILY",
+            Frequency::Weekly => "WEEKLY",
+            Frequency::Monthly => "MONTHLY",
+            Frequency::Yearly => "YEARLY",
+        }
+    }
+}
+
+impl FromStr for Frequency {
+    type Err = ErrorKind;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_uppercase().as_str() {
+            "DAILY" => Ok(Frequency::Daily),
+            "WEEKLY" => Ok(Frequency::Weekly),
+            "MONTHLY" => Ok(Frequency::Monthly),
+            "YEARLY" => Ok(Frequency::Yearly),
+            _ => Err(ErrorKind::InvalidRecurrence(format!(
+                "unknown frequency '{}'",
+                s
+            ))),
+        }
+    }
+}
+
+/// An entry of `BYDAY`: a weekday, optionally with the ordinal of that weekday within the month
+/// (`2TU` is the second Tuesday, `-1FR` the last Friday). Ordinals are only allowed in monthly
+/// rules.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ByDay {
+    pub ordinal: Option<i32>,
+    pub weekday: Weekday,
+}
+
+const WEEKDAY_CODES: [(Weekday, &str); 7] = [
+    (Weekday::Mon, "MO"),
+    (Weekday::Tue, "TU"),
+    (Weekday::Wed, "WE"),
+    (Weekday::Thu, "TH"),
+    (Weekday::Fri, "FR"),
+    (Weekday::Sat, "SA"),
+    (Weekday::Sun, "SU"),
+];
+
+impl FromStr for ByDay {
+    type Err = ErrorKind;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_uppercase();
+        let invalid = || ErrorKind::InvalidRecurrence(format!("invalid BYDAY entry '{}'", s));
+        if s.len() < 2 {
+            return Err(invalid());
+        }
+        let (ordinal, code) = s.split_at(s.len() - 2);
+        let weekday = WEEKDAY_CODES
+            .iter()
+            .find(|(_, c)| *c == code)
+            .map(|(d, _)| *d)
+            .ok_or_else(invalid)?;
+        let ordinal = match ordinal {
+            "" => None,
+            o => match o.trim_start_matches('+').parse::<i32>() {
+                Ok(0) | Err(_) => return Err(invalid()),
+                Ok(n) => Some(n),
+            },
+        };
+        Ok(ByDay { ordinal, weekday })
+    }
+}
+
+impl fmt::Display for ByDay {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(n) = self.ordinal {
+            write!(f, "{}", n)?;
+        }
+        let (_, code) = WEEKDAY_CODES
+            .iter()
+            .find(|(d, _)| *d == self.weekday)
+            .unwrap();
+        f.write_str(code)
+    }
+}
+
+/// When an event repeats, relative to its first occurrence
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecurrenceRule {
+    pub freq: Frequency,
+    /// Repeat every `interval` days, weeks, months or years
+    pub interval: u32,
+    /// For weekly rules, the days of the week to repeat on (the day of the first occurrence if
+    /// empty); for monthly rules, the (nth) weekdays of the month instead of the day of the month
+    pub by_day: Vec<ByDay>,
+    /// Total number of occurrences, counting the first one
+    pub count: Option<u32>,
+    /// Last time an occurrence may start at
+    pub until: Option<DateTime<Local>>,
+}
+
+impl RecurrenceRule {
+    pub fn new(freq: Frequency) -> Self {
+        Self {
+            freq,
+            interval: 1,
+            by_day: Vec::new(),
+            count: None,
+            until: None,
+        }
+    }
+
+    /// Checks the parts that can't be combined: `INTERVAL` must be positive, `BYDAY` is only
+    /// supported for weekly and monthly rules, and ordinals only for monthly ones.
+    pub fn validate(&self) -> Result<(), ErrorKind> {
+        let invalid = |msg: &str| Err(ErrorKind::InvalidRecurrence(msg.into()));
+        if self.interval == 0 {
+            return invalid("INTERVAL must be at least 1");
+        }
+        match self.freq {
+            Frequency::Daily | Frequency::Yearly if !self.by_day.is_empty() => {
+                invalid("BYDAY is only supported for WEEKLY and MONTHLY rules")
+            }
+            Frequency::Weekly if self.by_day.iter().any(|d| d.ordinal.is_some()) => {
+                invalid("BYDAY ordinals like 2TU are only supported for MONTHLY rules")
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// The dates of the `period`th period (day, week, month or year, taking `interval` into
+    /// account) after the one of `first`, and the first date of that period, or `None` once that
+    /// period is past the range of dates that can be represented.
+    fn candidates(&self, first: NaiveDate, period: i64) -> Option<(NaiveDate, Vec<NaiveDate>)> {
+        let step = period.checked_mul(self.interval as i64)?;
+        let (period_start, mut dates) = match self.freq {
+            Frequency::Daily => {
+                let date = first.checked_add_signed(Duration::days(step))?;
+                (date, vec![date])
+            }
+            Frequency::Weekly => {
+                let week_start = first
+                    .checked_sub_signed(Duration::days(
+                        first.weekday().num_days_from_monday() as i64
+                    ))?
+                    .checked_add_signed(Duration::weeks(step))?;
+                let weekdays = if self.by_day.is_empty() {
+                    vec![first.weekday()]
+                } else {
+                    self.by_day.iter().map(|d| d.weekday).collect()
+                };
+                let dates = weekdays
+                    .into_iter()
+                    .filter_map(|d| {
+                        week_start
+                            .checked_add_signed(Duration::days(d.num_days_from_monday() as i64))
+                    })
+                    .collect();
+                (week_start, dates)
+            }
+            Frequency::Monthly => {
+                let months =
+                    (first.year() as i64 * 12 + first.month0() as i64).checked_add(step)?;
+                let year = i32::try_from(months.div_euclid(12)).ok()?;
+                let month = months.rem_euclid(12) as u32 + 1;
+                let dates = if self.by_day.is_empty() {
+                    NaiveDate::from_ymd_opt(year, month, first.day())
+                        .into_iter()
+                        .collect()
+                } else {
+                    self.by_day
+                        .iter()
+                        .flat_map(|d| weekdays_in_month(year, month, *d))
+                        .collect()
+                };
+                (NaiveDate::from_ymd_opt(year, month, 1)?, dates)
+            }
+            Frequency::Yearly => {
+                let year = first.year().checked_add(i32::try_from(step).ok()?)?;
+                let dates = NaiveDate::from_ymd_opt(year, first.month(), first.day())
+                    .into_iter()
+                    .collect();
+                (NaiveDate::from_ymd_opt(year, 1, 1)?, dates)
+            }
+        };
+        dates.sort();
+        dates.dedup();
+        Some((period_start, dates))
+    }
+}
+
+/// The dates in the given month matching `by_day`
+fn weekdays_in_month(year: i32, month: u32, by_day: ByDay) -> Vec<NaiveDate> {
+    let all: Vec<_> = (1..=31)
+        .filter_map(|d| NaiveDate::from_ymd_opt(year, month, d))
+        .filter(|d| d.weekday() == by_day.weekday)
+        .collect();
+    match by_day.ordinal {
+        None => all,
+        Some(n) if n > 0 => all.get(n as usize - 1).copied().into_iter().collect(),
+        Some(n) => all
+            .len()
+            .checked_sub(n.abs() as usize)
+            .and_then(|i| all.get(i))
+            .copied()
+            .into_iter()
+            .collect(),
+    }
+}
+
+fn parse_until(s: &str) -> Option<DateTime<Local>> {
+    if let Some(utc) = s.strip_suffix('Z') {
+        return NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S")
+            .ok()
+            .map(|t| Local.from_utc_datetime(&t));
+    }
+    NaiveDateTime::parse_from_str(s, "%Y%m%dT%H%M%S")
+        .ok()
+        .or_else(|| {
+            NaiveDate::parse_from_str(s, "%Y%m%d")
+                .ok()
+                .map(|d| d.and_hms(23, 59, 59))
+        })
+        .and_then(|t| Local.from_local_datetime(&t).earliest())
+        .or_else(|| time::parse_datetime(s))
+}
+
+impl FromStr for RecurrenceRule {
+    type Err = ErrorKind;
+
+    /// Parses an RRULE value such as `FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,WE;COUNT=10`. A leading
+    /// `RRULE:` is allowed, and `WKST` is accepted but ignored (weeks always start on Monday).
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let s = s.strip_prefix("RRULE:").unwrap_or(s);
+        let invalid = |msg: String| ErrorKind::InvalidRecurrence(msg);
+        let mut freq = None;
+        let mut rule = RecurrenceRule::new(Frequency::Daily);
+        for part in s.split(';').filter(|p| !p.is_empty()) {
+            let eq = part
+                .find('=')
+                .ok_or_else(|| invalid(format!("expected KEY=VALUE, got '{}'", part)))?;
+            let (key, val) = (&part[..eq], &part[eq + 1..]);
+            match key.to_uppercase().as_str() {
+                "FREQ" => freq = Some(val.parse()?),
+                "INTERVAL" => {
+                    rule.interval = val
+                        .parse()
+                        .map_err(|_| invalid(format!("invalid INTERVAL '{}'", val)))?
+                }
+                "BYDAY" => {
+                    rule.by_day = val.split(',').map(str::parse).collect::<Result<_, _>>()?
+                }
+                "COUNT" => {
+                    rule.count = Some(
+                        val.parse()
+                            .ok()
+                            .filter(|c| *c > 0)
+                            .ok_or_else(|| invalid(format!("invalid COUNT '{}'", val)))?,
+                    )
+                }
+                "UNTIL" => {
+                    rule.until = Some(
+                        parse_until(val)
+                            .ok_or_else(|| invalid(format!("invalid UNTIL '{}'", val)))?,
+                    )
+                }
+                "WKST" => {}
+                k => return Err(invalid(format!("unsupported rule part '{}'", k))),
+            }
+        }
+        rule.freq = freq.ok_or_else(|| invalid("missing FREQ".into()))?;
+        rule.validate()?;
+        Ok(rule)
+    }
+}
+
+impl fmt::Display for RecurrenceRule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "FREQ={}", self.freq.name())?;
+        if self.interval != 1 {
+            write!(f, ";INTERVAL={}", self.interval)?;
+        }
+        if !self.by_day.is_empty() {
+            let days: Vec<_> = self.by_day.iter().map(ByDay::to_string).collect();
+            write!(f, ";BYDAY={}", days.join(","))?;
+        }
+        if let Some(count) = self.count {
+            write!(f, ";COUNT={}", count)?;
+        }
+        if let Some(until) = self.until {
+            write!(
+                f,
+                ";UNTIL={}",
+                until.with_timezone(&Utc).format("%Y%m%dT%H%M%SZ")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// A rule along with the occurrences that were removed or moved. Occurrences are identified by
+/// the start time the rule gives them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Recurrence {
+    pub rule: RecurrenceRule,
+    pub exceptions: Vec<DateTime<Local>>,
+    pub overrides: HashMap<DateTime<Local>, Interval>,
+}
+
+impl Recurrence {
+    pub fn new(rule: RecurrenceRule) -> Self {
+        Self {
+            rule,
+            exceptions: Vec::new(),
+            overrides: HashMap::new(),
+        }
+    }
+
+    /// Removes the occurrence starting at `original`
+    pub fn skip(&mut self, original: DateTime<Local>) {
+        self.overrides.remove(&original);
+        if !self.exceptions.contains(&original) {
+            self.exceptions.push(original);
+        }
+    }
+
+    /// Replaces the occurrence starting at `original` by `interval`
+    pub fn reschedule(&mut self, original: DateTime<Local>, interval: Interval) {
+        self.exceptions.retain(|e| *e != original);
+        self.overrides.insert(original, interval);
+    }
+
+    /// Whether the rule gives an occurrence starting at `original`, where `first` is the first
+    /// occurrence. Skipped and moved occurrences still count.
+    pub fn is_occurrence(&self, first: &Interval, original: DateTime<Local>) -> bool {
+        self.starts(first, original).contains(&original)
+    }
+
+    /// The occurrences overlapping `window`, where `first` is the first occurrence, in order of
+    /// their start time.
+    pub fn expand(&self, first: &Interval, window: &Interval) -> Vec<Interval> {
+        // An occurrence may be moved into the window from after it
+        let limit = self.overrides.keys().copied().fold(window.end(), max);
+        let mut occurrences: Vec<_> = self
+            .starts(first, limit)
+            .into_iter()
+            .filter(|start| !self.exceptions.contains(start))
+            .map(|start| {
+                self.overrides
+                    .get(&start)
+                    .copied()
+                    .unwrap_or_else(|| Interval::from_start(start, first.length()))
+            })
+            .filter(|occurrence| occurrence.overlaps(window))
+            .collect();
+        occurrences.sort_by_key(Interval::start);
+        occurrences
+    }
+
+    /// The start times the rule gives up to `limit`, ignoring exceptions and overrides
+    fn starts(&self, first: &Interval, limit: DateTime<Local>) -> Vec<DateTime<Local>> {
+        let time_of_day = first.start().naive_local().time();
+        let first_date = first.start().naive_local().date();
+
+        let mut starts = Vec::new();
+        'periods: for period in 0.. {
+            let (period_start, dates) = match self.rule.candidates(first_date, period) {
+                Some(candidates) => candidates,
+                None => break,
+            };
+            if period_start > limit.naive_local().date() {
+                break;
+            }
+            for date in dates {
+                let start = match local_time(date, time_of_day) {
+                    Some(start) if start >= first.start() => start,
+                    _ => continue,
+                };
+                if start > limit || self.rule.until.map_or(false, |u| start > u) {
+                    break 'periods;
+                }
+                if self
+                    .rule
+                    .count
+                    .map_or(false, |c| starts.len() >= c as usize)
+                {
+                    break 'periods;
+                }
+                starts.push(start);
+            }
+        }
+        starts
+    }
+}
+
+fn local_time(date: NaiveDate, time: NaiveTime) -> Option<DateTime<Local>> {
+    Local.from_local_datetime(&date.and_time(time)).earliest()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(s: &str) -> DateTime<Local> {
+        time::parse_datetime(s).unwrap()
+    }
+
+    fn hour_at(s: &str) -> Interval {
+        Interval::from_start(at(s), Duration::hours(1))
+    }
+
+    fn starts(recurrence: &Recurrence, first: &str, window: (&str, &str)) -> Vec<DateTime<Local>> {
+        let window = Interval::from_start(at(window.0), at(window.1) - at(window.0));
+        recurrence
+            .expand(&hour_at(first), &window)
+            .iter()
+            .map(Interval::start)
+            .collect()
+    }
+
+    #[test]
+    fn parses_rules() {
+        let rule: RecurrenceRule = "RRULE:FREQ=monthly;INTERVAL=2;BYDAY=2TU,-1FR;COUNT=5;WKST=MO"
+            .parse()
+            .unwrap();
+        assert_eq!(rule.freq, Frequency::Monthly);
+        assert_eq!(rule.interval, 2);
+        assert_eq!(
+            rule.by_day,
+            vec![
+                ByDay {
+                    ordinal: Some(2),
+                    weekday: Weekday::Tue
+                },
+                ByDay {
+                    ordinal: Some(-1),
+                    weekday: Weekday::Fri
+                },
+            ]
+        );
+        assert_eq!(rule.count, Some(5));
+        assert_eq!(
+            rule.to_string(),
+            "FREQ=MONTHLY;INTERVAL=2;BYDAY=2TU,-1FR;COUNT=5"
+        );
+
+        let rule: RecurrenceRule = "FREQ=DAILY;UNTIL=20260110".parse().unwrap();
+        assert_eq!(rule.until, Some(at("2026-01-10 23:59:59")));
+    }
+
+    #[test]
+    fn rejects_invalid_rules() {
+        for s in &[
+            "",
+            "INTERVAL=2",
+            "FREQ=HOURLY",
+            "FREQ=DAILY;INTERVAL=0",
+            "FREQ=DAILY;INTERVAL=-1",
+            "FREQ=DAILY;COUNT=x",
+            "FREQ=DAILY;COUNT=0",
+            "FREQ=DAILY;BYMONTH=1",
+            "FREQ=DAILY;BYDAY=MO",
+            "FREQ=WEEKLY;BYDAY=XX",
+            "FREQ=WEEKLY;BYDAY=0MO",
+            "FREQ=WEEKLY;BYDAY=2MO",
+            "FREQ=YEARLY;BYDAY=MO",
+            "FREQ",
+        ] {
+            assert!(s.parse::<RecurrenceRule>().is_err(), "{}", s);
+        }
+    }
+
+    #[test]
+    fn expands_weekly_rules() {
+        // 2026-01-05 is a Monday
+        let recurrence = Recurrence::new("FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,WE".parse().unwrap());
+        assert_eq!(
+            starts(
+                &recurrence,
+                "2026-01-07 09:00",
+                ("2026-01-01", "2026-02-01")
+            ),
+            vec![
+                at("2026-01-07 09:00"),
+                at("2026-01-19 09:00"),
+                at("2026-01-21 09:00"),
+            ]
+        );
+    }
+
+    #[test]
+    fn expands_monthly_rules() {
+        let recurrence = Recurrence::new("FREQ=MONTHLY;BYDAY=-1FR;COUNT=3".parse().unwrap());
+        assert_eq!(
+            starts(
+                &recurrence,
+                "2026-01-30 09:00",
+                ("2026-01-01", "2027-01-01")
+            ),
+            vec![
+                at("2026-01-30 09:00"),
+                at("2026-02-27 09:00"),
+                at("2026-03-27 09:00"),
+            ]
+        );
+        // Months without a 31st are skipped
+        let recurrence = Recurrence::new("FREQ=MONTHLY;COUNT=3".parse().unwrap());
+        assert_eq!(
+            starts(
+                &recurrence,
+                "2026-01-31 09:00",
+                ("2026-01-01", "2027-01-01")
+            ),
+            vec![
+                at("2026-01-31 09:00"),
+                at("2026-03-31 09:00"),
+                at("2026-05-31 09:00"),
+            ]
+        );
+    }
+
+    #[test]
+    fn stops_at_until() {
+        let recurrence = Recurrence::new("FREQ=DAILY;UNTIL=20260103".parse().unwrap());
+        assert_eq!(
+            starts(
+                &recurrence,
+                "2026-01-01 09:00",
+                ("2026-01-01", "2026-02-01")
+            )
+            .len(),
+            3
+        );
+    }
+
+    #[test]
+    fn stops_at_the_last_representable_date() {
+        let start = NaiveDate::from_ymd(262142, 6, 1).and_hms(9, 0, 0);
+        let first = Interval::from_start(
+            Local.from_local_datetime(&start).unwrap(),
+            Duration::hours(1),
+        );
+        let window = Interval::from_start(first.start(), Duration::days(500));
+        let recurrence = Recurrence::new("FREQ=YEARLY".parse().unwrap());
+        assert_eq!(recurrence.expand(&first, &window).len(), 2);
+        for freq in &["DAILY", "WEEKLY", "MONTHLY", "YEARLY"] {
+            let rule = format!("FREQ={};INTERVAL=4000000000", freq);
+            let recurrence = Recurrence::new(rule.parse().unwrap());
+            assert_eq!(recurrence.expand(&first, &window), vec![first], "{}", freq);
+        }
+    }
+
+    #[test]
+    fn applies_exceptions_and_overrides() {
+        let mut recurrence = Recurrence::new("FREQ=DAILY;COUNT=4".parse().unwrap());
+        let first = hour_at("2026-01-01 09:00");
+        assert!(recurrence.is_occurrence(&first, at("2026-01-02 09:00")));
+        assert!(!recurrence.is_occurrence(&first, at("2026-01-02 10:00")));
+        assert!(!recurrence.is_occurrence(&first, at("2026-01-05 09:00")));
+
+        recurrence.skip(at("2026-01-02 09:00"));
+        recurrence.reschedule(at("2026-01-04 09:00"), hour_at("2026-02-01 12:00"));
+        assert!(recurrence.is_occurrence(&first, at("2026-01-02 09:00")));
+        assert_eq!(
+            starts(
+                &recurrence,
+                "2026-01-01 09:00",
+                ("2026-01-01", "2026-01-10")
+            ),
+            vec![at("2026-01-01 09:00"), at("2026-01-03 09:00")]
+        );
+        // The moved occurrence shows up where it was moved to
+        assert_eq!(
+            starts(
+                &recurrence,
+                "2026-01-01 09:00",
+                ("2026-02-01", "2026-02-02")
+            ),
+            vec![at("2026-02-01 12:00")]
+        );
+    }
+}
@@ -0,0 +1,501 @@
+//! Reports on the time recorded in task sessions over a range, grouped by task, project, tag or
+//! log type, and optionally split by day, week or month. Tags and whether time is billable come
+//! from the `tags` (comma separated) and `billable` props of the task and of the projects
+//! containing it, so that they can be set once on a whole project.
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{Datelike, Duration, NaiveDate};
+
+use super::{error::*, event::*, log::*, time, tree::ancestors};
+
+/// The prop listing the tags of a task or project, separated by commas
+pub const TAGS_PROP: &str = "tags";
+/// The prop telling whether the time on a task or project is billable
+pub const BILLABLE_PROP: &str = "billable";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportGroup {
+    Task,
+    /// The closest project containing the task
+    Project,
+    /// Time with several tags counts once for each of them
+    Tag,
+    /// The type of the log recorded along with the session, such as focus blocks
+    LogType,
+}
+
+impl ReportGroup {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ReportGroup::Task => "task",
+            ReportGroup::Project => "project",
+            ReportGroup::Tag => "tag",
+            ReportGroup::LogType => "type",
+        }
+    }
+}
+
+impl fmt::Display for ReportGroup {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for ReportGroup {
+    type Err = ErrorKind;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "task" => Ok(ReportGroup::Task),
+            "project" => Ok(ReportGroup::Project),
+            "tag" => Ok(ReportGroup::Tag),
+            "type" => Ok(ReportGroup::LogType),
+            _ => Err(ErrorKind::InvalidField {
+                field: "by".into(),
+                message: format!("'{}' is not one of 'task', 'project', 'tag' or 'type'", s),
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportPeriod {
+    Day,
+    /// From Monday to Sunday
+    Week,
+    Month,
+}
+
+impl ReportPeriod {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ReportPeriod::Day => "day",
+            ReportPeriod::Week => "week",
+            ReportPeriod::Month => "month",
+        }
+    }
+
+    /// The first day of the period containing `date`
+    pub fn start_of(&self, date: NaiveDate) -> NaiveDate {
+        match self {
+            ReportPeriod::Day => date,
+            ReportPeriod::Week => {
+                date - Duration::days(date.weekday().num_days_from_monday() as i64)
+            }
+            ReportPeriod::Month => date.with_day(1).unwrap(),
+        }
+    }
+
+    /// The first day of the period after the one containing `date`
+    pub fn next(&self, date: NaiveDate) -> NaiveDate {
+        let start = self.start_of(date);
+        match self {
+            ReportPeriod::Day => start + Duration::days(1),
+            ReportPeriod::Week => start + Duration::weeks(1),
+            ReportPeriod::Month if start.month() == 12 => {
+                NaiveDate::from_ymd(start.year() + 1, 1, 1)
+            }
+            ReportPeriod::Month => NaiveDate::from_ymd(start.year(), start.month() + 1, 1),
+        }
+    }
+}
+
+impl fmt::Display for ReportPeriod {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for ReportPeriod {
+    type Err = ErrorKind;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "day" => Ok(ReportPeriod::Day),
+            "week" => Ok(ReportPeriod::Week),
+            "month" => Ok(ReportPeriod::Month),
+            _ => Err(ErrorKind::InvalidField {
+                field: "per".into(),
+                message: format!("'{}' is not one of 'day', 'week' or 'month'", s),
+            }),
+        }
+    }
+}
+
+/// The time of one group in one period
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReportRow {
+    /// The first day of the period, or `None` when the report isn't split by period
+    pub period: Option<NaiveDate>,
+    /// The task or project when grouping by them, `None` for time outside of any project
+    pub id: Option<i32>,
+    /// The name of the task or project, or the tag or log type, empty for time without one
+    pub key: String,
+    pub total: Duration,
+    pub billable: Duration,
+}
+
+impl ReportRow {
+    pub fn non_billable(&self) -> Duration {
+        self.total - self.billable
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Report {
+    pub range: Interval,
+    pub group: ReportGroup,
+    pub period: Option<ReportPeriod>,
+    /// By period, then by key
+    pub rows: Vec<ReportRow>,
+    /// The time in the range, counted once even when it is in several rows
+    pub total: Duration,
+    pub billable: Duration,
+}
+
+impl Report {
+    pub fn non_billable(&self) -> Duration {
+        self.total - self.billable
+    }
+}
+
+/// What a task's time is filed under
+struct TaskInfo {
+    name: String,
+    /// The closest project containing the task, and its name
+    project: Option<(i32, String)>,
+    tags: Vec<String>,
+    billable: bool,
+}
+
+fn is_true(s: &str) -> bool {
+    matches!(s.trim(), "true" | "yes" | "1")
+}
+
+fn task_info(task: &Task) -> TaskInfo {
+    let id = task.common().id().unwrap();
+    let containing: Vec<EventType> = ancestors(id).into_iter().filter_map(get_event).collect();
+    let mut tags = Vec::new();
+    let mut billable = None;
+    // The task itself comes first, so that it overrides the projects on `billable`
+    let props =
+        std::iter::once(task.common().props()).chain(containing.iter().map(|e| e.common().props()));
+    for props in props {
+        if let Some(list) = props.get(TAGS_PROP) {
+            for tag in list.split(',').map(str::trim).filter(|t| !t.is_empty()) {
+                if !tags.iter().any(|t| t == tag) {
+                    tags.push(tag.to_string());
+                }
+            }
+        }
+        if billable.is_none() {
+            billable = props.get(BILLABLE_PROP).map(|b| is_true(b));
+        }
+    }
+    TaskInfo {
+        name: task.common().name().to_string(),
+        project: containing
+            .first()
+            .map(|p| (p.common().id().unwrap(), p.common().name().to_string())),
+        tags,
+        billable: billable.unwrap_or(false),
+    }
+}
+
+/// The log types of the logged sessions, by task and start time
+fn session_log_types() -> HashMap<(i32, i64), String> {
+    let mut types = HashMap::new();
+    for log in get_logs() {
+        let props = get_props_for(log.id);
+        let task = props.get("task").and_then(|t| t.parse::<i32>().ok());
+        let start = props.get("start").and_then(time::parse_datetime);
+        if let (Some(task), Some(start), Some(typ)) = (task, start, props.get(TYPE_PROP)) {
+            types.insert((task, start.timestamp()), typ.clone());
+        }
+    }
+    types
+}
+
+/// `session` cut to `range`, then into the parts falling into each period
+fn split(
+    session: &Interval,
+    range: &Interval,
+    period: Option<ReportPeriod>,
+) -> Vec<(Option<NaiveDate>, Duration)> {
+    let mut start = session.start().max(range.start());
+    let end = session.end().min(range.end());
+    let mut parts = Vec::new();
+    while start < end {
+        let date = start.naive_local().date();
+        let (key, part_end) = match period {
+            Some(period) => (
+                Some(period.start_of(date)),
+                time::start_of_day(period.next(date)).map_or(end, |t| t.min(end)),
+            ),
+            None => (None, end),
+        };
+        parts.push((key, part_end - start));
+        start = part_end;
+    }
+    parts
+}
+
+/// The time recorded on tasks within `range`, grouped by `group` and split by `period` if given.
+/// Sessions running over the edges of the range or of a period only count for the part inside.
+pub fn time_report(range: &Interval, group: ReportGroup, period: Option<ReportPeriod>) -> Report {
+    let log_types = match group {
+        ReportGroup::LogType => session_log_types(),
+        _ => HashMap::new(),
+    };
+    // Rows of tasks and projects with the same name are told apart by their ids
+    let mut rows: BTreeMap<(Option<NaiveDate>, String, Option<i32>), ReportRow> = BTreeMap::new();
+    let mut total = Duration::zero();
+    let mut billable = Duration::zero();
+    for task in get_tasks() {
+        if task.sessions().is_empty() {
+            continue;
+        }
+        let id = task.common().id().unwrap();
+        let info = task_info(&task);
+        for session in task.sessions() {
+            let keys: Vec<(String, Option<i32>)> = match group {
+                ReportGroup::Task => vec![(info.name.clone(), Some(id))],
+                ReportGroup::Project => vec![match &info.project {
+                    Some((project, name)) => (name.clone(), Some(*project)),
+                    None => (String::new(), None),
+                }],
+                ReportGroup::Tag if info.tags.is_empty() => vec![(String::new(), None)],
+                ReportGroup::Tag => info.tags.iter().map(|t| (t.clone(), None)).collect(),
+                ReportGroup::LogType => vec![(
+                    log_types
+                        .get(&(id, session.start().timestamp()))
+                        .cloned()
+                        .unwrap_or_default(),
+                    None,
+                )],
+            };
+            for (date, length) in split(session, range, period) {
+                total = total + length;
+                if info.billable {
+                    billable = billable + length;
+                }
+                for (key, key_id) in keys.iter() {
+                    let row =
+                        rows.entry((date, key.clone(), *key_id))
+                            .or_insert_with(|| ReportRow {
+                                period: date,
+                                id: *key_id,
+                                key: key.clone(),
+                                total: Duration::zero(),
+                                billable: Duration::zero(),
+                            });
+                    row.total = row.total + length;
+                    if info.billable {
+                        row.billable = row.billable + length;
+                    }
+                }
+            }
+        }
+    }
+    Report {
+        range: *range,
+        group,
+        period,
+        rows: rows.into_values().collect(),
+        total,
+        billable,
+    }
+}
+
+/// A duration in hours, to two decimals
+fn hours(d: Duration) -> String {
+    format!("{:.2}", d.num_seconds() as f64 / 3600.0)
+}
+
+fn period_column(report: &Report, row: &ReportRow) -> String {
+    match (report.period, row.period) {
+        (Some(ReportPeriod::Month), Some(date)) => date.format("%Y-%m").to_string(),
+        (_, Some(date)) => date.format("%Y-%m-%d").to_string(),
+        (_, None) => String::new(),
+    }
+}
+
+/// `report` as an aligned table, with the hours of each row and a total line
+pub fn render_text(report: &Report) -> String {
+    let mut header = vec![report.group.name().to_string()];
+    if let Some(period) = report.period {
+        header.insert(0, period.name().to_string());
+    }
+    header.extend(
+        ["total", "billable", "non-billable"]
+            .iter()
+            .map(|s| s.to_string()),
+    );
+    let mut lines = vec![header];
+    for row in report.rows.iter() {
+        let mut line = vec![if row.key.is_empty() {
+            "-".to_string()
+        } else {
+            row.key.clone()
+        }];
+        if report.period.is_some() {
+            line.insert(0, period_column(report, row));
+        }
+        line.extend(vec![
+            hours(row.total),
+            hours(row.billable),
+            hours(row.non_billable()),
+        ]);
+        lines.push(line);
+    }
+    let mut footer = vec!["total".to_string()];
+    if report.period.is_some() {
+        footer.insert(0, String::new());
+    }
+    footer.extend(vec![
+        hours(report.total),
+        hours(report.billable),
+        hours(report.non_billable()),
+    ]);
+    lines.push(footer);
+
+    let columns = lines[0].len();
+    let widths: Vec<usize> = (0..columns)
+        .map(|i| {
+            lines
+                .iter()
+                .map(|l| l[i].chars().count())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    // The hours are right aligned, the rest left aligned
+    let text_columns = columns - 3;
+    let mut out = String::new();
+    for line in lines {
+        let cells: Vec<String> = line
+            .iter()
+            .enumerate()
+            .map(|(i, cell)| {
+                if i < text_columns {
+                    format!("{:<width$}", cell, width = widths[i])
+                } else {
+                    format!("{:>width$}", cell, width = widths[i])
+                }
+            })
+            .collect();
+        out.push_str(cells.join("  ").trim_end());
+        out.push('\n');
+    }
+    out
+}
+
+fn csv_field(s: &str) -> String {
+    if s.contains(&[',', '"', '\n', '\r'][..]) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+/// `report` as CSV with a header line, one line per row and the hours as decimal numbers. The
+/// period column is only there when the report is split by period.
+pub fn render_csv(report: &Report) -> String {
+    let mut header = Vec::new();
+    if let Some(period) = report.period {
+        header.push(period.name());
+    }
+    header.extend(&[
+        report.group.name(),
+        "total_hours",
+        "billable_hours",
+        "non_billable_hours",
+    ]);
+    let mut out = header.join(",");
+    out.push_str("\r\n");
+    for row in report.rows.iter() {
+        let mut fields = Vec::new();
+        if report.period.is_some() {
+            fields.push(period_column(report, row));
+        }
+        fields.push(csv_field(&row.key));
+        fields.push(hours(row.total));
+        fields.push(hours(row.billable));
+        fields.push(hours(row.non_billable()));
+        out.push_str(&fields.join(","));
+        out.push_str("\r\n");
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(s: &str) -> chrono::DateTime<chrono::Local> {
+        time::parse_datetime(s).unwrap()
+    }
+
+    fn between(start: &str, end: &str) -> Interval {
+        Interval::builder()
+            .start(at(start))
+            .end(at(end))
+            .build()
+            .unwrap()
+    }
+
+    fn task_with_session(name: &str, session: Interval) -> i32 {
+        let mut task = Task::new(EventCommon::new(name.into(), Priority::Medium));
+        task.add_session(session);
+        add_task(&mut task)
+    }
+
+    #[test]
+    fn keeps_tasks_with_the_same_name_apart() {
+        let first = task_with_session("review", between("2026-01-05 09:00", "2026-01-05 10:00"));
+        let second = task_with_session("review", between("2026-01-05 11:00", "2026-01-05 13:00"));
+        let range = between("2026-01-05", "2026-01-06");
+        let report = time_report(&range, ReportGroup::Task, Some(ReportPeriod::Day));
+        let rows: Vec<_> = report
+            .rows
+            .iter()
+            .map(|r| (r.id, r.key.as_str(), r.total))
+            .collect();
+        assert_eq!(
+            rows,
+            vec![
+                (Some(first), "review", Duration::hours(1)),
+                (Some(second), "review", Duration::hours(2)),
+            ]
+        );
+        assert_eq!(report.total, Duration::hours(3));
+    }
+
+    #[test]
+    fn splits_sessions_by_period() {
+        let session = between("2026-01-04 22:00", "2026-01-05 02:00");
+        let range = between("2026-01-01", "2026-02-01");
+        let date = |s| NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap();
+        assert_eq!(
+            split(&session, &range, Some(ReportPeriod::Day)),
+            vec![
+                (Some(date("2026-01-04")), Duration::hours(2)),
+                (Some(date("2026-01-05")), Duration::hours(2)),
+            ]
+        );
+        // 2026-01-05 is a Monday
+        assert_eq!(
+            split(&session, &range, Some(ReportPeriod::Week)),
+            vec![
+                (Some(date("2025-12-29")), Duration::hours(2)),
+                (Some(date("2026-01-05")), Duration::hours(2)),
+            ]
+        );
+        assert_eq!(
+            split(&session, &between("2026-01-05 01:00", "2026-02-01"), None),
+            vec![(None, Duration::hours(1))]
+        );
+    }
+}
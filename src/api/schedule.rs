@@ -0,0 +1,540 @@
+//! Automatic placement of task sessions into free time. The scheduler takes the fixed events as
+//! busy time, and fills the available time that is left with sessions of the unfinished tasks,
+//! earliest deadline first and most important first among tasks due at the same time.
+use std::cmp::Reverse;
+
+use chrono::{DateTime, Duration, Local, TimeZone};
+
+use super::{availability::*, dependency::*, event::*, state::API_STATE, time};
+
+#[derive(Debug, Clone)]
+pub struct SchedulerConfig {
+    /// When tasks may be scheduled
+    pub availability: Availability,
+    /// Sessions shorter than this are only placed to finish off a task
+    pub min_session: Duration,
+    pub max_session: Duration,
+}
+
+impl Default for SchedulerConfig {
+    fn default() -> Self {
+        Self {
+            availability: Availability::default(),
+            min_session: Duration::minutes(30),
+            max_session: Duration::hours(2),
+        }
+    }
+}
+
+/// How much time a task still needs, by when, and which unfinished tasks (with what lag) it has
+/// to wait for
+#[derive(Debug, Clone)]
+pub struct TaskDemand {
+    pub id: i32,
+    pub priority: Priority,
+    pub remaining: Duration,
+    pub deadline: Option<DateTime<Local>>,
+    pub depends_on: Vec<(i32, Duration)>,
+}
+
+/// A proposed session of the task `task`, with why it was placed there
+#[derive(Debug, Clone)]
+pub struct Placement {
+    pub task: i32,
+    pub interval: Interval,
+    pub reason: String,
+}
+
+/// A task that couldn't be (fully) scheduled
+#[derive(Debug, Clone)]
+pub struct Unplaced {
+    pub task: i32,
+    pub remaining: Duration,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Schedule {
+    pub placements: Vec<Placement>,
+    pub unplaced: Vec<Unplaced>,
+}
+
+fn ordinal(n: usize) -> String {
+    let suffix = match (n % 10, n % 100) {
+        (1, 11) | (2, 12) | (3, 13) => "th",
+        (1, _) => "st",
+        (2, _) => "nd",
+        (3, _) => "rd",
+        _ => "th",
+    };
+    format!("{}{}", n, suffix)
+}
+
+/// Places sessions for `demands` in the available time of `window` after `now` that isn't `busy`.
+/// A task is only placed after the last session of every task it depends on, plus the lag.
+///
+/// Placements of an earlier run in `previous` are kept as long as they are still in the future
+/// (or ongoing), still free and still needed, so re-running after a change only moves what the
+/// change affects. Everything else is placed again from scratch, as are the tasks waiting for
+/// others in `demands`, since what they wait for may move.
+pub fn plan(
+    window: &Interval,
+    busy: &[Interval],
+    demands: &[TaskDemand],
+    config: &SchedulerConfig,
+    previous: &[Placement],
+    now: DateTime<Local>,
+) -> Schedule {
+    let mut schedule = Schedule::default();
+    let mut remaining: Vec<Duration> = demands.iter().map(|d| d.remaining).collect();
+
+    let position = |id: i32| demands.iter().position(|d| d.id == id);
+
+    let mut previous = previous.to_vec();
+    previous.sort_by_key(|p| p.interval.start());
+    for p in previous {
+        let i = match position(p.task) {
+            Some(i) => i,
+            None => continue,
+        };
+        if demands[i]
+            .depends_on
+            .iter()
+            .any(|(on, _)| position(*on).is_some())
+        {
+            continue;
+        }
+        let still_free = p.interval.overlaps(window)
+            && p.interval.end() > now
+            && !busy.iter().any(|b| b.overlaps(&p.interval))
+            && !schedule
+                .placements
+                .iter()
+                .any(|q| q.interval.overlaps(&p.interval));
+        if still_free && remaining[i] > Duration::zero() {
+            // Only keep what is inside the window and still needed
+            let start = p.interval.start().max(window.start());
+            let end = p.interval.end().min(window.end()).min(start + remaining[i]);
+            let interval = Interval::from_start(start, end - start);
+            remaining[i] = remaining[i] - interval.length();
+            schedule.placements.push(Placement { interval, ..p });
+        }
+    }
+
+    let start = window.start().max(now);
+    let mut free = match Interval::builder().start(start).end(window.end()).build() {
+        Ok(w) => config.availability.available_in(&w),
+        Err(_) => Vec::new(),
+    };
+    let kept: Vec<_> = schedule.placements.iter().map(|p| p.interval).collect();
+    free = subtract(subtract(free, busy), &kept);
+
+    let mut by_urgency: Vec<&TaskDemand> = demands.iter().collect();
+    by_urgency.sort_by_key(|d| (d.deadline.is_none(), d.deadline, Reverse(d.priority)));
+    // Every task comes after the ones it waits for, and the most urgent first otherwise
+    let ids: Vec<i32> = by_urgency.iter().map(|d| d.id).collect();
+    let dependencies: Vec<Dependency> = demands
+        .iter()
+        .flat_map(|d| {
+            d.depends_on
+                .iter()
+                .map(move |&(depends_on, lag)| Dependency {
+                    task: d.id,
+                    depends_on,
+                    lag,
+                })
+        })
+        .collect();
+    let order: Vec<usize> = topological_order(&ids, &dependencies)
+        .unwrap_or(ids)
+        .into_iter()
+        .filter_map(position)
+        .collect();
+
+    for (rank, &i) in order.iter().enumerate() {
+        let demand = &demands[i];
+        let mut why_first = match demand.deadline {
+            Some(due) => format!(
+                "due {}, {} priority",
+                time::format_datetime(&due),
+                demand.priority
+            ),
+            None => format!("no deadline, {} priority", demand.priority),
+        };
+
+        let mut earliest = start;
+        let mut blocked = None;
+        for &(on, lag) in demand.depends_on.iter() {
+            let j = match position(on) {
+                Some(j) => j,
+                None => continue,
+            };
+            if remaining[j] > Duration::zero() {
+                blocked = Some(on);
+                break;
+            }
+            let end = schedule
+                .placements
+                .iter()
+                .filter(|p| p.task == on)
+                .map(|p| p.interval.end())
+                .max();
+            if let Some(end) = end {
+                earliest = earliest.max(end + lag);
+            }
+            why_first.push_str(&format!(", after task {}", on));
+        }
+        if let Some(on) = blocked {
+            schedule.unplaced.push(Unplaced {
+                task: demand.id,
+                remaining: remaining[i],
+                reason: format!("depends on task {}, which couldn't be fully scheduled", on),
+            });
+            continue;
+        }
+
+        // Sessions are spread out over the free slots, unless that makes the task late while
+        // putting them back to back doesn't
+        let mut spread = free.clone();
+        let mut sessions = fill(&mut spread, earliest, remaining[i], config, false);
+        if let Some(due) = demand.deadline {
+            let late = |sessions: &[Interval]| sessions.iter().filter(|s| s.end() > due).count();
+            let mut packed = free.clone();
+            let packed_sessions = fill(&mut packed, earliest, remaining[i], config, true);
+            if late(&packed_sessions) < late(&sessions) {
+                spread = packed;
+                sessions = packed_sessions;
+            }
+        }
+        free = spread;
+        for interval in sessions {
+            let late = demand.deadline.map_or(false, |due| interval.end() > due);
+            schedule.placements.push(Placement {
+                task: demand.id,
+                interval,
+                reason: format!(
+                    "{} in line ({}); {}",
+                    ordinal(rank + 1),
+                    why_first,
+                    if late {
+                        "no free time left before the deadline, so placed as early as possible"
+                    } else {
+                        "earliest free time"
+                    }
+                ),
+            });
+            remaining[i] = remaining[i] - interval.length();
+        }
+        if remaining[i] > Duration::zero() {
+            schedule.unplaced.push(Unplaced {
+                task: demand.id,
+                remaining: remaining[i],
+                reason: "not enough free time in the window".into(),
+            });
+        }
+    }
+
+    schedule.placements.sort_by_key(|p| p.interval.start());
+    schedule
+}
+
+/// Takes sessions adding up to at most `needed` out of the `free` slots after `earliest`, in
+/// order. With `back_to_back`, each slot is used up before moving on to the next one; otherwise
+/// each pass over the slots takes at most one session from each, so sessions are spread out
+/// before they are put back to back.
+fn fill(
+    free: &mut Vec<Interval>,
+    earliest: DateTime<Local>,
+    needed: Duration,
+    config: &SchedulerConfig,
+    back_to_back: bool,
+) -> Vec<Interval> {
+    let mut sessions = Vec::new();
+    let mut remaining = needed;
+    loop {
+        let before = remaining;
+        let mut slot = 0;
+        while remaining > Duration::zero() && slot < free.len() {
+            let slot_interval = free[slot];
+            if slot_interval.end() <= earliest {
+                slot += 1;
+                continue;
+            }
+            let available = if slot_interval.start() < earliest {
+                Interval::from_start(earliest, slot_interval.end() - earliest)
+            } else {
+                slot_interval
+            };
+            let length = remaining.min(config.max_session).min(available.length());
+            if length < config.min_session && length < remaining {
+                slot += 1;
+                continue;
+            }
+            let interval = Interval::from_start(available.start(), length);
+            sessions.push(interval);
+            remaining = remaining - length;
+            let mut rest = Vec::new();
+            if slot_interval.start() < interval.start() {
+                rest.push(Interval::from_start(
+                    slot_interval.start(),
+                    interval.start() - slot_interval.start(),
+                ));
+            }
+            let left_after = interval.end() < slot_interval.end();
+            if left_after {
+                rest.push(Interval::from_start(
+                    interval.end(),
+                    slot_interval.end() - interval.end(),
+                ));
+            }
+            let parts = rest.len();
+            free.splice(slot..=slot, rest);
+            slot += if back_to_back && left_after {
+                // Carry on right after this session
+                parts - 1
+            } else {
+                // Leave the rest of this slot to other tasks for this pass
+                parts
+            };
+        }
+        if remaining == before {
+            break;
+        }
+    }
+    sessions
+}
+
+/// What the unfinished task `task` still needs according to its estimate, and by when. A subtask
+/// is due by the deadline of its projects if it has none of its own or a later one. `depends_on`
+/// is what it waits for among the unfinished tasks.
+fn demand_of(
+    task: &Task,
+    project_deadline: Option<DateTime<Local>>,
+    depends_on: Vec<(i32, Duration)>,
+) -> Result<TaskDemand, String> {
+    let remaining = task.remaining().ok_or_else(|| "no estimate".to_string())?;
+    let deadline = match (task.deadline(), project_deadline) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    };
+    Ok(TaskDemand {
+        id: task.common().id().unwrap(),
+        priority: task.common().priority(),
+        remaining,
+        deadline,
+        depends_on,
+    })
+}
+
+/// The stored plan, in order
+pub fn get_plan() -> Vec<Placement> {
+    API_STATE.with(|s| {
+        s.lock()
+            .unwrap()
+            .storage
+            .get_planned_sessions()
+            .into_iter()
+            .map(|r| Placement {
+                task: r.id,
+                interval: Interval::from_start(
+                    Local.from_utc_datetime(&r.start),
+                    Duration::seconds(r.length),
+                ),
+                reason: r.reason,
+            })
+            .collect()
+    })
+}
+
+pub fn get_plan_for(task: i32) -> Vec<Placement> {
+    get_plan().into_iter().filter(|p| p.task == task).collect()
+}
+
+/// Plans the unfinished tasks into `window` around the stored events, keeping what still fits of
+/// the stored plan, and stores the new plan in place of the old one.
+pub fn run_scheduler(window: &Interval, config: &SchedulerConfig) -> Schedule {
+    let busy = busy_in(window);
+    let tasks: Vec<Task> = get_tasks()
+        .into_iter()
+        .filter(|t| !t.common().finished())
+        .collect();
+    let unfinished: Vec<i32> = tasks.iter().filter_map(|t| t.common().id()).collect();
+    let dependencies = get_dependencies();
+    let projects = get_projects();
+    // The earliest deadline of the projects containing each task, at any depth
+    let project_deadline = |id: i32| {
+        projects
+            .iter()
+            .filter(|p| p.tasks().iter().any(|t| t.common().id() == Some(id)))
+            .filter_map(Project::deadline)
+            .min()
+    };
+    let mut demands = Vec::new();
+    let mut unplaced = Vec::new();
+    for task in tasks.iter() {
+        let id = task.common().id().unwrap();
+        let depends_on = dependencies
+            .iter()
+            .filter(|d| d.task == id && unfinished.contains(&d.depends_on))
+            .map(|d| (d.depends_on, d.lag))
+            .collect();
+        match demand_of(task, project_deadline(id), depends_on) {
+            Ok(d) if d.remaining > Duration::zero() => demands.push(d),
+            Ok(_) => {}
+            Err(reason) => unplaced.push(Unplaced {
+                task: id,
+                remaining: Duration::zero(),
+                reason,
+            }),
+        }
+    }
+    // Whatever waits for a task that can't be scheduled can't be scheduled either
+    while let Some((i, on)) = demands.iter().enumerate().find_map(|(i, d)| {
+        d.depends_on
+            .iter()
+            .find(|(on, _)| unplaced.iter().any(|u| u.task == *on))
+            .map(|(on, _)| (i, *on))
+    }) {
+        let demand = demands.remove(i);
+        unplaced.push(Unplaced {
+            task: demand.id,
+            remaining: demand.remaining,
+            reason: format!("depends on task {}, which can't be scheduled", on),
+        });
+    }
+
+    let mut schedule = plan(window, &busy, &demands, config, &get_plan(), Local::now());
+    schedule.unplaced.extend(unplaced);
+    API_STATE.with(|s| {
+        let storage = &mut s.lock().unwrap().storage;
+        storage.clear_planned_sessions();
+        for p in schedule.placements.iter() {
+            storage.add_planned_session(
+                p.task,
+                p.interval.start().naive_utc(),
+                p.interval.length().num_seconds(),
+                &p.reason,
+            );
+        }
+    });
+    schedule
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(s: &str) -> DateTime<Local> {
+        time::parse_datetime(s).unwrap()
+    }
+
+    fn between(start: &str, end: &str) -> Interval {
+        Interval::from_start(at(start), at(end) - at(start))
+    }
+
+    fn demand(id: i32, hours: i64, deadline: Option<&str>) -> TaskDemand {
+        TaskDemand {
+            id,
+            priority: Priority::Medium,
+            remaining: Duration::hours(hours),
+            deadline: deadline.map(at),
+            depends_on: Vec::new(),
+        }
+    }
+
+    fn intervals(schedule: &Schedule) -> Vec<Interval> {
+        schedule.placements.iter().map(|p| p.interval).collect()
+    }
+
+    // 2026-01-05 is a Monday
+    const MONDAY: &str = "2026-01-05";
+
+    #[test]
+    fn spreads_sessions_over_days() {
+        let window = between(MONDAY, "2026-01-10");
+        let schedule = plan(
+            &window,
+            &[],
+            &[demand(1, 6, None)],
+            &SchedulerConfig::default(),
+            &[],
+            at(MONDAY),
+        );
+        assert_eq!(
+            intervals(&schedule),
+            vec![
+                between("2026-01-05 09:00", "2026-01-05 11:00"),
+                between("2026-01-06 09:00", "2026-01-06 11:00"),
+                between("2026-01-07 09:00", "2026-01-07 11:00"),
+            ]
+        );
+    }
+
+    #[test]
+    fn puts_sessions_back_to_back_to_meet_the_deadline() {
+        let window = between(MONDAY, "2026-01-10");
+        let schedule = plan(
+            &window,
+            &[],
+            &[demand(1, 6, Some("2026-01-06"))],
+            &SchedulerConfig::default(),
+            &[],
+            at(MONDAY),
+        );
+        assert_eq!(
+            intervals(&schedule),
+            vec![
+                between("2026-01-05 09:00", "2026-01-05 11:00"),
+                between("2026-01-05 11:00", "2026-01-05 13:00"),
+                between("2026-01-05 13:00", "2026-01-05 15:00"),
+            ]
+        );
+        assert!(schedule
+            .placements
+            .iter()
+            .all(|p| p.reason.ends_with("earliest free time")));
+    }
+
+    #[test]
+    fn only_labels_late_sessions_as_late() {
+        let window = between(MONDAY, "2026-01-10");
+        let schedule = plan(
+            &window,
+            &[],
+            &[demand(1, 10, Some("2026-01-06"))],
+            &SchedulerConfig::default(),
+            &[],
+            at(MONDAY),
+        );
+        let late: Vec<_> = schedule
+            .placements
+            .iter()
+            .filter(|p| p.reason.contains("no free time left"))
+            .map(|p| p.interval)
+            .collect();
+        assert_eq!(late, vec![between("2026-01-06 09:00", "2026-01-06 11:00")]);
+    }
+
+    #[test]
+    fn keeps_previous_placements_within_the_window() {
+        let window = between(MONDAY, "2026-01-05 12:00");
+        let previous = Placement {
+            task: 1,
+            interval: between("2026-01-05 10:00", "2026-01-05 14:00"),
+            reason: "earlier".into(),
+        };
+        let schedule = plan(
+            &window,
+            &[],
+            &[demand(1, 1, None)],
+            &SchedulerConfig::default(),
+            &[previous],
+            at(MONDAY),
+        );
+        assert_eq!(
+            intervals(&schedule),
+            vec![between("2026-01-05 10:00", "2026-01-05 11:00")]
+        );
+        assert!(schedule.unplaced.is_empty());
+    }
+}
@@ -1,17 +1,31 @@
 use std::collections::HashMap;
 use std::sync::Mutex;
 
-use super::{event::*, log::*};
+use super::{
+    agenda::AgendaConfig, availability::Availability, conflict::ConflictPolicy, event::*,
+    habit::Habit, lifecycle::Lifecycle, log::*,
+};
 use crate::storage::Storage;
 
 pub struct APIState<'lua> {
     pub(crate) storage: LogStorage,
     pub(crate) log_types: LogTypes,
+    /// Transitions that happened but whose handlers haven't been run yet
+    pub(crate) pending: Vec<(i32, Lifecycle)>,
+    pub(crate) conflict_policy: ConflictPolicy,
+    pub(crate) calendars: HashMap<String, Availability>,
+    pub(crate) habits: HashMap<String, Habit>,
+    pub(crate) agenda: AgendaConfig,
 }
 
 lazy_static! {
     pub static API_STATE: Mutex<APIState<'static>> = Mutex::new(APIState {
         storage: Storage::new(),
         log_types: HashMap::new(),
+        pending: Vec::new(),
+        conflict_policy: ConflictPolicy::Warn,
+        calendars: HashMap::new(),
+        habits: HashMap::new(),
+        agenda: AgendaConfig::default(),
     });
 }
@@ -0,0 +1,212 @@
+//! Parsing and formatting of the times and durations accepted by the API, so that Lua and the
+//! command line agree on what a time looks like
+use chrono::{DateTime, Duration, Local, NaiveDate, NaiveDateTime, TimeZone};
+
+const DATETIME_FORMATS: &[&str] = &[
+    "%Y-%m-%d %H:%M:%S",
+    "%Y-%m-%d %H:%M",
+    "%Y-%m-%dT%H:%M:%S",
+    "%Y-%m-%dT%H:%M",
+];
+
+/// Parses a local time. Accepts `now`, RFC 3339, `YYYY-MM-DD HH:MM[:SS]` (with either a space or
+/// a `T`), and `YYYY-MM-DD` meaning the start of that day.
+pub fn parse_datetime<S: AsRef<str>>(s: S) -> Option<DateTime<Local>> {
+    let s = s.as_ref().trim();
+    if s == "now" {
+        return Some(Local::now());
+    }
+    if let Ok(t) = DateTime::parse_from_rfc3339(s) {
+        return Some(t.with_timezone(&Local));
+    }
+    let naive = DATETIME_FORMATS
+        .iter()
+        .find_map(|fmt| NaiveDateTime::parse_from_str(s, fmt).ok())
+        .or_else(|| {
+            NaiveDate::parse_from_str(s, "%Y-%m-%d")
+                .ok()
+                .map(|d| d.and_hms(0, 0, 0))
+        })?;
+    Local.from_local_datetime(&naive).earliest()
+}
+
+/// The first moment of the local day `date`: midnight, or when the clocks skip midnight, the end
+/// of the gap. `None` if the gap is over three hours long, which no time zone does.
+pub fn start_of_day(date: NaiveDate) -> Option<DateTime<Local>> {
+    let midnight = date.and_hms(0, 0, 0);
+    (0..=3 * 60)
+        .map(|minutes| midnight + Duration::minutes(minutes))
+        .find_map(|t| Local.from_local_datetime(&t).earliest())
+}
+
+/// Formats a local time the way it is handed out to scripts.
+pub fn format_datetime(t: &DateTime<Local>) -> String {
+    t.to_rfc3339()
+}
+
+/// Parses a duration. Accepts a plain number of seconds, `HH:MM`, or any sequence of
+/// `<n><unit>` with units `w`, `d`, `h`, `m` and `s` (e.g. `1h30m`). A leading `-` negates it.
+/// Durations too long to represent are rejected.
+pub fn parse_duration<S: AsRef<str>>(s: S) -> Option<Duration> {
+    let s = s.as_ref().trim();
+    if let Some(rest) = s.strip_prefix('-') {
+        return parse_duration(rest).map(|d| -d);
+    }
+    if is_number(s) {
+        return seconds(s.parse().ok()?, 1);
+    }
+    if let Some(colon) = s.find(':') {
+        let (hours, minutes) = (&s[..colon], &s[colon + 1..]);
+        if !is_number(hours) || !is_number(minutes) {
+            return None;
+        }
+        return seconds(hours.parse().ok()?, 3600)?
+            .checked_add(&seconds(minutes.parse().ok()?, 60)?);
+    }
+
+    let mut total = Duration::zero();
+    let mut num = String::new();
+    for c in s.chars() {
+        if c.is_ascii_digit() {
+            num.push(c);
+            continue;
+        }
+        let n = num.parse::<i64>().ok()?;
+        num.clear();
+        let unit = match c {
+            'w' => 7 * 86400,
+            'd' => 86400,
+            'h' => 3600,
+            'm' => 60,
+            's' => 1,
+            _ => return None,
+        };
+        total = total.checked_add(&seconds(n, unit)?)?;
+    }
+    if num.is_empty() && !s.is_empty() {
+        Some(total)
+    } else {
+        None
+    }
+}
+
+fn is_number(s: &str) -> bool {
+    !s.is_empty() && s.chars().all(|c| c.is_ascii_digit())
+}
+
+/// `n` units of `unit` seconds each, or `None` if that doesn't fit in a `Duration`
+pub(crate) fn seconds(n: i64, unit: i64) -> Option<Duration> {
+    let millis = n.checked_mul(unit)?.checked_mul(1000)?;
+    Some(Duration::milliseconds(millis))
+}
+
+/// Formats a duration in the `1h30m` form understood by `parse_duration`.
+pub fn format_duration(d: &Duration) -> String {
+    if *d < Duration::zero() {
+        return format!("-{}", format_duration(&-*d));
+    }
+    let secs = d.num_seconds();
+    let (days, hours, minutes, seconds) = (
+        secs / 86400,
+        secs % 86400 / 3600,
+        secs % 3600 / 60,
+        secs % 60,
+    );
+    let mut s = String::new();
+    for (n, unit) in [(days, 'd'), (hours, 'h'), (minutes, 'm'), (seconds, 's')].iter() {
+        if *n != 0 {
+            s.push_str(&format!("{}{}", n, unit));
+        }
+    }
+    if s.is_empty() {
+        s.push_str("0s");
+    }
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::event::{Interval, IntervalBuildError};
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse_duration("90"), Some(Duration::seconds(90)));
+        assert_eq!(parse_duration("1:30"), Some(Duration::minutes(90)));
+        assert_eq!(parse_duration("1h30m"), Some(Duration::minutes(90)));
+        assert_eq!(parse_duration("-2d"), Some(Duration::days(-2)));
+        assert_eq!(parse_duration("1w1s"), Some(Duration::seconds(604801)));
+    }
+
+    #[test]
+    fn rejects_invalid_durations() {
+        for s in &["", "-", "1x", "1h30", "1:-30", "1:+30", ":30", "1:"] {
+            assert_eq!(parse_duration(s), None, "{}", s);
+        }
+    }
+
+    #[test]
+    fn rejects_overflowing_durations() {
+        assert_eq!(parse_duration("99999999999999w"), None);
+        assert_eq!(parse_duration("9223372036854775807"), None);
+        assert_eq!(parse_duration("99999999999999:00"), None);
+        assert_eq!(parse_duration("9000000000000000s9000000000000000s"), None);
+        assert_eq!(parse_duration("-9223372036854776"), None);
+    }
+
+    #[test]
+    fn builds_intervals() {
+        let start = parse_datetime("2020-09-07 09:00").unwrap();
+        let end = parse_datetime("2020-09-07 10:30").unwrap();
+        let length = parse_duration("1h30m").unwrap();
+        let expected = Ok(Interval::from_start(start, length));
+        assert_eq!(
+            Interval::builder().start(start).length(length).build(),
+            expected
+        );
+        assert_eq!(
+            Interval::builder().length(length).end(end).build(),
+            expected
+        );
+        assert_eq!(Interval::builder().start(start).end(end).build(), expected);
+        assert_eq!(
+            Interval::builder()
+                .start(start)
+                .length(length)
+                .end(end)
+                .build(),
+            expected
+        );
+        assert_eq!(
+            Interval::builder()
+                .start(start)
+                .length(length)
+                .end(start)
+                .build(),
+            Err(IntervalBuildError::InconsistentConstraints {
+                start,
+                length,
+                end: start
+            })
+        );
+        assert_eq!(
+            Interval::builder().start(end).end(start).build(),
+            Err(IntervalBuildError::NegativeLength(-length))
+        );
+    }
+
+    #[test]
+    fn rejects_intervals_out_of_range() {
+        let now = Local::now();
+        let length = parse_duration("100000000w").unwrap();
+        let out_of_range = Err(IntervalBuildError::OutOfRange(length));
+        assert_eq!(
+            Interval::builder().start(now).length(length).build(),
+            out_of_range
+        );
+        assert_eq!(
+            Interval::builder().length(length).end(now).build(),
+            out_of_range
+        );
+    }
+}
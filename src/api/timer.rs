@@ -0,0 +1,83 @@
+//! Time tracking for tasks. At most one task has a running timer; stopping it records the time
+//! since it was started as a session of the task. The running timer lives in storage, so it
+//! survives the program going down in the middle of a session.
+use std::collections::HashMap;
+
+use chrono::{DateTime, Duration, Local, TimeZone};
+
+use super::{error::*, event::*, log::*, state::API_STATE, time};
+
+/// The task whose timer is running, and since when
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RunningTimer {
+    pub task: i32,
+    pub start: DateTime<Local>,
+}
+
+impl RunningTimer {
+    pub fn elapsed(&self, now: DateTime<Local>) -> Duration {
+        (now - self.start).max(Duration::zero())
+    }
+}
+
+pub fn current_task() -> Option<RunningTimer> {
+    API_STATE.with(|s| {
+        s.lock().unwrap().storage.get_timer().map(|t| RunningTimer {
+            task: t.id,
+            start: Local.from_utc_datetime(&t.start),
+        })
+    })
+}
+
+/// Stores the session of `timer` ending at `now` and logs it. The timer is only cleared once the
+/// session is stored, so it is never lost in between.
+fn record_session(timer: &RunningTimer, now: DateTime<Local>) -> Interval {
+    let session = Interval::from_start(timer.start, timer.elapsed(now));
+    add_session(timer.task, &session);
+    API_STATE.with(|s| s.lock().unwrap().storage.clear_timer());
+    let name = get_event(timer.task).map_or_else(String::new, |e| e.common().name().to_string());
+    let mut props = HashMap::new();
+    props.insert("task".to_string(), timer.task.to_string());
+    props.insert("start".to_string(), time::format_datetime(&session.start()));
+    props.insert("end".to_string(), time::format_datetime(&session.end()));
+    props.insert(
+        "length".to_string(),
+        time::format_duration(&session.length()),
+    );
+    add_log_with_props(format!("worked on task {}", name), "", &props);
+    session
+}
+
+/// Starts the timer of the task `id`, stopping the one running for another task first. Returns
+/// the session recorded by stopping it, if any. Starting the running timer again does nothing,
+/// and finished tasks can't be started.
+pub fn start_task(id: i32) -> Result<Option<Interval>> {
+    let task = get_task_checked("start_task", id)?;
+    if task.common().finished() {
+        return Err(Error {
+            method: "start_task".into(),
+            kind: ErrorKind::TaskFinished(id),
+        });
+    }
+    let now = Local::now();
+    let stopped = match current_task() {
+        Some(timer) if timer.task == id => return Ok(None),
+        Some(timer) => Some(record_session(&timer, now)),
+        None => None,
+    };
+    API_STATE.with(|s| s.lock().unwrap().storage.set_timer(id, now.naive_utc()));
+    add_log(format!("started task {}", task.common().name()), "");
+    Ok(stopped)
+}
+
+/// Stops the timer of the task `id`, returning the session it recorded
+pub fn stop_task(id: i32) -> Result<Interval> {
+    get_task_checked("stop_task", id)?;
+    match current_task() {
+        Some(timer) if timer.task == id => Ok(record_session(&timer, Local::now())),
+        _ => Err(Error {
+            method: "stop_task".into(),
+            kind: ErrorKind::TimerNotRunning(id),
+        }),
+    }
+}
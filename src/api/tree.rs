@@ -0,0 +1,165 @@
+//! Queries and changes on the hierarchy of projects and tasks. Projects can contain tasks and
+//! other projects to any depth, through the parent id of each item in storage.
+use super::{error::*, event::*, state::API_STATE};
+
+pub fn parent_of(id: i32) -> Option<i32> {
+    API_STATE.with(|s| {
+        s.lock()
+            .unwrap()
+            .storage
+            .get_event(id)
+            .and_then(|r| r.parent)
+    })
+}
+
+/// The items directly inside `id`
+pub fn children(id: i32) -> Vec<i32> {
+    API_STATE.with(|s| {
+        s.lock()
+            .unwrap()
+            .storage
+            .get_children(id)
+            .into_iter()
+            .map(|r| r.id)
+            .collect()
+    })
+}
+
+/// The projects containing `id`, the closest first
+pub fn ancestors(id: i32) -> Vec<i32> {
+    let mut ancestors = Vec::new();
+    let mut current = parent_of(id);
+    while let Some(parent) = current {
+        // Moves never create cycles, but don't loop forever on a broken hierarchy either
+        if ancestors.contains(&parent) {
+            break;
+        }
+        ancestors.push(parent);
+        current = parent_of(parent);
+    }
+    ancestors
+}
+
+/// Everything inside `id` at any depth, each item before its contents
+pub fn subtree(id: i32) -> Vec<i32> {
+    let mut subtree = Vec::new();
+    let mut stack = children(id);
+    stack.reverse();
+    while let Some(item) = stack.pop() {
+        if item == id || subtree.contains(&item) {
+            continue;
+        }
+        subtree.push(item);
+        let mut inner = children(item);
+        inner.reverse();
+        stack.extend(inner);
+    }
+    subtree
+}
+
+fn wrong_kind(id: i32, expected: &str, found: &EventType) -> Error {
+    Error {
+        method: "move_item".into(),
+        kind: ErrorKind::WrongEventKind {
+            id,
+            expected: expected.into(),
+            found: found.kind().into(),
+        },
+    }
+}
+
+fn get(id: i32) -> Result<EventType> {
+    get_event(id).ok_or_else(|| Error {
+        method: "move_item".into(),
+        kind: ErrorKind::InvalidEventId(id),
+    })
+}
+
+/// Moves the task or project `id` into the project `parent`, or to the top level if `parent` is
+/// `None`. A project can't be moved into itself or anything inside it. The projects it leaves are
+/// finished if that leaves only finished work in them, and the ones it enters are unfinished if
+/// it brings unfinished work along.
+pub fn move_item(id: i32, parent: Option<i32>) -> Result<()> {
+    let item = get(id)?;
+    if let EventType::Event(_) = item {
+        return Err(wrong_kind(id, "task or project", &item));
+    }
+    if let Some(parent) = parent {
+        match get(parent)? {
+            EventType::Project(_) => {}
+            p => return Err(wrong_kind(parent, "project", &p)),
+        }
+        if parent == id || subtree(id).contains(&parent) {
+            return Err(Error {
+                method: "move_item".into(),
+                kind: ErrorKind::CyclicHierarchy { id, parent },
+            });
+        }
+    }
+    let old_parent = parent_of(id);
+    API_STATE.with(|s| s.lock().unwrap().storage.set_parent(id, parent));
+    for project in old_parent.iter().chain(parent.iter()) {
+        roll_up(*project);
+    }
+    Ok(())
+}
+
+/// Brings whether the project `id` and the projects containing it are finished in line with
+/// what they contain
+pub(crate) fn roll_up(id: i32) {
+    let project = match get_event(id) {
+        Some(EventType::Project(p)) => p,
+        _ => return,
+    };
+    if !project.is_complete() {
+        let projects: Vec<i32> = std::iter::once(id).chain(ancestors(id)).collect();
+        API_STATE.with(|s| {
+            let storage = &mut s.lock().unwrap().storage;
+            for project in projects {
+                storage.set_finished(project, false);
+            }
+        });
+    } else if !project.common().finished() {
+        finish_event(id);
+    } else if let Some(parent) = parent_of(id) {
+        roll_up(parent);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task(name: &str) -> Task {
+        Task::new(EventCommon::new(name.into(), Priority::Medium))
+    }
+
+    fn finished(id: i32) -> bool {
+        get_event(id).unwrap().common().finished()
+    }
+
+    #[test]
+    fn rolls_up_finished_state() {
+        let outer = add_project(&mut Project::new(EventCommon::new(
+            "launch".into(),
+            Priority::Medium,
+        )));
+        let inner = add_subproject(
+            outer,
+            &mut Project::new(EventCommon::new("docs".into(), Priority::Medium)),
+        );
+        let write = add_subtask(inner, &mut task("write"));
+        finish_event(write);
+        assert!(finished(inner) && finished(outer));
+
+        update_task(write, &task("write"));
+        assert!(!finished(write) && !finished(inner) && !finished(outer));
+
+        finish_event(write);
+        assert!(finished(outer));
+        let review = add_subtask(inner, &mut task("review"));
+        assert!(!finished(inner) && !finished(outer));
+        finish_event(review);
+        assert!(finished(inner) && finished(outer));
+    }
+}
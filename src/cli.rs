@@ -0,0 +1,457 @@
+//! The command line: its arguments, and the subcommands that work on the stored logs directly
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use chrono::{Duration, Local, TimeZone};
+use clap::{crate_version, App, AppSettings, Arg, ArgMatches, SubCommand};
+
+use sched_test::{
+    api::{
+        self,
+        chart::ChartOutput,
+        error::ErrorKind,
+        export, ical,
+        quick::{add_quick_log, QuickLog},
+        time, Interval, LogQuery,
+    },
+    script::daemon::DaemonConfig,
+    storage::model::Log,
+};
+
+const TIME_FORMAT: &str = "%Y-%m-%d %H:%M";
+/// The longest `--poll` the daemon takes, a day
+const MAX_POLL_SECS: i64 = 24 * 60 * 60;
+const ADD_HELP: &str = "\
+The first word is the name and the other plain words the description. `+tag` adds a tag, \
+`@context` (or `context=`, but only one of them) sets the context and `KEY=VALUE` sets a prop. \
+Words with spaces in them are always plain, and so are words starting with `\\`.
+
+    sched add \"fixed CI\" -t bugfix +ci @work finished=yes";
+
+fn filter_args<'a, 'b>(cmd: App<'a, 'b>) -> App<'a, 'b> {
+    cmd.arg(
+        Arg::with_name("FILTER")
+            .multiple(true)
+            .help("Words like `type:TYPE`, `KEY=VALUE` or part of the name"),
+    )
+    .arg(
+        Arg::with_name("from")
+            .long("from")
+            .value_name("TIME")
+            .help("Only logs from this time on"),
+    )
+    .arg(
+        Arg::with_name("to")
+            .long("to")
+            .value_name("TIME")
+            .help("Only logs before this time (default: now)"),
+    )
+}
+
+fn prop_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("prop")
+        .short("p")
+        .long("prop")
+        .value_name("KEY=VALUE")
+        .multiple(true)
+        .number_of_values(1)
+        .help("Sets a prop")
+}
+
+fn format_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("format")
+        .long("format")
+        .value_name("FORMAT")
+        .possible_values(&["json", "ical"])
+        .help("json for logs or ical for events and tasks (default: json)")
+}
+
+pub fn app() -> App<'static, 'static> {
+    App::new("sched")
+        .version(crate_version!())
+        .about("Logs and schedules, scripted in Lua")
+        .setting(AppSettings::VersionlessSubcommands)
+        .arg(
+            Arg::with_name("config-dir")
+                .long("config-dir")
+                .value_name("DIR")
+                .global(true)
+                .help("Where init.lua is (default: the `sched` config directory)"),
+        )
+        .arg(
+            Arg::with_name("db")
+                .long("db")
+                .value_name("DATABASE")
+                .global(true)
+                .help("The database to use"),
+        )
+        .arg(
+            Arg::with_name("profile")
+                .long("profile")
+                .value_name("NAME")
+                .global(true)
+                .help("Uses a database of its own, and sets `profile` for init.lua"),
+        )
+        .subcommand(
+            SubCommand::with_name("add")
+                .about("Adds a log")
+                .after_help(ADD_HELP)
+                .arg(
+                    Arg::with_name("WORDS")
+                        .required(true)
+                        .multiple(true)
+                        .help("The name, description, +tags, @context and KEY=VALUE props"),
+                )
+                .arg(
+                    Arg::with_name("type")
+                        .short("t")
+                        .long("type")
+                        .value_name("TYPE")
+                        .help("The log type, whose defaults fill in the props not given"),
+                )
+                .arg(prop_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("list").about("Lists the logs").arg(
+                Arg::with_name("limit")
+                    .short("n")
+                    .long("limit")
+                    .value_name("N")
+                    .help("Only the last N"),
+            ),
+        )
+        .subcommand(
+            SubCommand::with_name("show")
+                .about("Shows a log with its props")
+                .arg(Arg::with_name("ID").required(true)),
+        )
+        .subcommand(
+            SubCommand::with_name("edit")
+                .about("Changes a log")
+                .arg(Arg::with_name("ID").required(true))
+                .arg(Arg::with_name("name").long("name").value_name("NAME"))
+                .arg(Arg::with_name("desc").long("desc").value_name("DESC"))
+                .arg(prop_arg())
+                .arg(
+                    Arg::with_name("unset")
+                        .short("u")
+                        .long("unset")
+                        .value_name("KEY")
+                        .multiple(true)
+                        .number_of_values(1)
+                        .help("Removes a prop"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("rm")
+                .about("Removes logs")
+                .arg(Arg::with_name("ID").required(true).multiple(true)),
+        )
+        .subcommand(filter_args(
+            SubCommand::with_name("query").about("Lists the logs matching a filter"),
+        ))
+        .subcommand(filter_args(
+            SubCommand::with_name("export")
+                .about("Writes the logs as JSON, or the events and tasks as iCalendar")
+                .arg(format_arg())
+                .arg(
+                    Arg::with_name("output")
+                        .short("o")
+                        .long("output")
+                        .value_name("FILE")
+                        .help("Where to write (default: standard output)"),
+                ),
+        ))
+        .subcommand(
+            SubCommand::with_name("import")
+                .about(
+                    "Adds logs from JSON, skipping the ones with the time and name of a stored \
+                     log, or events and tasks from iCalendar",
+                )
+                .arg(Arg::with_name("FILE").required(true))
+                .arg(format_arg().help("json or ical (default: ical for .ics files)")),
+        )
+        .subcommand(SubCommand::with_name("repl").about("Starts the Lua REPL"))
+        .subcommand(
+            SubCommand::with_name("run")
+                .about("Runs a Lua script, with its arguments in `arg`")
+                .setting(AppSettings::TrailingVarArg)
+                .arg(Arg::with_name("SCRIPT").required(true))
+                .arg(Arg::with_name("ARGS").multiple(true)),
+        )
+        .subcommand(
+            SubCommand::with_name("eval")
+                .about("Evaluates Lua code and prints its values")
+                .arg(Arg::with_name("CODE").required(true)),
+        )
+        .subcommand(
+            SubCommand::with_name("agenda")
+                .about("Prints the agenda")
+                .arg(Arg::with_name("SPAN").possible_values(&["day", "week"])),
+        )
+        .subcommand(
+            SubCommand::with_name("heatmap")
+                .about("Prints a calendar of how many logs there are each day")
+                .arg(Arg::with_name("TYPE"))
+                .arg(Arg::with_name("name").long("name").value_name("NAME"))
+                .arg(Arg::with_name("width").long("width").value_name("COLUMNS"))
+                .arg(
+                    Arg::with_name("plain")
+                        .long("plain")
+                        .help("Prints a table instead"),
+                ),
+        )
+        .subcommand(SubCommand::with_name("tui").about("Starts the terminal UI"))
+        .subcommand(
+            SubCommand::with_name("daemon")
+                .about("Runs the handlers of events as their times come")
+                .arg(
+                    Arg::with_name("command")
+                        .long("command")
+                        .value_name("CMD")
+                        .help("A shell command to run for every transition"),
+                )
+                .arg(
+                    Arg::with_name("poll")
+                        .long("poll")
+                        .value_name("SECONDS")
+                        .help(
+                            "The longest to sleep at a time, 60 by default and at most a day. \
+                             Events added or changed by other commands are only picked up when \
+                             waking up, so within this time",
+                        ),
+                ),
+        )
+}
+
+fn parse_id(s: &str) -> Result<i32, String> {
+    s.parse().map_err(|_| format!("Invalid id: '{}'", s))
+}
+
+fn parse_number<T: std::str::FromStr>(s: &str, what: &str) -> Result<T, String> {
+    s.parse()
+        .map_err(|_| format!("Invalid number of {}: '{}'", what, s))
+}
+
+fn parse_time(s: &str) -> Result<chrono::DateTime<Local>, String> {
+    time::parse_datetime(s).ok_or_else(|| format!("Invalid time: '{}'", s))
+}
+
+fn parse_props(args: &ArgMatches) -> Result<HashMap<String, String>, String> {
+    args.values_of("prop")
+        .into_iter()
+        .flatten()
+        .map(|prop| match prop.find('=') {
+            Some(eq) => Ok((prop[..eq].to_string(), prop[eq + 1..].to_string())),
+            None => Err(format!("Props are written as KEY=VALUE, not '{}'", prop)),
+        })
+        .collect()
+}
+
+fn get_log(id: i32) -> Result<Log, String> {
+    api::get_log(id).ok_or_else(|| format!("No log with id {}", id))
+}
+
+fn print_logs(mut logs: Vec<Log>, limit: Option<usize>) {
+    logs.sort_by_key(|log| log.time);
+    let skip = limit.map_or(0, |limit| logs.len().saturating_sub(limit));
+    for log in logs.into_iter().skip(skip) {
+        let time = Local.from_utc_datetime(&log.time).format(TIME_FORMAT);
+        if log.desc.is_empty() {
+            println!("{:>5}  {}  {}", log.id, time, log.name);
+        } else {
+            println!("{:>5}  {}  {}: {}", log.id, time, log.name, log.desc);
+        }
+    }
+}
+
+/// The query of the filter arguments
+fn query(args: &ArgMatches) -> Result<LogQuery, String> {
+    let filter: Vec<&str> = args.values_of("FILTER").into_iter().flatten().collect();
+    let mut query = LogQuery::parse(&filter.join(" "));
+    if args.is_present("from") || args.is_present("to") {
+        let start = match args.value_of("from") {
+            Some(from) => parse_time(from)?,
+            None => Local.timestamp(0, 0),
+        };
+        let end = match args.value_of("to") {
+            Some(to) => parse_time(to)?,
+            None => Local::now(),
+        };
+        let range = Interval::builder()
+            .start(start)
+            .end(end)
+            .build()
+            .map_err(|e| e.to_string())?;
+        query.range = Some(range);
+    }
+    Ok(query)
+}
+
+/// `sched add WORDS... [--type TYPE] [--prop KEY=VALUE]...`, printing the id of the new log
+pub fn add(args: &ArgMatches) -> Result<(), String> {
+    let words: Vec<&str> = args.values_of("WORDS").unwrap().collect();
+    let mut log = QuickLog::parse(&words).map_err(|e| e.to_string())?;
+    log.log_type = args.value_of("type").map(String::from);
+    log.props.extend(parse_props(args)?);
+    let id = add_quick_log(log).map_err(|e| match &e.kind {
+        ErrorKind::MissingFields { fields, .. } => {
+            let fields: Vec<String> = fields.iter().map(|f| format!("{}=...", f)).collect();
+            format!("{}\nGive them as {}", e, fields.join(" "))
+        }
+        ErrorKind::InvalidLogType(_) => {
+            let mut types: Vec<String> = api::get_log_types().into_keys().collect();
+            types.sort();
+            if types.is_empty() {
+                format!("{}\nNo log types are defined in init.lua", e)
+            } else {
+                format!("{}\nThe log types are: {}", e, types.join(", "))
+            }
+        }
+        _ => e.to_string(),
+    })?;
+    println!("{}", id);
+    Ok(())
+}
+
+pub fn list(args: &ArgMatches) -> Result<(), String> {
+    let limit = match args.value_of("limit") {
+        Some(limit) => Some(parse_number(limit, "logs")?),
+        None => None,
+    };
+    print_logs(api::get_logs(), limit);
+    Ok(())
+}
+
+pub fn show(args: &ArgMatches) -> Result<(), String> {
+    let log = get_log(parse_id(args.value_of("ID").unwrap())?)?;
+    println!("Log {}: {}", log.id, log.name);
+    println!(
+        "Time: {}",
+        Local.from_utc_datetime(&log.time).format(TIME_FORMAT)
+    );
+    if !log.desc.is_empty() {
+        println!("Description: {}", log.desc);
+    }
+    let props = api::get_props_for(log.id);
+    let mut keys: Vec<&String> = props.keys().collect();
+    keys.sort();
+    for key in keys {
+        println!("  {}: {}", key, props[key]);
+    }
+    Ok(())
+}
+
+pub fn edit(args: &ArgMatches) -> Result<(), String> {
+    let log = get_log(parse_id(args.value_of("ID").unwrap())?)?;
+    let mut props = api::get_props_for(log.id);
+    props.extend(parse_props(args)?);
+    for key in args.values_of("unset").into_iter().flatten() {
+        props.remove(key);
+    }
+    let name = args.value_of("name").unwrap_or(&log.name);
+    let desc = args.value_of("desc").unwrap_or(&log.desc);
+    api::update_log(log.id, name, desc, &props);
+    Ok(())
+}
+
+/// `sched rm ID...`. Nothing is removed if any of the ids is wrong.
+pub fn rm(args: &ArgMatches) -> Result<(), String> {
+    let ids = args
+        .values_of("ID")
+        .unwrap()
+        .map(|id| get_log(parse_id(id)?).map(|log| log.id))
+        .collect::<Result<Vec<i32>, String>>()?;
+    for id in ids {
+        api::delete_log(id);
+    }
+    Ok(())
+}
+
+pub fn query_cmd(args: &ArgMatches) -> Result<(), String> {
+    print_logs(api::query_logs(&query(args)?), None);
+    Ok(())
+}
+
+pub fn export(args: &ArgMatches) -> Result<(), String> {
+    let text = match args.value_of("format") {
+        Some("ical") => ical::export(),
+        _ => export::export_logs(&query(args)?),
+    };
+    match args.value_of("output") {
+        Some(path) => fs::write(path, text).map_err(|e| format!("{}: {}", path, e)),
+        None => {
+            println!("{}", text.trim_end());
+            Ok(())
+        }
+    }
+}
+
+pub fn import(args: &ArgMatches) -> Result<(), String> {
+    let path = args.value_of("FILE").unwrap();
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+    let is_ical = Path::new(path).extension().map_or(false, |e| e == "ics");
+    match args.value_of("format") {
+        Some("ical") => {}
+        Some(_) => return import_logs(&text),
+        None if is_ical => {}
+        None => return import_logs(&text),
+    }
+    let summary = ical::import(&text).map_err(|e| e.to_string())?;
+    println!(
+        "Created {}, updated {}, skipped {}",
+        summary.created.len(),
+        summary.updated.len(),
+        summary.skipped.len()
+    );
+    for reason in summary.skipped {
+        eprintln!("Skipped: {}", reason);
+    }
+    Ok(())
+}
+
+fn import_logs(text: &str) -> Result<(), String> {
+    let ids = export::import_logs(text).map_err(|e| e.to_string())?;
+    println!("Imported {} logs", ids.len());
+    Ok(())
+}
+
+/// How many days of agenda `sched agenda [day|week]` prints
+pub fn agenda_days(args: &ArgMatches) -> i64 {
+    match args.value_of("SPAN") {
+        Some("week") => 7,
+        _ => 1,
+    }
+}
+
+/// Reads `sched heatmap [TYPE] [--name NAME] [--width COLUMNS] [--plain]`
+pub fn heatmap_args(args: &ArgMatches) -> Result<(LogQuery, ChartOutput), String> {
+    let query = LogQuery {
+        log_type: args.value_of("TYPE").map(String::from),
+        name: args.value_of("name").map(String::from),
+        ..LogQuery::default()
+    };
+    let output = if args.is_present("plain") {
+        ChartOutput::Plain
+    } else if let Some(width) = args.value_of("width") {
+        ChartOutput::Terminal(parse_number(width, "columns")?)
+    } else {
+        ChartOutput::detect()
+    };
+    Ok((query, output))
+}
+
+/// Reads `sched daemon [--command CMD] [--poll SECONDS]`
+pub fn daemon_config(args: &ArgMatches) -> Result<DaemonConfig, String> {
+    let mut config = DaemonConfig {
+        command: args.value_of("command").map(String::from),
+        ..DaemonConfig::default()
+    };
+    if let Some(poll) = args.value_of("poll") {
+        let poll: i64 = parse_number(poll, "seconds")?;
+        config.poll = Duration::seconds(poll.clamp(1, MAX_POLL_SECS));
+    }
+    Ok(config)
+}
@@ -1,25 +1,123 @@
 use std::fs;
+use std::path::PathBuf;
+use std::process;
 
-use dirs::config_dir;
+use dirs::{config_dir, data_dir};
 
 use sched_test::*;
 
+mod cli;
+
+/// Prints `e` and exits with a failure status
+fn fail<E: std::fmt::Display>(e: E) -> ! {
+    eprintln!("{}", e);
+    process::exit(1);
+}
+
+fn create_dir(dir: &PathBuf) {
+    if !dir.is_dir() {
+        if let Err(e) = fs::create_dir_all(dir) {
+            fail(format!("{}: {}", dir.display(), e));
+        }
+    }
+}
+
 fn main() {
+    let matches = cli::app().get_matches();
+    let (command, args) = matches.subcommand();
+    // The global options can be given before or after the subcommand
+    let global = |name: &str| {
+        args.and_then(|args| args.value_of(name))
+            .or_else(|| matches.value_of(name))
+    };
+
+    // Has to happen before anything uses the API, which opens the database
+    let profile = global("profile");
+    match (global("db"), profile) {
+        (Some(db), _) => storage::set_database(db),
+        (None, Some(profile)) => {
+            let data_dir = data_dir().unwrap().join("sched");
+            create_dir(&data_dir);
+            let db = data_dir.join(format!("{}.db", profile));
+            storage::set_database(db.to_string_lossy());
+        }
+        (None, None) => {}
+    }
+    let config_dir = match global("config-dir") {
+        Some(dir) => PathBuf::from(dir),
+        None => config_dir().unwrap().join("sched"),
+    };
+    create_dir(&config_dir);
+
     let ctx = script::ScriptContext::new();
     if let Err(e) = ctx.init_lib() {
-        eprintln!("{}", e);
-        return;
-    };
-    let config_dir = config_dir().unwrap().join("sched");
-    if !config_dir.is_dir() {
-        if let Err(e) = fs::create_dir_all(&config_dir) {
-            eprintln!("{}", e);
-            return;
+        fail(e);
+    }
+    if !command.is_empty() {
+        if let Err(e) = ctx.set_mode(command) {
+            fail(e);
+        }
+    }
+    if let Some(profile) = profile {
+        if let Err(e) = ctx.set_profile(profile) {
+            fail(e);
         }
     }
     if let Err(e) = ctx.init_user(config_dir) {
-        eprintln!("{}", e);
-        return;
+        fail(e);
+    }
+
+    let args = match args {
+        Some(args) => args,
+        None => return,
     };
-    // ctx.repl();
+    let result = match command {
+        "add" => cli::add(args),
+        "list" => cli::list(args),
+        "show" => cli::show(args),
+        "edit" => cli::edit(args),
+        "rm" => cli::rm(args),
+        "query" => cli::query_cmd(args),
+        "export" => cli::export(args),
+        "import" => cli::import(args),
+        "repl" => {
+            ctx.repl();
+            Ok(())
+        }
+        "run" => {
+            let script = args.value_of("SCRIPT").unwrap();
+            let script_args: Vec<String> = args
+                .values_of("ARGS")
+                .into_iter()
+                .flatten()
+                .map(String::from)
+                .collect();
+            ctx.run_file(script, &script_args)
+                .map_err(|e| format!("{}: {}", script, e))
+        }
+        "eval" => match ctx.eval(args.value_of("CODE").unwrap()) {
+            Ok(values) if values.is_empty() => Ok(()),
+            Ok(values) => {
+                println!("{}", values);
+                Ok(())
+            }
+            Err(e) => Err(e.to_string()),
+        },
+        "agenda" => {
+            ctx.print_agenda(cli::agenda_days(args));
+            Ok(())
+        }
+        "heatmap" => cli::heatmap_args(args).map(|(query, output)| {
+            print!("{}", api::chart::log_heatmap(&query, output));
+        }),
+        "tui" => {
+            ctx.run_tui();
+            Ok(())
+        }
+        "daemon" => cli::daemon_config(args).map(|config| ctx.run_daemon(&config)),
+        _ => Ok(()),
+    };
+    if let Err(e) = result {
+        fail(e);
+    }
 }
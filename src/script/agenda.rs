@@ -0,0 +1,141 @@
+//! Lua side of the agenda
+
+use chrono::Local;
+use rlua::prelude::*;
+
+use super::event::{datetime_from_lua, datetime_to_lua, duration_from_lua, EventHandle};
+use crate::api::{
+    agenda::{self, Agenda, AgendaDay, AgendaItem, AgendaSection},
+    error::*,
+};
+
+/// Where the agenda filter of the scripts is kept
+const FILTER_KEY: &str = "sched_agenda_filter";
+
+impl<'lua> ToLua<'lua> for AgendaItem {
+    fn to_lua(self, ctx: LuaContext<'lua>) -> LuaResult<LuaValue<'lua>> {
+        let table = ctx.create_table()?;
+        table.set("section", self.section.name())?;
+        table.set("event", EventHandle(self.id))?;
+        table.set("kind", self.kind)?;
+        table.set("name", self.name)?;
+        table.set("priority", self.priority)?;
+        if let Some(interval) = self.interval {
+            table.set("interval", interval)?;
+        }
+        if let Some(deadline) = self.deadline {
+            table.set("deadline", datetime_to_lua(&deadline, ctx)?)?;
+        }
+        Ok(LuaValue::Table(table))
+    }
+}
+
+impl<'lua> ToLua<'lua> for AgendaDay {
+    fn to_lua(self, ctx: LuaContext<'lua>) -> LuaResult<LuaValue<'lua>> {
+        let table = ctx.create_table()?;
+        table.set("date", self.date.format("%Y-%m-%d").to_string())?;
+        table.set("events", self.events)?;
+        table.set("sessions", self.sessions)?;
+        Ok(LuaValue::Table(table))
+    }
+}
+
+impl<'lua> ToLua<'lua> for Agenda {
+    fn to_lua(self, ctx: LuaContext<'lua>) -> LuaResult<LuaValue<'lua>> {
+        let table = ctx.create_table()?;
+        table.set("range", self.range)?;
+        table.set("days", self.days)?;
+        table.set("overdue", self.overdue)?;
+        table.set("due", self.due)?;
+        table.set("habits", self.habits)?;
+        Ok(LuaValue::Table(table))
+    }
+}
+
+/// `set_agenda{sections=, due_within=, min_priority=, filter=}`: changes how the agenda looks.
+/// `sections` lists the sections to show in order, out of `"overdue"`, `"events"`, `"sessions"`,
+/// `"due"` and `"habits"`. `filter` is called with each item and leaves it out unless it returns
+/// true; `filter=false` removes the filter, so that nothing is left out. Anything not given stays
+/// as it was.
+pub fn set_agenda<'lua>(ctx: LuaContext<'lua>, table: LuaTable<'lua>) -> LuaResult<()> {
+    let mut config = agenda::get_agenda_config();
+    if let Some(sections) = table.get::<_, Option<Vec<String>>>("sections")? {
+        config.sections = sections
+            .iter()
+            .map(|s| s.parse::<AgendaSection>())
+            .collect::<std::result::Result<_, _>>()
+            .map_err(|kind| -> LuaError {
+                Error {
+                    method: "set_agenda".into(),
+                    kind,
+                }
+                .into()
+            })?;
+    }
+    match table.get::<_, LuaValue>("due_within")? {
+        LuaValue::Nil => {}
+        v => config.due_within = duration_from_lua(v)?,
+    }
+    if let Some(priority) = table.get("min_priority")? {
+        config.min_priority = priority;
+    }
+    match table.get::<_, LuaValue>("filter")? {
+        LuaValue::Nil => {}
+        LuaValue::Boolean(false) => ctx.set_named_registry_value(FILTER_KEY, LuaValue::Nil)?,
+        v => ctx.set_named_registry_value(FILTER_KEY, LuaFunction::from_lua(v, ctx)?)?,
+    }
+    agenda::set_agenda_config(config);
+    Ok(())
+}
+
+/// Reads `from` (default now) and `days` (default 1, or 7 with `week=true`, and at most a year),
+/// and builds the agenda of those days with the filter of the scripts applied
+fn build<'lua>(ctx: LuaContext<'lua>, table: Option<LuaTable<'lua>>) -> LuaResult<Agenda> {
+    let (from, days) = match table {
+        Some(table) => {
+            let from = match table.get::<_, LuaValue>("from")? {
+                LuaValue::Nil => Local::now(),
+                v => datetime_from_lua(v)?,
+            };
+            let week = table.get::<_, Option<bool>>("week")?.unwrap_or(false);
+            let days = table.get::<_, Option<i64>>("days")?;
+            (from, days.unwrap_or(if week { 7 } else { 1 }))
+        }
+        None => (Local::now(), 1),
+    };
+    let config = agenda::get_agenda_config();
+    let range = agenda::agenda_range(from.naive_local().date(), days);
+    let mut agenda = agenda::agenda(&range, Local::now(), &config);
+    if let Some(filter) = ctx.named_registry_value::<_, Option<LuaFunction>>(FILTER_KEY)? {
+        let mut result = Ok(());
+        agenda.retain(|item| {
+            if result.is_err() {
+                return true;
+            }
+            match filter.call::<_, bool>(item.clone()) {
+                Ok(keep) => keep,
+                Err(e) => {
+                    result = Err(e);
+                    true
+                }
+            }
+        });
+        result?;
+    }
+    Ok(agenda)
+}
+
+/// `agenda{from=, days=, week=}`: what the days hold, as `{range=, days=, overdue=, due=,
+/// habits=}` with each day `{date=, events=, sessions=}`
+pub fn agenda<'lua>(ctx: LuaContext<'lua>, table: Option<LuaTable<'lua>>) -> LuaResult<Agenda> {
+    build(ctx, table)
+}
+
+/// `agenda_text{from=, days=, week=}`: the same agenda as text to print
+pub fn agenda_text<'lua>(
+    ctx: LuaContext<'lua>,
+    table: Option<LuaTable<'lua>>,
+) -> LuaResult<String> {
+    let agenda = build(ctx, table)?;
+    Ok(agenda::render_agenda(&agenda, &agenda::get_agenda_config()))
+}
@@ -0,0 +1,119 @@
+//! Lua side of availability calendars
+
+use chrono::{Duration, Local, NaiveDate, NaiveTime};
+use rlua::prelude::*;
+
+use super::event::duration_from_lua;
+use crate::api::{self, error::*, Availability, ByDay, Interval};
+
+fn invalid(method: &str, field: &str, message: String) -> LuaError {
+    Error {
+        method: method.into(),
+        kind: ErrorKind::InvalidField {
+            field: field.into(),
+            message,
+        },
+    }
+    .into()
+}
+
+fn time_of_day(method: &str, field: &str, s: String) -> LuaResult<NaiveTime> {
+    NaiveTime::parse_from_str(&s, "%H:%M").map_err(|_| invalid(method, field, format!("'{}'", s)))
+}
+
+/// Reads the changes to `base` in `table`: the working hours as `work_days` (e.g. `{"MO",
+/// "TU"}`), `work_start` and `work_end` (e.g. `"09:00"`), `holidays` as dates (e.g.
+/// `"2020-12-25"`) and `blocked` intervals. Holidays and blocked ranges add to the ones of `base`.
+pub fn availability_from_table(
+    method: &str,
+    table: &LuaTable,
+    base: Availability,
+) -> LuaResult<Availability> {
+    let mut availability = base;
+    let hours = &mut availability.working_hours;
+    if let Some(days) = table.get::<_, Option<Vec<String>>>("work_days")? {
+        hours.days = days
+            .iter()
+            .map(|d| {
+                d.parse::<ByDay>()
+                    .map(|d| d.weekday)
+                    .map_err(|e| invalid(method, "work_days", e.to_string()))
+            })
+            .collect::<LuaResult<_>>()?;
+    }
+    if let Some(start) = table.get::<_, Option<String>>("work_start")? {
+        hours.start = time_of_day(method, "work_start", start)?;
+    }
+    if let Some(end) = table.get::<_, Option<String>>("work_end")? {
+        hours.end = time_of_day(method, "work_end", end)?;
+    }
+    if hours.start >= hours.end {
+        return Err(invalid(
+            method,
+            "work_end",
+            format!(
+                "{} isn't after `work_start` {}",
+                hours.end.format("%H:%M"),
+                hours.start.format("%H:%M")
+            ),
+        ));
+    }
+    if let Some(holidays) = table.get::<_, Option<Vec<String>>>("holidays")? {
+        for day in holidays {
+            let date = NaiveDate::parse_from_str(&day, "%Y-%m-%d")
+                .map_err(|_| invalid(method, "holidays", format!("'{}'", day)))?;
+            availability.holidays.push(date);
+        }
+    }
+    if let Some(blocked) = table.get::<_, Option<Vec<Interval>>>("blocked")? {
+        availability.blocked.extend(blocked);
+    }
+    Ok(availability)
+}
+
+/// `add_calendar{name=, work_days=, work_start=, work_end=, holidays=, blocked=}`: defines the
+/// calendar `name`, replacing any calendar of the same name. Fields left out are as in the
+/// default calendar (9:00 to 17:00 on weekdays, no holidays).
+pub fn add_calendar(table: LuaTable) -> LuaResult<()> {
+    let name: String = table
+        .get::<_, Option<String>>("name")?
+        .ok_or_else(|| -> LuaError {
+            Error {
+                method: "add_calendar".into(),
+                kind: ErrorKind::MissingField {
+                    typ: "calendar".into(),
+                    field: "name".into(),
+                },
+            }
+            .into()
+        })?;
+    let availability = availability_from_table("add_calendar", &table, Availability::default())?;
+    api::set_calendar(name, availability);
+    Ok(())
+}
+
+/// `block(range, calendar)`: makes `range` unavailable in `calendar` (default `"default"`). Like
+/// calendars themselves, blocked ranges aren't stored, so they belong in `init.lua`.
+pub fn block(range: Interval, calendar: Option<String>) {
+    api::block(calendar.as_deref().unwrap_or(api::DEFAULT_CALENDAR), range);
+}
+
+/// `free_slots{length=, within=, calendar=}`: the free intervals of at least `length` (default
+/// any) within the interval `within` (default the coming week), in the available time of
+/// `calendar` (default the default one), that no event takes up
+pub fn free_slots(table: LuaTable) -> LuaResult<Vec<Interval>> {
+    let length = match table.get::<_, LuaValue>("length")? {
+        LuaValue::Nil => Duration::zero(),
+        v => duration_from_lua(v)?,
+    };
+    let within = match table.get::<_, Option<Interval>>("within")? {
+        Some(within) => within,
+        None => Interval::builder()
+            .start(Local::now())
+            .length(Duration::weeks(1))
+            .build()
+            .unwrap(),
+    };
+    let calendar: Option<String> = table.get("calendar")?;
+    api::free_slots(&within, length, calendar.as_deref()).map_err(|e| e.into())
+}
@@ -0,0 +1,51 @@
+//! Lua side of CalDAV sync
+
+use rlua::prelude::*;
+
+use super::event::EventHandle;
+use crate::api::caldav::{self, CalDavConfig, ConflictResolution, SyncSummary};
+use crate::api::error::*;
+
+impl<'lua> ToLua<'lua> for SyncSummary {
+    fn to_lua(self, ctx: LuaContext<'lua>) -> LuaResult<LuaValue<'lua>> {
+        let handles = |ids: Vec<i32>| ids.into_iter().map(EventHandle).collect::<Vec<_>>();
+        let table = ctx.create_table()?;
+        table.set("uploaded", handles(self.uploaded))?;
+        table.set("downloaded", handles(self.downloaded))?;
+        table.set("removed", handles(self.removed))?;
+        table.set("deleted", handles(self.deleted))?;
+        table.set("conflicts", handles(self.conflicts))?;
+        table.set("errors", self.errors)?;
+        Ok(LuaValue::Table(table))
+    }
+}
+
+/// `sync_caldav{url=, user=, password=, on_conflict=}`: syncs with the collection at `url`.
+/// `on_conflict` is `"local"`, `"remote"` or `"skip"` (the default). Returns `{uploaded=,
+/// downloaded=, removed=, deleted=, conflicts=, errors=}`.
+pub fn sync_caldav(table: LuaTable) -> LuaResult<SyncSummary> {
+    let error = |kind| -> LuaError {
+        Error {
+            method: "sync_caldav".into(),
+            kind,
+        }
+        .into()
+    };
+    let url = table.get::<_, Option<String>>("url")?.ok_or_else(|| {
+        error(ErrorKind::MissingField {
+            typ: "caldav config".into(),
+            field: "url".into(),
+        })
+    })?;
+    let on_conflict = match table.get::<_, Option<String>>("on_conflict")? {
+        Some(s) => s.parse::<ConflictResolution>().map_err(error)?,
+        None => ConflictResolution::default(),
+    };
+    let config = CalDavConfig {
+        url,
+        user: table.get("user")?,
+        password: table.get("password")?,
+        on_conflict,
+    };
+    caldav::sync(&config).map_err(|e| e.into())
+}
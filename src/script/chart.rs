@@ -0,0 +1,91 @@
+//! Lua side of terminal charts
+
+use std::collections::HashMap;
+
+use rlua::prelude::*;
+
+use crate::api::{
+    chart::{self, ChartOutput},
+    error::*,
+    Interval, LogQuery,
+};
+
+/// Reads `width` (drawing for a terminal that wide) and `plain` (plain tables) from `table`. If
+/// neither is set, the charts fit standard output.
+pub fn output_from_table(table: Option<&LuaTable>) -> LuaResult<ChartOutput> {
+    let table = match table {
+        Some(table) => table,
+        None => return Ok(ChartOutput::detect()),
+    };
+    if table.get::<_, Option<bool>>("plain")?.unwrap_or(false) {
+        return Ok(ChartOutput::Plain);
+    }
+    Ok(match table.get::<_, Option<usize>>("width")? {
+        Some(width) => ChartOutput::Terminal(width),
+        None => ChartOutput::detect(),
+    })
+}
+
+/// A log type, or `{type=, name=, props=, range=}`
+fn query_from_lua(value: LuaValue) -> LuaResult<LogQuery> {
+    match value {
+        LuaValue::Nil => Ok(LogQuery::default()),
+        LuaValue::String(s) => Ok(LogQuery {
+            log_type: Some(s.to_str()?.to_string()),
+            ..LogQuery::default()
+        }),
+        LuaValue::Table(table) => Ok(LogQuery {
+            log_type: table.get("type")?,
+            name: table.get("name")?,
+            props: table
+                .get::<_, Option<HashMap<String, String>>>("props")?
+                .unwrap_or_default(),
+            range: table.get::<_, Option<Interval>>("range")?,
+        }),
+        _ => Err(Error {
+            method: "heatmap".into(),
+            kind: ErrorKind::InvalidField {
+                field: "query".into(),
+                message: "expected a log type or a table".into(),
+            },
+        }
+        .into()),
+    }
+}
+
+/// A list of `{label, value}` pairs, kept in order, or a table of values by label, sorted by label
+fn items_from_table(table: LuaTable) -> LuaResult<Vec<(String, f64)>> {
+    if table.raw_len() > 0 {
+        return table
+            .sequence_values::<LuaTable>()
+            .map(|item| {
+                let item = item?;
+                Ok((item.get(1)?, item.get(2)?))
+            })
+            .collect();
+    }
+    let mut items = table
+        .pairs::<String, f64>()
+        .collect::<LuaResult<Vec<_>>>()?;
+    items.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(items)
+}
+
+/// `bar_chart(items, {width=, plain=})`: `items` as horizontal bars
+pub fn bar_chart(items: LuaTable, opts: Option<LuaTable>) -> LuaResult<String> {
+    let output = output_from_table(opts.as_ref())?;
+    Ok(chart::bar_chart(&items_from_table(items)?, output))
+}
+
+/// `sparkline(values, {width=, plain=})`
+pub fn sparkline(values: Vec<f64>, opts: Option<LuaTable>) -> LuaResult<String> {
+    let output = output_from_table(opts.as_ref())?;
+    Ok(chart::sparkline(&values, output))
+}
+
+/// `heatmap(query, {width=, plain=})`: a calendar of how many logs matching `query` there are
+/// each day, `query` being a log type or `{type=, name=, props=, range=}`
+pub fn heatmap(query: LuaValue, opts: Option<LuaTable>) -> LuaResult<String> {
+    let output = output_from_table(opts.as_ref())?;
+    Ok(chart::log_heatmap(&query_from_lua(query)?, output))
+}
@@ -5,7 +5,10 @@ use std::path::Path;
 use either::*;
 use rlua::prelude::*;
 
-use super::lua;
+use super::{
+    agenda, availability, caldav, chart, daemon, event, focus, habit, ical, lua, report, schedule,
+    timer, tui,
+};
 use crate::api;
 
 pub struct ScriptContext {
@@ -96,16 +99,275 @@ impl ScriptContext {
                 "get_props_for",
                 ctx.create_function(|_, id| Ok(api::get_props_for(id)))?,
             )?;
+            globals.set(
+                "update_log",
+                ctx.create_function(|_, (id, s1, s2, p): (_, String, String, _)| {
+                    Ok(api::update_log(id, s1, s2, &p))
+                })?,
+            )?;
+            globals.set(
+                "delete_log",
+                ctx.create_function(|_, id| Ok(api::delete_log(id)))?,
+            )?;
             globals.set(
                 "add_log_with_type",
                 ctx.create_function(|_, (s1, s2, t, p, c): (String, String, _, _, _)| {
                     api::add_log_with_type(s1, s2, t, p, c).map_err(|e| e.into())
                 })?,
             )?;
+            globals.set("interval", ctx.create_function(|_, t| event::interval(t))?)?;
+            globals.set(
+                "add_event",
+                ctx.create_function(|ctx, t| event::add_event(ctx, t))?,
+            )?;
+            globals.set(
+                "add_task",
+                ctx.create_function(|ctx, t| event::add_task(ctx, t))?,
+            )?;
+            globals.set(
+                "add_project",
+                ctx.create_function(|ctx, t| event::add_project(ctx, t))?,
+            )?;
+            globals.set(
+                "get_event",
+                ctx.create_function(|_, id| Ok(event::get_event(id)))?,
+            )?;
+            globals.set(
+                "get_events",
+                ctx.create_function(|_, opts| event::get_events(opts))?,
+            )?;
+            globals.set(
+                "get_tasks",
+                ctx.create_function(|_, opts| event::get_tasks(opts))?,
+            )?;
+            globals.set(
+                "get_projects",
+                ctx.create_function(|_, opts| event::get_projects(opts))?,
+            )?;
+            globals.set(
+                "on",
+                ctx.create_function(|ctx, (name, f): (String, LuaFunction)| {
+                    event::set_handler(ctx, "on", None, name, f)
+                })?,
+            )?;
+            globals.set("tick", ctx.create_function(|ctx, ()| event::tick(ctx))?)?;
+            globals.set(
+                "progress",
+                ctx.create_function(|ctx, project| event::progress(ctx, project))?,
+            )?;
+            globals.set(
+                "overdue",
+                ctx.create_function(|_, ()| Ok(event::overdue()))?,
+            )?;
+            globals.set(
+                "critical_path",
+                ctx.create_function(|ctx, project| event::critical_path(ctx, project))?,
+            )?;
+            globals.set(
+                "topological_order",
+                ctx.create_function(|_, tasks| event::topological_order(tasks))?,
+            )?;
+            globals.set(
+                "conflicts",
+                ctx.create_function(|_, range| Ok(event::conflicts(range)))?,
+            )?;
+            globals.set(
+                "set_conflict_policy",
+                ctx.create_function(|_, policy| event::set_conflict_policy(policy))?,
+            )?;
+            globals.set(
+                "get_conflict_policy",
+                ctx.create_function(|_, ()| Ok(event::get_conflict_policy()))?,
+            )?;
+            globals.set(
+                "schedule",
+                ctx.create_function(|ctx, t| schedule::schedule(ctx, t))?,
+            )?;
+            globals.set(
+                "get_plan",
+                ctx.create_function(|_, ()| Ok(schedule::get_plan()))?,
+            )?;
+            globals.set(
+                "add_calendar",
+                ctx.create_function(|_, t| availability::add_calendar(t))?,
+            )?;
+            globals.set(
+                "block",
+                ctx.create_function(|_, (range, calendar)| {
+                    Ok(availability::block(range, calendar))
+                })?,
+            )?;
+            globals.set(
+                "free_slots",
+                ctx.create_function(|_, t| availability::free_slots(t))?,
+            )?;
+            globals.set(
+                "export_ical",
+                ctx.create_function(|_, path| ical::export_ical(path))?,
+            )?;
+            globals.set(
+                "import_ical",
+                ctx.create_function(|_, path| ical::import_ical(path))?,
+            )?;
+            globals.set(
+                "sync_caldav",
+                ctx.create_function(|_, t| caldav::sync_caldav(t))?,
+            )?;
+            globals.set(
+                "start_task",
+                ctx.create_function(|_, id| timer::start_task(id))?,
+            )?;
+            globals.set(
+                "stop_task",
+                ctx.create_function(|_, id| timer::stop_task(id))?,
+            )?;
+            globals.set(
+                "current_task",
+                ctx.create_function(|_, ()| Ok(timer::current_task()))?,
+            )?;
+            globals.set(
+                "start_focus",
+                ctx.create_function(|_, (task, t)| focus::start_focus(task, t))?,
+            )?;
+            globals.set(
+                "focus_status",
+                ctx.create_function(|_, ()| Ok(focus::focus_status()))?,
+            )?;
+            globals.set(
+                "interrupt_focus",
+                ctx.create_function(|_, reason| focus::interrupt_focus(reason))?,
+            )?;
+            globals.set(
+                "stop_focus",
+                ctx.create_function(|_, ()| focus::stop_focus())?,
+            )?;
+            globals.set(
+                "focus_stats",
+                ctx.create_function(|_, range| Ok(focus::focus_stats(range)))?,
+            )?;
+            globals.set(
+                "focus",
+                ctx.create_function(|_, (task, t)| focus::focus(task, t))?,
+            )?;
+            globals.set(
+                "add_habit",
+                ctx.create_function(|_, t| habit::add_habit(t))?,
+            )?;
+            globals.set("habits", ctx.create_function(|_, ()| Ok(habit::habits()))?)?;
+            globals.set(
+                "time_report",
+                ctx.create_function(|_, t| report::time_report(t))?,
+            )?;
+            globals.set(
+                "time_report_text",
+                ctx.create_function(|_, t| report::time_report_text(t))?,
+            )?;
+            globals.set(
+                "time_report_csv",
+                ctx.create_function(|_, t| report::time_report_csv(t))?,
+            )?;
+            globals.set(
+                "time_report_chart",
+                ctx.create_function(|_, t| report::time_report_chart(t))?,
+            )?;
+            globals.set(
+                "bar_chart",
+                ctx.create_function(|_, (items, opts)| chart::bar_chart(items, opts))?,
+            )?;
+            globals.set(
+                "sparkline",
+                ctx.create_function(|_, (values, opts)| chart::sparkline(values, opts))?,
+            )?;
+            globals.set(
+                "heatmap",
+                ctx.create_function(|_, (query, opts)| chart::heatmap(query, opts))?,
+            )?;
+            globals.set(
+                "set_agenda",
+                ctx.create_function(|ctx, t| agenda::set_agenda(ctx, t))?,
+            )?;
+            globals.set(
+                "agenda",
+                ctx.create_function(|ctx, t| agenda::agenda(ctx, t))?,
+            )?;
+            globals.set(
+                "agenda_text",
+                ctx.create_function(|ctx, t| agenda::agenda_text(ctx, t))?,
+            )?;
+            globals.set("tui", ctx.create_function(|ctx, ()| tui::run(ctx))?)?;
             Ok(())
         })
     }
 
+    /// Sets the `mode` global, which tells `init.lua` how the program was started (e.g.
+    /// `"daemon"`)
+    pub fn set_mode(&self, mode: &str) -> LuaResult<()> {
+        self.lua.context(|ctx| ctx.globals().set("mode", mode))
+    }
+
+    /// Sets the `profile` global to the profile given on the command line
+    pub fn set_profile(&self, profile: &str) -> LuaResult<()> {
+        self.lua
+            .context(|ctx| ctx.globals().set("profile", profile))
+    }
+
+    /// Runs the script at `path`, with its name and `args` in the `arg` global like the
+    /// standalone Lua interpreter does
+    pub fn run_file<P: AsRef<Path>>(
+        &self,
+        path: P,
+        args: &[String],
+    ) -> Result<(), Either<IoError, LuaError>> {
+        let path = path.as_ref();
+        let code = read_to_string(path).map_err(Left)?;
+        self.lua
+            .context(|ctx| {
+                let arg = ctx.create_sequence_from(args.iter().cloned())?;
+                arg.set(0, path.to_string_lossy().into_owned())?;
+                ctx.globals().set("arg", arg)?;
+                ctx.load(&code)
+                    .set_name(path.to_string_lossy().as_ref())?
+                    .exec()
+            })
+            .map_err(Right)
+    }
+
+    /// Evaluates `code` as an expression, or runs it as statements if it isn't one, and gives
+    /// back its values formatted like the REPL shows them
+    pub fn eval(&self, code: &str) -> LuaResult<String> {
+        self.lua.context(|ctx| {
+            let values = ctx.load(code).eval::<LuaMultiValue>()?;
+            Ok(values
+                .iter()
+                .map(|value| lua::format_value(value, &ctx))
+                .collect::<Vec<_>>()
+                .join("\t"))
+        })
+    }
+
+    /// Prints the agenda of the `days` days starting today
+    pub fn print_agenda(&self, days: i64) {
+        let text = self.lua.context(|ctx| {
+            let opts = ctx.create_table()?;
+            opts.set("days", days)?;
+            agenda::agenda_text(ctx, Some(opts))
+        });
+        match text {
+            Ok(text) => print!("{}", text),
+            Err(e) => eprintln!("{}", e),
+        }
+    }
+
+    pub fn run_tui(&self) {
+        if let Err(e) = self.lua.context(tui::run) {
+            eprintln!("{}", e);
+        }
+    }
+
+    pub fn run_daemon(&self, config: &daemon::DaemonConfig) {
+        self.lua.context(|ctx| daemon::run(ctx, config));
+    }
+
     pub fn repl(&self) {
         self.lua.context(|ctx| {
             lua::repl(ctx);
@@ -0,0 +1,79 @@
+//! The long-running mode: wait for the timed transitions of the stored events, tasks and projects
+//! and run their handlers (and optionally a shell command) when they happen.
+
+use std::process::Command;
+use std::thread;
+
+use chrono::{Duration, Local};
+use rlua::prelude::*;
+
+use super::event::run_handlers;
+use crate::api::{self, Lifecycle};
+
+/// How far ahead to look for the next transition. Anything later is found on a later wake-up.
+const LOOKAHEAD_DAYS: i64 = 7;
+
+#[derive(Debug, Clone)]
+pub struct DaemonConfig {
+    /// Run through `sh -c` for every transition, with `SCHED_ID`, `SCHED_KIND`, `SCHED_NAME` and
+    /// `SCHED_LIFECYCLE` in its environment
+    pub command: Option<String>,
+    /// The longest the daemon sleeps at a time. Changes to the store by other processes are
+    /// picked up within this time, and so are transitions missed while the system was suspended
+    /// (the sleep clock doesn't advance during suspend).
+    pub poll: Duration,
+}
+
+impl Default for DaemonConfig {
+    fn default() -> Self {
+        Self {
+            command: None,
+            poll: Duration::minutes(1),
+        }
+    }
+}
+
+fn run_command(command: &str, id: i32, lifecycle: Lifecycle) {
+    let mut cmd = Command::new("sh");
+    cmd.arg("-c")
+        .arg(command)
+        .env("SCHED_ID", id.to_string())
+        .env("SCHED_LIFECYCLE", lifecycle.name());
+    if let Some(event) = api::get_event(id) {
+        cmd.env("SCHED_KIND", event.kind())
+            .env("SCHED_NAME", event.common().name());
+    }
+    match cmd.status() {
+        Ok(status) if !status.success() => {
+            eprintln!("Command for {} of {} failed: {}", lifecycle, id, status)
+        }
+        Ok(_) => {}
+        Err(e) => eprintln!("Couldn't run command for {} of {}: {}", lifecycle, id, e),
+    }
+}
+
+/// Runs forever, handling transitions as they happen. Every wake-up fires everything that
+/// happened since the previous check, so nothing is lost by waking up late, and the first one
+/// fires what happened while the daemon wasn't running. Failing handlers and commands are
+/// reported on stderr and don't stop the daemon.
+pub fn run(ctx: LuaContext, config: &DaemonConfig) {
+    loop {
+        let now = Local::now();
+        api::check_transitions(now);
+        let transitions = api::take_pending();
+        if let Some(command) = &config.command {
+            for (id, lifecycle) in transitions.iter() {
+                run_command(command, *id, *lifecycle);
+            }
+        }
+        if let Err(e) = run_handlers(ctx, transitions) {
+            eprintln!("{}", e);
+        }
+
+        let now = Local::now();
+        let wake = api::next_transition(now, Duration::days(LOOKAHEAD_DAYS))
+            .map_or(config.poll, |next| (next - now).min(config.poll))
+            .max(Duration::zero());
+        thread::sleep(wake.to_std().unwrap_or_default());
+    }
+}
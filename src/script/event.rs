@@ -0,0 +1,907 @@
+//! Lua side of the event API: conversions of the event types from and to Lua values, and the
+//! functions exposed to scripts.
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::ops::Bound;
+
+use chrono::{DateTime, Duration, Local, TimeZone};
+use rlua::prelude::*;
+
+use crate::api::{
+    self, error::*, schedule, time, Conflict, ConflictPolicy, Event, EventCommon, EventType,
+    Interval, IntervalBuilder, Lifecycle, Priority, Project, Recurrence, RecurrenceRule, Task,
+};
+
+/// Name of the registry table mapping event ids to their tables of handlers
+const HANDLERS_KEY: &str = "sched_event_handlers";
+
+fn type_name(v: &LuaValue) -> &'static str {
+    match v {
+        LuaValue::Nil => "nil",
+        LuaValue::Boolean(_) => "boolean",
+        LuaValue::LightUserData(_) => "lightuserdata",
+        LuaValue::Integer(_) => "integer",
+        LuaValue::Number(_) => "number",
+        LuaValue::String(_) => "string",
+        LuaValue::Table(_) => "table",
+        LuaValue::Function(_) => "function",
+        LuaValue::Thread(_) => "thread",
+        LuaValue::UserData(_) => "userdata",
+        LuaValue::Error(_) => "error",
+    }
+}
+
+fn conversion_error(from: &'static str, to: &'static str, message: String) -> LuaError {
+    LuaError::FromLuaConversionError {
+        from,
+        to,
+        message: Some(message),
+    }
+}
+
+/// Reads a time from Lua, either as a Unix timestamp or as a string understood by
+/// `time::parse_datetime`.
+pub fn datetime_from_lua(value: LuaValue) -> LuaResult<DateTime<Local>> {
+    match value {
+        LuaValue::Integer(i) => timestamp_from_lua(i, "integer"),
+        LuaValue::Number(n) => timestamp_from_lua(n as i64, "number"),
+        LuaValue::String(s) => {
+            let s = s.to_str()?;
+            time::parse_datetime(s)
+                .ok_or_else(|| conversion_error("string", "DateTime", format!("'{}'", s)))
+        }
+        v => Err(conversion_error(type_name(&v), "DateTime", "".into())),
+    }
+}
+
+fn timestamp_from_lua(secs: i64, from: &'static str) -> LuaResult<DateTime<Local>> {
+    Local.timestamp_opt(secs, 0).single().ok_or_else(|| {
+        conversion_error(
+            from,
+            "DateTime",
+            format!("timestamp {} is out of range", secs),
+        )
+    })
+}
+
+pub fn datetime_to_lua<'lua>(
+    t: &DateTime<Local>,
+    ctx: LuaContext<'lua>,
+) -> LuaResult<LuaValue<'lua>> {
+    time::format_datetime(t).to_lua(ctx)
+}
+
+/// Reads a duration from Lua, either as a number of seconds or as a string understood by
+/// `time::parse_duration`.
+pub fn duration_from_lua(value: LuaValue) -> LuaResult<Duration> {
+    match value {
+        LuaValue::Integer(i) => seconds_from_lua(i, "integer"),
+        LuaValue::Number(n) => seconds_from_lua(n as i64, "number"),
+        LuaValue::String(s) => {
+            let s = s.to_str()?;
+            time::parse_duration(s)
+                .ok_or_else(|| conversion_error("string", "Duration", format!("'{}'", s)))
+        }
+        v => Err(conversion_error(type_name(&v), "Duration", "".into())),
+    }
+}
+
+fn seconds_from_lua(secs: i64, from: &'static str) -> LuaResult<Duration> {
+    time::seconds(secs, 1).ok_or_else(|| {
+        conversion_error(
+            from,
+            "Duration",
+            format!("{} seconds is out of range", secs),
+        )
+    })
+}
+
+/// Collects whichever of `start`, `length` and `end` are present in `table`.
+pub fn interval_builder_from_table(table: &LuaTable) -> LuaResult<IntervalBuilder> {
+    let mut builder = Interval::builder();
+    match table.get::<_, LuaValue>("start")? {
+        LuaValue::Nil => {}
+        v => {
+            builder.start(datetime_from_lua(v)?);
+        }
+    }
+    match table.get::<_, LuaValue>("length")? {
+        LuaValue::Nil => {}
+        v => {
+            builder.length(duration_from_lua(v)?);
+        }
+    }
+    match table.get::<_, LuaValue>("end")? {
+        LuaValue::Nil => {}
+        v => {
+            builder.end(datetime_from_lua(v)?);
+        }
+    }
+    Ok(builder)
+}
+
+impl<'lua> FromLua<'lua> for Interval {
+    fn from_lua(value: LuaValue<'lua>, _ctx: LuaContext<'lua>) -> LuaResult<Self> {
+        match value {
+            LuaValue::Table(t) => interval_builder_from_table(&t)?
+                .build()
+                .map_err(|e| conversion_error("table", "Interval", e.to_string())),
+            v => Err(conversion_error(type_name(&v), "Interval", "".into())),
+        }
+    }
+}
+
+impl<'lua> ToLua<'lua> for Interval {
+    fn to_lua(self, ctx: LuaContext<'lua>) -> LuaResult<LuaValue<'lua>> {
+        let table = ctx.create_table()?;
+        table.set("start", datetime_to_lua(&self.start(), ctx)?)?;
+        table.set("length", self.length().num_seconds())?;
+        table.set("end", datetime_to_lua(&self.end(), ctx)?)?;
+        Ok(LuaValue::Table(table))
+    }
+}
+
+/// `interval{start=, length=, end=}`: validates the given constraints and returns the complete
+/// interval.
+pub fn interval(table: LuaTable) -> LuaResult<Interval> {
+    interval_builder_from_table(&table)?.build().map_err(|e| {
+        Error {
+            method: "interval".into(),
+            kind: e.into(),
+        }
+        .into()
+    })
+}
+
+/// Priorities are given either as their level (1 to 9) or their name, and are handed out as their
+/// name
+impl<'lua> FromLua<'lua> for Priority {
+    fn from_lua(value: LuaValue<'lua>, _ctx: LuaContext<'lua>) -> LuaResult<Self> {
+        match value {
+            LuaValue::Integer(i) => Priority::try_from(i as i32)
+                .map_err(|e| conversion_error("integer", "Priority", e.to_string())),
+            LuaValue::String(s) => s
+                .to_str()?
+                .parse()
+                .map_err(|e: ErrorKind| conversion_error("string", "Priority", e.to_string())),
+            v => Err(conversion_error(type_name(&v), "Priority", "".into())),
+        }
+    }
+}
+
+impl<'lua> ToLua<'lua> for Priority {
+    fn to_lua(self, ctx: LuaContext<'lua>) -> LuaResult<LuaValue<'lua>> {
+        self.name().to_lua(ctx)
+    }
+}
+
+/// Recurrence rules are given either as an RRULE string (`"FREQ=WEEKLY;BYDAY=MO,WE"`) or as a table
+/// with the same parts in lowercase: `{freq="weekly", interval=, by_day={"MO", "WE"}, count=,
+/// until=}`
+impl<'lua> FromLua<'lua> for RecurrenceRule {
+    fn from_lua(value: LuaValue<'lua>, _ctx: LuaContext<'lua>) -> LuaResult<Self> {
+        let invalid = |e: ErrorKind| conversion_error("table", "RecurrenceRule", e.to_string());
+        match value {
+            LuaValue::String(s) => s.to_str()?.parse().map_err(|e: ErrorKind| {
+                conversion_error("string", "RecurrenceRule", e.to_string())
+            }),
+            LuaValue::Table(t) => {
+                let freq: String = t.get("freq")?;
+                let mut rule = RecurrenceRule::new(freq.parse().map_err(invalid)?);
+                rule.interval = t.get::<_, Option<u32>>("interval")?.unwrap_or(1);
+                for day in t
+                    .get::<_, Option<Vec<String>>>("by_day")?
+                    .unwrap_or_default()
+                {
+                    rule.by_day.push(day.parse().map_err(invalid)?);
+                }
+                rule.count = t.get("count")?;
+                rule.until = match t.get::<_, LuaValue>("until")? {
+                    LuaValue::Nil => None,
+                    v => Some(datetime_from_lua(v)?),
+                };
+                rule.validate().map_err(invalid)?;
+                Ok(rule)
+            }
+            v => Err(conversion_error(type_name(&v), "RecurrenceRule", "".into())),
+        }
+    }
+}
+
+fn handlers_table(ctx: LuaContext) -> LuaResult<LuaTable> {
+    match ctx.named_registry_value::<_, Option<LuaTable>>(HANDLERS_KEY)? {
+        Some(t) => Ok(t),
+        None => {
+            let t = ctx.create_table()?;
+            ctx.set_named_registry_value(HANDLERS_KEY, t.clone())?;
+            Ok(t)
+        }
+    }
+}
+
+fn handler_key(ctx: LuaContext, id: Option<i32>) -> LuaResult<LuaValue> {
+    match id {
+        Some(id) => id.to_lua(ctx),
+        None => "all".to_lua(ctx),
+    }
+}
+
+/// Registers `f` to be called on the lifecycle transition `name` of the event `id`, or of all
+/// events if `id` is `None`. Handlers only live as long as the Lua state, so scripts are expected
+/// to register them again on every run. An event already exists by the time it can be given
+/// handlers, so `created` can only be handled for all events.
+pub fn set_handler<'lua>(
+    ctx: LuaContext<'lua>,
+    method: &str,
+    id: Option<i32>,
+    name: String,
+    f: LuaFunction<'lua>,
+) -> LuaResult<()> {
+    let kind = match Lifecycle::from_name(&name) {
+        None => Some(ErrorKind::InvalidLifecycle(name.clone())),
+        Some(Lifecycle::Created) if id.is_some() => Some(ErrorKind::InvalidField {
+            field: "name".into(),
+            message: "events are already created when they get handlers, use the global \
+                      `on(\"created\", f)` instead"
+                .into(),
+        }),
+        Some(_) => None,
+    };
+    if let Some(kind) = kind {
+        return Err(Error {
+            method: method.into(),
+            kind,
+        }
+        .into());
+    }
+    let handlers = handlers_table(ctx)?;
+    let key = handler_key(ctx, id)?;
+    let for_event = match handlers.get::<_, Option<LuaTable>>(key.clone())? {
+        Some(t) => t,
+        None => {
+            let t = ctx.create_table()?;
+            handlers.set(key, t.clone())?;
+            t
+        }
+    };
+    for_event.set(name, f)
+}
+
+pub fn get_handler<'lua>(
+    ctx: LuaContext<'lua>,
+    id: Option<i32>,
+    lifecycle: Lifecycle,
+) -> LuaResult<Option<LuaFunction<'lua>>> {
+    let key = handler_key(ctx, id)?;
+    match handlers_table(ctx)?.get::<_, Option<LuaTable>>(key)? {
+        Some(t) => t.get(lifecycle.name()),
+        None => Ok(None),
+    }
+}
+
+/// Runs the handlers of all pending transitions, first the ones of the event itself and then the
+/// ones for all events. Handlers are called with the event and the name of the transition. All
+/// pending transitions are handled even if some handler fails; the first failure is returned.
+pub fn dispatch(ctx: LuaContext) -> LuaResult<()> {
+    run_handlers(ctx, api::take_pending())
+}
+
+/// Runs the handlers of `transitions` like `dispatch` does
+pub fn run_handlers(ctx: LuaContext, transitions: Vec<(i32, Lifecycle)>) -> LuaResult<()> {
+    let mut result = Ok(());
+    for (id, lifecycle) in transitions {
+        for handler in [Some(id), None].iter() {
+            let f = match get_handler(ctx, *handler, lifecycle)? {
+                Some(f) => f,
+                None => continue,
+            };
+            if let Err(cause) = f.call::<_, ()>((EventHandle(id), lifecycle.name())) {
+                if result.is_ok() {
+                    result = Err(Error {
+                        method: "dispatch".into(),
+                        kind: ErrorKind::HandlerFailed {
+                            id,
+                            lifecycle: lifecycle.name().into(),
+                            cause,
+                        },
+                    }
+                    .into());
+                }
+            }
+        }
+    }
+    result
+}
+
+/// `tick()`: queues up the timed transitions that happened since the last tick and runs their
+/// handlers
+pub fn tick(ctx: LuaContext) -> LuaResult<()> {
+    api::check_transitions(Local::now());
+    dispatch(ctx)
+}
+
+fn seconds(d: Option<Duration>) -> Option<i64> {
+    d.map(|d| d.num_seconds())
+}
+
+/// What scripts get for a stored event, task or project. It only holds the id, so every access
+/// sees the current state in storage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EventHandle(pub i32);
+
+impl EventHandle {
+    fn get(&self, method: &str) -> LuaResult<EventType> {
+        api::get_event(self.0).ok_or_else(|| {
+            Error {
+                method: method.into(),
+                kind: ErrorKind::InvalidEventId(self.0),
+            }
+            .into()
+        })
+    }
+
+    fn get_event(&self, method: &str) -> LuaResult<Event> {
+        match self.get(method)? {
+            EventType::Event(e) => Ok(e),
+            e => Err(self.wrong_kind(method, "event", &e)),
+        }
+    }
+
+    fn get_recurring(&self, method: &str) -> LuaResult<Event> {
+        let event = self.get_event(method)?;
+        if event.recurrence().is_none() {
+            return Err(Error {
+                method: method.into(),
+                kind: ErrorKind::NotRecurring(self.0),
+            }
+            .into());
+        }
+        Ok(event)
+    }
+
+    fn wrong_kind(&self, method: &str, expected: &str, found: &EventType) -> LuaError {
+        Error {
+            method: method.into(),
+            kind: ErrorKind::WrongEventKind {
+                id: self.0,
+                expected: expected.into(),
+                found: found.kind().into(),
+            },
+        }
+        .into()
+    }
+}
+
+impl LuaUserData for EventHandle {
+    fn add_methods<'lua, M: LuaUserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_method("finish", |ctx, this, ()| {
+            this.get("finish")?;
+            api::finish_event(this.0);
+            dispatch(ctx)
+        });
+        methods.add_method("delete", |_, this, ()| {
+            api::delete_event(this.0).map_err(|e| e.into())
+        });
+        methods.add_method("set_prop", |_, this, (key, val): (String, String)| {
+            this.get("set_prop")?;
+            api::set_event_prop(this.0, key, val);
+            Ok(())
+        });
+        methods.add_method("on", |ctx, this, (name, f): (String, LuaFunction)| {
+            this.get("on")?;
+            set_handler(ctx, "on", Some(this.0), name, f)
+        });
+        methods.add_method("occurrences", |_, this, window: Interval| {
+            Ok(this.get_event("occurrences")?.occurrences(&window))
+        });
+        methods.add_method(
+            "set_recurrence",
+            |ctx, this, rule: Option<RecurrenceRule>| {
+                this.get_event("set_recurrence")?;
+                let conflicts = api::set_recurrence(this.0, rule.map(Recurrence::new).as_ref())
+                    .map_err(|e| -> LuaError { e.into() })?;
+                conflicts_to_lua(ctx, conflicts)
+            },
+        );
+        methods.add_method("skip", |_, this, original: LuaValue| {
+            this.get_recurring("skip")?;
+            api::skip_occurrence(this.0, datetime_from_lua(original)?).map_err(|e| e.into())
+        });
+        methods.add_method(
+            "reschedule",
+            |ctx, this, (original, interval): (LuaValue, Interval)| {
+                this.get_recurring("reschedule")?;
+                let conflicts =
+                    api::reschedule_occurrence(this.0, datetime_from_lua(original)?, &interval)
+                        .map_err(|e| -> LuaError { e.into() })?;
+                conflicts_to_lua(ctx, conflicts)
+            },
+        );
+        methods.add_method("set_due", |_, this, due: LuaValue| {
+            let event = this.get("set_due")?;
+            if let EventType::Event(_) = event {
+                return Err(this.wrong_kind("set_due", "task or project", &event));
+            }
+            let due = match due {
+                LuaValue::Nil => None,
+                v => Some(datetime_from_lua(v)?),
+            };
+            api::set_deadline(this.0, due);
+            Ok(())
+        });
+        methods.add_method(
+            "depends_on",
+            |_, this, (other, lag): (EventHandle, Option<LuaValue>)| {
+                let lag = match lag {
+                    None | Some(LuaValue::Nil) => Duration::zero(),
+                    Some(v) => duration_from_lua(v)?,
+                };
+                api::add_dependency(this.0, other.0, lag).map_err(|e| e.into())
+            },
+        );
+        methods.add_method("remove_dependency", |_, this, other: EventHandle| {
+            api::remove_dependency(this.0, other.0).map_err(|e| e.into())
+        });
+        methods.add_method("add_session", |_, this, session: Interval| {
+            match this.get("add_session")? {
+                EventType::Task(_) => {
+                    api::add_session(this.0, &session);
+                    Ok(())
+                }
+                e => Err(this.wrong_kind("add_session", "task", &e)),
+            }
+        });
+        methods.add_method("set_estimate", |_, this, estimate: LuaValue| {
+            match this.get("set_estimate")? {
+                EventType::Task(_) => {
+                    let estimate = match estimate {
+                        LuaValue::Nil => None,
+                        v => Some(duration_from_lua(v)?),
+                    };
+                    api::set_estimate(this.0, estimate).map_err(|e| e.into())
+                }
+                e => Err(this.wrong_kind("set_estimate", "task", &e)),
+            }
+        });
+        methods.add_method("add_subtask", |ctx, this, spec: LuaTable| {
+            match this.get("add_subtask")? {
+                EventType::Project(_) => {
+                    let mut task = task_from_table("add_subtask", &spec)?;
+                    let handle = EventHandle(api::add_subtask(this.0, &mut task));
+                    dispatch(ctx)?;
+                    Ok(handle)
+                }
+                e => Err(this.wrong_kind("add_subtask", "project", &e)),
+            }
+        });
+        methods.add_method("add_subproject", |ctx, this, spec: LuaTable| {
+            match this.get("add_subproject")? {
+                EventType::Project(_) => {
+                    let mut project = project_from_table("add_subproject", &spec)?;
+                    let handle = EventHandle(api::add_subproject(this.0, &mut project));
+                    dispatch(ctx)?;
+                    Ok(handle)
+                }
+                e => Err(this.wrong_kind("add_subproject", "project", &e)),
+            }
+        });
+        methods.add_method("move_to", |ctx, this, parent: Option<EventHandle>| {
+            api::move_item(this.0, parent.map(|p| p.0)).map_err(|e| -> LuaError { e.into() })?;
+            dispatch(ctx)
+        });
+        methods.add_meta_method(LuaMetaMethod::Index, |ctx, this, key: String| {
+            let event = this.get("__index")?;
+            let common = event.common();
+            match (key.as_str(), &event) {
+                ("id", _) => this.0.to_lua(ctx),
+                ("kind", _) => event.kind().to_lua(ctx),
+                ("name", _) => common.name().to_lua(ctx),
+                ("priority", _) => common.priority().to_lua(ctx),
+                ("priority_level", _) => common.priority().level().to_lua(ctx),
+                ("finished", _) => common.finished().to_lua(ctx),
+                ("props", _) => common.props().clone().to_lua(ctx),
+                ("interval", EventType::Event(e)) => e.interval().to_lua(ctx),
+                ("recurrence", EventType::Event(e)) => {
+                    e.recurrence().map(|r| r.rule.to_string()).to_lua(ctx)
+                }
+                ("exceptions", EventType::Event(e)) => match e.recurrence() {
+                    Some(r) => r
+                        .exceptions
+                        .iter()
+                        .map(|t| datetime_to_lua(t, ctx))
+                        .collect::<LuaResult<Vec<_>>>()?
+                        .to_lua(ctx),
+                    None => Ok(LuaValue::Nil),
+                },
+                ("sessions", EventType::Task(t)) => t.sessions().to_vec().to_lua(ctx),
+                ("due", EventType::Task(t)) => match t.deadline() {
+                    Some(d) => datetime_to_lua(&d, ctx),
+                    None => Ok(LuaValue::Nil),
+                },
+                ("due", EventType::Project(p)) => match p.deadline() {
+                    Some(d) => datetime_to_lua(&d, ctx),
+                    None => Ok(LuaValue::Nil),
+                },
+                ("overdue", EventType::Task(t)) => t.is_overdue(Local::now()).to_lua(ctx),
+                ("overdue", EventType::Project(p)) => p.is_overdue(Local::now()).to_lua(ctx),
+                ("slack", _) => seconds(api::slack(&event, Local::now())).to_lua(ctx),
+                ("dependencies", EventType::Task(_)) => api::dependencies_of(this.0)
+                    .into_iter()
+                    .map(|d| EventHandle(d.depends_on))
+                    .collect::<Vec<_>>()
+                    .to_lua(ctx),
+                ("dependents", EventType::Task(_)) => api::dependents_of(this.0)
+                    .into_iter()
+                    .map(|d| EventHandle(d.task))
+                    .collect::<Vec<_>>()
+                    .to_lua(ctx),
+                ("planned", EventType::Task(_)) => schedule::get_plan_for(this.0)
+                    .into_iter()
+                    .map(|p| p.interval)
+                    .collect::<Vec<_>>()
+                    .to_lua(ctx),
+                ("estimate", EventType::Task(t)) => seconds(t.estimate()).to_lua(ctx),
+                ("estimate", EventType::Project(p)) => seconds(p.estimate()).to_lua(ctx),
+                ("actual", EventType::Task(t)) => t.actual().num_seconds().to_lua(ctx),
+                ("actual", EventType::Project(p)) => p.actual().num_seconds().to_lua(ctx),
+                ("remaining", EventType::Task(t)) => seconds(t.remaining()).to_lua(ctx),
+                ("remaining", EventType::Project(p)) => seconds(p.remaining()).to_lua(ctx),
+                ("progress", EventType::Task(t)) => t.progress().to_lua(ctx),
+                ("progress", EventType::Project(p)) => p.progress().to_lua(ctx),
+                ("subtasks", EventType::Project(p)) => p
+                    .subtasks()
+                    .iter()
+                    .filter_map(|t| t.common().id().map(EventHandle))
+                    .collect::<Vec<_>>()
+                    .to_lua(ctx),
+                ("subprojects", EventType::Project(p)) => p
+                    .subprojects()
+                    .iter()
+                    .filter_map(|p| p.common().id().map(EventHandle))
+                    .collect::<Vec<_>>()
+                    .to_lua(ctx),
+                ("complete", EventType::Project(p)) => p.is_complete().to_lua(ctx),
+                ("parent", _) => api::parent_of(this.0).map(EventHandle).to_lua(ctx),
+                ("children", _) => to_handles(api::children(this.0)).to_lua(ctx),
+                ("ancestors", _) => to_handles(api::ancestors(this.0)).to_lua(ctx),
+                ("subtree", _) => to_handles(api::subtree(this.0)).to_lua(ctx),
+                _ => Ok(LuaValue::Nil),
+            }
+        });
+        methods.add_meta_method(LuaMetaMethod::ToString, |_, this, ()| {
+            let event = this.get("__tostring")?;
+            Ok(format!(
+                "{} {}: {}",
+                event.kind(),
+                this.0,
+                event.common().name()
+            ))
+        });
+        methods.add_meta_method(LuaMetaMethod::Eq, |_, this, other: EventHandle| {
+            Ok(*this == other)
+        });
+    }
+}
+
+/// Reads the fields shared by events, tasks and projects: `name` (required), `priority`
+/// (defaults to `"medium"`), `props` and `finished`.
+fn common_from_table(method: &str, typ: &str, table: &LuaTable) -> LuaResult<EventCommon> {
+    let name: String = table
+        .get::<_, Option<String>>("name")?
+        .ok_or_else(|| -> LuaError {
+            Error {
+                method: method.into(),
+                kind: ErrorKind::MissingField {
+                    typ: typ.into(),
+                    field: "name".into(),
+                },
+            }
+            .into()
+        })?;
+    let priority: Option<Priority> = table.get("priority")?;
+    let mut common = EventCommon::new(name, priority.unwrap_or_default());
+    let props: Option<HashMap<String, String>> = table.get("props")?;
+    for (key, val) in props.unwrap_or_default() {
+        common.set_prop(key, val);
+    }
+    if table.get::<_, Option<bool>>("finished")?.unwrap_or(false) {
+        common.finish();
+    }
+    Ok(common)
+}
+
+fn event_from_table(method: &str, table: &LuaTable) -> LuaResult<Event> {
+    let common = common_from_table(method, "event", table)?;
+    let interval = interval_builder_from_table(table)?
+        .build()
+        .map_err(|e| -> LuaError {
+            Error {
+                method: method.into(),
+                kind: e.into(),
+            }
+            .into()
+        })?;
+    let mut event = Event::new(common, interval);
+    if let Some(rule) = table.get::<_, Option<RecurrenceRule>>("recur")? {
+        let mut recurrence = Recurrence::new(rule);
+        let exceptions: Option<Vec<LuaValue>> = table.get("exceptions")?;
+        for original in exceptions.unwrap_or_default() {
+            let original = datetime_from_lua(original)?;
+            if !recurrence.is_occurrence(&interval, original) {
+                return Err(Error {
+                    method: method.into(),
+                    kind: ErrorKind::NotAnOccurrence(original),
+                }
+                .into());
+            }
+            recurrence.skip(original);
+        }
+        event.set_recurrence(Some(recurrence));
+    }
+    Ok(event)
+}
+
+fn task_from_table(method: &str, table: &LuaTable) -> LuaResult<Task> {
+    let mut task = Task::new(common_from_table(method, "task", table)?);
+    match table.get::<_, LuaValue>("estimate")? {
+        LuaValue::Nil => {}
+        v => {
+            let estimate = Some(duration_from_lua(v)?);
+            api::check_estimate(method, estimate).map_err(|e| -> LuaError { e.into() })?;
+            task.set_estimate(estimate);
+        }
+    }
+    match table.get::<_, LuaValue>("due")? {
+        LuaValue::Nil => {}
+        v => task.set_deadline(Some(datetime_from_lua(v)?)),
+    }
+    let sessions: Option<Vec<Interval>> = table.get("sessions")?;
+    for session in sessions.unwrap_or_default() {
+        task.add_session(session);
+    }
+    Ok(task)
+}
+
+fn project_from_table(method: &str, table: &LuaTable) -> LuaResult<Project> {
+    let mut project = Project::new(common_from_table(method, "project", table)?);
+    match table.get::<_, LuaValue>("due")? {
+        LuaValue::Nil => {}
+        v => project.set_deadline(Some(datetime_from_lua(v)?)),
+    }
+    let subtasks: Option<Vec<LuaTable>> = table.get("subtasks")?;
+    for spec in subtasks.unwrap_or_default() {
+        project.add_subtask(task_from_table(method, &spec)?);
+    }
+    let subprojects: Option<Vec<LuaTable>> = table.get("subprojects")?;
+    for spec in subprojects.unwrap_or_default() {
+        project.add_subproject(project_from_table(method, &spec)?);
+    }
+    Ok(project)
+}
+
+/// `add_event{name=, priority=, start=, length=, end=, props=, recur=, exceptions=}`, where
+/// `recur` is a recurrence rule and `exceptions` the start times of occurrences to skip. Under the
+/// `warn` conflict policy, the conflicts with stored events follow the new event, if there are
+/// any.
+pub fn add_event<'lua>(ctx: LuaContext<'lua>, table: LuaTable) -> LuaResult<LuaMultiValue<'lua>> {
+    let mut event = event_from_table("add_event", &table)?;
+    let (id, conflicts) = api::add_event(&mut event).map_err(|e| -> LuaError { e.into() })?;
+    dispatch(ctx)?;
+    let mut values = conflicts_to_lua(ctx, conflicts)?.into_vec();
+    values.insert(0, EventHandle(id).to_lua(ctx)?);
+    Ok(LuaMultiValue::from_vec(values))
+}
+
+/// `add_task{name=, priority=, props=, sessions=}`
+pub fn add_task(ctx: LuaContext, table: LuaTable) -> LuaResult<EventHandle> {
+    let mut task = task_from_table("add_task", &table)?;
+    let handle = EventHandle(api::add_task(&mut task));
+    dispatch(ctx)?;
+    Ok(handle)
+}
+
+/// `add_project{name=, priority=, props=, subtasks={...}, subprojects={...}}`, where each subtask
+/// is a table as taken by `add_task` and each subproject one as taken by `add_project`
+pub fn add_project(ctx: LuaContext, table: LuaTable) -> LuaResult<EventHandle> {
+    let mut project = project_from_table("add_project", &table)?;
+    let handle = EventHandle(api::add_project(&mut project));
+    dispatch(ctx)?;
+    Ok(handle)
+}
+
+pub fn get_event(id: i32) -> Option<EventHandle> {
+    api::get_event(id).map(|_| EventHandle(id))
+}
+
+fn to_handles(ids: Vec<i32>) -> Vec<EventHandle> {
+    ids.into_iter().map(EventHandle).collect()
+}
+
+fn handles<'a, I: Iterator<Item = &'a EventCommon>>(commons: I) -> Vec<EventHandle> {
+    commons.filter_map(|c| c.id().map(EventHandle)).collect()
+}
+
+/// Applies the options taken by `get_events`, `get_tasks` and `get_projects`: `min_priority` and
+/// `max_priority` (both inclusive) filter, and `sort_by_priority` puts the most important first
+fn select<T: AsRef<EventCommon>>(
+    mut items: Vec<T>,
+    opts: Option<LuaTable>,
+) -> LuaResult<Vec<EventHandle>> {
+    if let Some(opts) = opts {
+        let min: Option<Priority> = opts.get("min_priority")?;
+        let max: Option<Priority> = opts.get("max_priority")?;
+        items = api::filter_by_priority(
+            items,
+            (
+                min.map_or(Bound::Unbounded, Bound::Included),
+                max.map_or(Bound::Unbounded, Bound::Included),
+            ),
+        );
+        if opts
+            .get::<_, Option<bool>>("sort_by_priority")?
+            .unwrap_or(false)
+        {
+            api::sort_by_priority(&mut items);
+        }
+    }
+    Ok(handles(items.iter().map(AsRef::as_ref)))
+}
+
+pub fn get_events(opts: Option<LuaTable>) -> LuaResult<Vec<EventHandle>> {
+    select(api::get_events(), opts)
+}
+
+pub fn get_tasks(opts: Option<LuaTable>) -> LuaResult<Vec<EventHandle>> {
+    select(api::get_tasks(), opts)
+}
+
+pub fn get_projects(opts: Option<LuaTable>) -> LuaResult<Vec<EventHandle>> {
+    select(api::get_projects(), opts)
+}
+
+impl<'lua> ToLua<'lua> for Conflict {
+    fn to_lua(self, ctx: LuaContext<'lua>) -> LuaResult<LuaValue<'lua>> {
+        let table = ctx.create_table()?;
+        table.set("a", self.a.0.map(EventHandle))?;
+        table.set("a_interval", self.a.1)?;
+        table.set("b", self.b.0.map(EventHandle))?;
+        table.set("b_interval", self.b.1)?;
+        table.set("overlap", self.overlap())?;
+        table.set("message", self.to_string())?;
+        Ok(LuaValue::Table(table))
+    }
+}
+
+/// The conflicts an event change ran into as the list of them, or nothing if there are none
+fn conflicts_to_lua(ctx: LuaContext, conflicts: Vec<Conflict>) -> LuaResult<LuaMultiValue> {
+    if conflicts.is_empty() {
+        Ok(LuaMultiValue::new())
+    } else {
+        conflicts.to_lua_multi(ctx)
+    }
+}
+
+/// `conflicts(range)`: the overlapping pairs of occurrences in the interval `range`, as tables
+/// `{a=, a_interval=, b=, b_interval=, overlap=, message=}`
+pub fn conflicts(range: Interval) -> Vec<Conflict> {
+    api::conflicts(&range)
+}
+
+/// `set_conflict_policy(policy)`, where `policy` is one of `"allow"`, `"warn"` or `"reject"`
+pub fn set_conflict_policy(policy: String) -> LuaResult<()> {
+    let policy = policy
+        .parse::<ConflictPolicy>()
+        .map_err(|kind| -> LuaError {
+            Error {
+                method: "set_conflict_policy".into(),
+                kind,
+            }
+            .into()
+        })?;
+    api::set_conflict_policy(policy);
+    Ok(())
+}
+
+pub fn get_conflict_policy() -> &'static str {
+    api::get_conflict_policy().name()
+}
+
+fn effort_table<'lua>(
+    ctx: LuaContext<'lua>,
+    estimate: Option<Duration>,
+    actual: Duration,
+    remaining: Option<Duration>,
+    progress: f64,
+) -> LuaResult<LuaTable<'lua>> {
+    let table = ctx.create_table()?;
+    table.set("estimate", seconds(estimate))?;
+    table.set("actual", actual.num_seconds())?;
+    table.set("remaining", seconds(remaining))?;
+    table.set("progress", progress)?;
+    table.set("variance", seconds(estimate.map(|e| actual - e)))?;
+    Ok(table)
+}
+
+fn project_report<'lua>(ctx: LuaContext<'lua>, project: &Project) -> LuaResult<LuaTable<'lua>> {
+    let report = effort_table(
+        ctx,
+        project.estimate(),
+        project.actual(),
+        project.remaining(),
+        project.progress(),
+    )?;
+    report.set("project", project.common().id().map(EventHandle))?;
+    report.set("name", project.common().name())?;
+    report.set("finished", project.common().finished())?;
+    let mut tasks = Vec::new();
+    for task in project.subtasks() {
+        let row = effort_table(
+            ctx,
+            task.estimate(),
+            task.actual(),
+            task.remaining(),
+            task.progress(),
+        )?;
+        row.set("task", task.common().id().map(EventHandle))?;
+        row.set("name", task.common().name())?;
+        row.set("finished", task.common().finished())?;
+        tasks.push(row);
+    }
+    report.set("tasks", tasks)?;
+    let projects = project
+        .subprojects()
+        .iter()
+        .map(|p| project_report(ctx, p))
+        .collect::<LuaResult<Vec<_>>>()?;
+    report.set("projects", projects)?;
+    Ok(report)
+}
+
+/// `progress(project)`: how the project and everything in it fare against their estimates, as
+/// `{project=, name=, finished=, estimate=, actual=, remaining=, progress=, variance=, tasks=,
+/// projects=}`, where `tasks` has a row like this for each subtask and `projects` a report like
+/// this for each subproject. Durations are in seconds, `progress` goes from 0 to 1 and
+/// `variance` is how much longer than estimated the work took so far.
+pub fn progress(ctx: LuaContext, project: EventHandle) -> LuaResult<LuaTable> {
+    match project.get("progress")? {
+        EventType::Project(p) => project_report(ctx, &p),
+        e => Err(project.wrong_kind("progress", "project", &e)),
+    }
+}
+
+/// `critical_path(project)`: `{tasks=, length=}`, the chain of dependent tasks in the project, at
+/// any depth, that takes the longest to get through given their remaining work and the lags
+/// between them, and how long that is in seconds
+pub fn critical_path(ctx: LuaContext, project: EventHandle) -> LuaResult<LuaTable> {
+    let path = match project.get("critical_path")? {
+        EventType::Project(p) => api::critical_path(&p),
+        e => return Err(project.wrong_kind("critical_path", "project", &e)),
+    };
+    let table = ctx.create_table()?;
+    table.set(
+        "tasks",
+        path.tasks.into_iter().map(EventHandle).collect::<Vec<_>>(),
+    )?;
+    table.set("length", path.length.num_seconds())?;
+    Ok(table)
+}
+
+/// `topological_order(tasks)`: `tasks` sorted so that each comes after the ones it depends on
+pub fn topological_order(tasks: Vec<EventHandle>) -> LuaResult<Vec<EventHandle>> {
+    let ids: Vec<i32> = tasks.iter().map(|t| t.0).collect();
+    api::topological_order(&ids, &api::get_dependencies())
+        .map(|ids| ids.into_iter().map(EventHandle).collect())
+        .map_err(|cycle| {
+            Error {
+                method: "topological_order".into(),
+                kind: ErrorKind::DependencyCycle(cycle),
+            }
+            .into()
+        })
+}
+
+/// `overdue()`: the unfinished tasks and projects past their deadline, the longest overdue first
+pub fn overdue() -> Vec<EventHandle> {
+    handles(api::get_overdue(Local::now()).iter().map(EventType::common))
+}
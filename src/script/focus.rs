@@ -0,0 +1,188 @@
+//! Lua side of focus sessions, and the interactive focus mode
+
+use std::io::{self, BufRead};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+
+use chrono::{Duration, Local};
+use rlua::prelude::*;
+
+use super::event::{datetime_to_lua, duration_from_lua, EventHandle};
+use crate::api::{self, error::*, FocusConfig, FocusDay, FocusPhase, FocusSession, Interval};
+
+/// How often the focus mode checks for the end of a phase
+const TICK_SECS: u64 = 1;
+
+impl<'lua> ToLua<'lua> for FocusSession {
+    fn to_lua(self, ctx: LuaContext<'lua>) -> LuaResult<LuaValue<'lua>> {
+        let table = ctx.create_table()?;
+        table.set("task", EventHandle(self.task))?;
+        table.set("phase", self.phase.name())?;
+        table.set("phase_start", datetime_to_lua(&self.phase_start, ctx)?)?;
+        table.set("phase_end", datetime_to_lua(&self.phase_end(), ctx)?)?;
+        table.set("remaining", self.remaining(Local::now()).num_seconds())?;
+        table.set("blocks", self.blocks)?;
+        table.set("interruptions", self.interruptions)?;
+        Ok(LuaValue::Table(table))
+    }
+}
+
+impl<'lua> ToLua<'lua> for FocusDay {
+    fn to_lua(self, ctx: LuaContext<'lua>) -> LuaResult<LuaValue<'lua>> {
+        let table = ctx.create_table()?;
+        table.set("date", self.date.format("%Y-%m-%d").to_string())?;
+        table.set("blocks", self.blocks)?;
+        table.set("focused", self.focused.num_seconds())?;
+        table.set("interruptions", self.interruptions)?;
+        let tasks = ctx.create_table()?;
+        for (task, spent) in self.tasks {
+            tasks.set(task, spent.num_seconds())?;
+        }
+        table.set("tasks", tasks)?;
+        Ok(LuaValue::Table(table))
+    }
+}
+
+/// Reads `work`, `short_break`, `long_break`, `long_break_every` and `max_idle_blocks` from
+/// `table`, with the defaults (25 minutes of work, 5 minute breaks, a 15 minute break every 4
+/// blocks, and ending after 4 blocks nobody saw) for the ones left out. `max_idle_blocks` of 0
+/// lets the session run until stopped.
+fn config_from_table(table: Option<LuaTable>) -> LuaResult<FocusConfig> {
+    let mut config = FocusConfig::default();
+    let table = match table {
+        Some(table) => table,
+        None => return Ok(config),
+    };
+    let duration = |key: &str, default: Duration| -> LuaResult<Duration> {
+        match table.get::<_, LuaValue>(key)? {
+            LuaValue::Nil => Ok(default),
+            v => duration_from_lua(v),
+        }
+    };
+    config.work = duration("work", config.work)?;
+    config.short_break = duration("short_break", config.short_break)?;
+    config.long_break = duration("long_break", config.long_break)?;
+    if let Some(every) = table.get::<_, Option<u32>>("long_break_every")? {
+        if every == 0 || every > i32::MAX as u32 {
+            return Err(Error {
+                method: "start_focus".into(),
+                kind: ErrorKind::InvalidField {
+                    field: "long_break_every".into(),
+                    message: format!("{} is not between 1 and {}", every, i32::MAX),
+                },
+            }
+            .into());
+        }
+        config.long_break_every = every;
+    }
+    if let Some(max) = table.get::<_, Option<u32>>("max_idle_blocks")? {
+        config.max_idle_blocks = Some(max).filter(|&max| max > 0);
+    }
+    Ok(config)
+}
+
+/// `start_focus(task, {work=, short_break=, long_break=, long_break_every=, max_idle_blocks=})`
+pub fn start_focus(task: i32, table: Option<LuaTable>) -> LuaResult<FocusSession> {
+    api::start_focus(task, config_from_table(table)?, Local::now()).map_err(|e| e.into())
+}
+
+/// `focus_status()`: the running session, brought up to date, or `nil` (also when it ended since
+/// nobody checked on it for too long)
+pub fn focus_status() -> Option<FocusSession> {
+    api::advance_focus(Local::now());
+    api::get_focus()
+}
+
+/// `interrupt_focus(reason)`
+pub fn interrupt_focus(reason: Option<String>) -> LuaResult<()> {
+    api::interrupt_focus(reason.unwrap_or_default(), Local::now()).map_err(|e| e.into())
+}
+
+/// `stop_focus()`, returning the work block it cut short, if any
+pub fn stop_focus() -> LuaResult<Option<Interval>> {
+    api::stop_focus(Local::now()).map_err(|e| e.into())
+}
+
+/// `focus_stats(range)`: the focus of each day in `range` (default the last week) that had any
+pub fn focus_stats(range: Option<Interval>) -> Vec<FocusDay> {
+    let range = range.unwrap_or_else(|| {
+        let now = Local::now();
+        Interval::builder()
+            .start(now - Duration::weeks(1))
+            .end(now)
+            .build()
+            .unwrap()
+    });
+    api::focus_stats(&range)
+}
+
+fn print_phase(session: &FocusSession) {
+    let what = match session.phase {
+        FocusPhase::Work => format!("Work block {}", session.blocks + 1),
+        phase => {
+            let mut name = phase.name().to_string();
+            name[..1].make_ascii_uppercase();
+            name
+        }
+    };
+    println!(
+        "[{}] {} until {}",
+        session.phase_start.format("%H:%M"),
+        what,
+        session.phase_end().format("%H:%M")
+    );
+}
+
+/// `focus(task, opts)`: runs a focus session on `task` in the terminal, printing each phase as it
+/// starts, until `q` is entered. Any other line entered is logged as an interruption, with the
+/// line as the reason.
+pub fn focus(task: i32, table: Option<LuaTable>) -> LuaResult<()> {
+    let session = start_focus(task, table)?;
+    println!("Enter a reason to log an interruption, or q to stop");
+    print_phase(&session);
+
+    let (lines, input) = mpsc::channel();
+    thread::spawn(move || {
+        for line in io::stdin().lock().lines() {
+            let line = match line {
+                Ok(line) => line,
+                Err(_) => break,
+            };
+            let quit = line.trim() == "q";
+            if lines.send(line).is_err() || quit {
+                break;
+            }
+        }
+    });
+
+    loop {
+        match input.recv_timeout(std::time::Duration::from_secs(TICK_SECS)) {
+            Ok(line) if line.trim() == "q" => break,
+            Ok(line) => {
+                interrupt_focus(Some(line.trim().to_string()))?;
+                println!("Interruption logged");
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+        let advanced = !api::advance_focus(Local::now()).is_empty();
+        match api::get_focus() {
+            Some(session) if advanced => print_phase(&session),
+            Some(_) => {}
+            None => {
+                println!("The session ended, having been left running");
+                return Ok(());
+            }
+        }
+    }
+
+    let session = api::get_focus();
+    stop_focus()?;
+    if let Some(session) = session {
+        println!(
+            "Stopped after {} work blocks and {} interruptions",
+            session.blocks, session.interruptions
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,63 @@
+//! Lua side of habits
+
+use rlua::prelude::*;
+
+use crate::api::{self, error::*, Habit, HabitPeriod, HabitStatus};
+
+impl<'lua> ToLua<'lua> for HabitStatus {
+    fn to_lua(self, ctx: LuaContext<'lua>) -> LuaResult<LuaValue<'lua>> {
+        let table = ctx.create_table()?;
+        table.set("name", self.habit.name)?;
+        table.set("log_type", self.habit.log_type)?;
+        table.set("times", self.habit.times)?;
+        table.set("per", self.habit.per.name())?;
+        table.set("done", self.done)?;
+        table.set("current_streak", self.current_streak)?;
+        table.set("best_streak", self.best_streak)?;
+        let missed: Vec<String> = self
+            .missed
+            .iter()
+            .map(|d| d.format("%Y-%m-%d").to_string())
+            .collect();
+        table.set("missed", missed)?;
+        Ok(LuaValue::Table(table))
+    }
+}
+
+/// `add_habit{name=, log_type=, times=, per=}`: a habit of adding logs of type `log_type`
+/// (default `name`) `times` times (default 1) per `"day"` (the default) or `"week"`
+pub fn add_habit(table: LuaTable) -> LuaResult<()> {
+    let error = |kind| -> LuaError {
+        Error {
+            method: "add_habit".into(),
+            kind,
+        }
+        .into()
+    };
+    let name = table.get::<_, Option<String>>("name")?.ok_or_else(|| {
+        error(ErrorKind::MissingField {
+            typ: "habit".into(),
+            field: "name".into(),
+        })
+    })?;
+    let per = match table.get::<_, Option<String>>("per")? {
+        Some(s) => s.parse::<HabitPeriod>().map_err(error)?,
+        None => HabitPeriod::Day,
+    };
+    let habit = Habit {
+        log_type: table
+            .get::<_, Option<String>>("log_type")?
+            .unwrap_or_else(|| name.clone()),
+        name,
+        times: table.get::<_, Option<u32>>("times")?.unwrap_or(1),
+        per,
+    };
+    api::add_habit(habit).map_err(|e| e.into())
+}
+
+/// `habits()`: how every habit is going today, as a list of `{name=, log_type=, times=, per=,
+/// done=, current_streak=, best_streak=, missed=}`, with `missed` the first days of the periods
+/// that weren't kept
+pub fn habits() -> Vec<HabitStatus> {
+    api::habits_today()
+}
@@ -0,0 +1,38 @@
+//! Lua side of iCalendar import and export
+
+use std::fs;
+
+use rlua::prelude::*;
+
+use super::event::EventHandle;
+use crate::api::ical::{self, ImportSummary};
+
+impl<'lua> ToLua<'lua> for ImportSummary {
+    fn to_lua(self, ctx: LuaContext<'lua>) -> LuaResult<LuaValue<'lua>> {
+        let handles = |ids: Vec<i32>| ids.into_iter().map(EventHandle).collect::<Vec<_>>();
+        let table = ctx.create_table()?;
+        table.set("created", handles(self.created))?;
+        table.set("updated", handles(self.updated))?;
+        table.set("skipped", self.skipped)?;
+        Ok(LuaValue::Table(table))
+    }
+}
+
+/// `export_ical(path)`: writes all events and tasks to the file `path`, or returns them as a
+/// string without a path
+pub fn export_ical(path: Option<String>) -> LuaResult<Option<String>> {
+    let text = ical::export();
+    match path {
+        Some(path) => fs::write(path, text)
+            .map(|_| None)
+            .map_err(LuaError::external),
+        None => Ok(Some(text)),
+    }
+}
+
+/// `import_ical(path)`, returning `{created=, updated=, skipped=}` with handles to the created
+/// and updated items and why the others were skipped
+pub fn import_ical(path: String) -> LuaResult<ImportSummary> {
+    let text = fs::read_to_string(path).map_err(LuaError::external)?;
+    ical::import(&text).map_err(|e| e.into())
+}
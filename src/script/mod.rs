@@ -1,4 +1,17 @@
+pub mod agenda;
+pub mod availability;
+pub mod caldav;
+pub mod chart;
 pub mod context;
+pub mod daemon;
+pub mod event;
+pub mod focus;
+pub mod habit;
+pub mod ical;
 pub mod lua;
+pub mod report;
+pub mod schedule;
+pub mod timer;
+pub mod tui;
 
 pub use context::*;
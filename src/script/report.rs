@@ -0,0 +1,111 @@
+//! Lua side of time reports
+
+use chrono::{Duration, Local};
+use rlua::prelude::*;
+
+use super::chart::output_from_table;
+use super::event::EventHandle;
+use crate::api::{
+    chart,
+    error::*,
+    report::{self, Report, ReportGroup, ReportPeriod, ReportRow},
+    Interval,
+};
+
+impl<'lua> ToLua<'lua> for ReportRow {
+    fn to_lua(self, ctx: LuaContext<'lua>) -> LuaResult<LuaValue<'lua>> {
+        let table = ctx.create_table()?;
+        if let Some(period) = self.period {
+            table.set("period", period.format("%Y-%m-%d").to_string())?;
+        }
+        if let Some(id) = self.id {
+            table.set("id", EventHandle(id))?;
+        }
+        table.set("key", self.key.as_str())?;
+        table.set("total", self.total.num_seconds())?;
+        table.set("billable", self.billable.num_seconds())?;
+        table.set("non_billable", self.non_billable().num_seconds())?;
+        Ok(LuaValue::Table(table))
+    }
+}
+
+impl<'lua> ToLua<'lua> for Report {
+    fn to_lua(self, ctx: LuaContext<'lua>) -> LuaResult<LuaValue<'lua>> {
+        let table = ctx.create_table()?;
+        table.set("range", self.range)?;
+        table.set("by", self.group.name())?;
+        if let Some(period) = self.period {
+            table.set("per", period.name())?;
+        }
+        table.set("total", self.total.num_seconds())?;
+        table.set("billable", self.billable.num_seconds())?;
+        table.set("non_billable", self.non_billable().num_seconds())?;
+        table.set("rows", self.rows)?;
+        Ok(LuaValue::Table(table))
+    }
+}
+
+/// Reads `range` (default the last week), `by` (`"task"`, the default, `"project"`, `"tag"` or
+/// `"type"`) and `per` (`"day"`, `"week"` or `"month"`, or left out for a single period)
+fn report_from_table(method: &str, table: Option<&LuaTable>) -> LuaResult<Report> {
+    let error = |kind| -> LuaError {
+        Error {
+            method: method.into(),
+            kind,
+        }
+        .into()
+    };
+    let (range, group, period) = match table {
+        Some(table) => (
+            table.get::<_, Option<Interval>>("range")?,
+            table.get::<_, Option<String>>("by")?,
+            table.get::<_, Option<String>>("per")?,
+        ),
+        None => (None, None, None),
+    };
+    let range = range.unwrap_or_else(|| {
+        let now = Local::now();
+        Interval::builder()
+            .start(now - Duration::weeks(1))
+            .end(now)
+            .build()
+            .unwrap()
+    });
+    let group = match group {
+        Some(s) => s.parse::<ReportGroup>().map_err(error)?,
+        None => ReportGroup::Task,
+    };
+    let period = match period {
+        Some(s) => Some(s.parse::<ReportPeriod>().map_err(error)?),
+        None => None,
+    };
+    Ok(report::time_report(&range, group, period))
+}
+
+/// `time_report{range=, by=, per=}`: the time recorded on tasks, as `{range=, by=, per=, total=,
+/// billable=, non_billable=, rows=}` with each row `{period=, id=, key=, total=, billable=,
+/// non_billable=}` and the times in seconds. `id` is the task or project when grouping by them,
+/// and `key` its name.
+pub fn time_report(table: Option<LuaTable>) -> LuaResult<Report> {
+    report_from_table("time_report", table.as_ref())
+}
+
+/// `time_report_text{range=, by=, per=}`: the same report as a table of hours to print
+pub fn time_report_text(table: Option<LuaTable>) -> LuaResult<String> {
+    report_from_table("time_report_text", table.as_ref()).map(|r| report::render_text(&r))
+}
+
+/// `time_report_csv{range=, by=, per=}`: the same report as CSV
+pub fn time_report_csv(table: Option<LuaTable>) -> LuaResult<String> {
+    report_from_table("time_report_csv", table.as_ref()).map(|r| report::render_csv(&r))
+}
+
+/// `time_report_chart{range=, by=, per=, width=, plain=}`: the same report as bars of hours, or
+/// as a sparkline per key when split by period
+pub fn time_report_chart(table: Option<LuaTable>) -> LuaResult<String> {
+    let report = report_from_table("time_report_chart", table.as_ref())?;
+    Ok(chart::report_chart(
+        &report,
+        output_from_table(table.as_ref())?,
+    ))
+}
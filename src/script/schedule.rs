@@ -0,0 +1,109 @@
+//! Lua side of the scheduler
+
+use chrono::{Duration, Local};
+use rlua::prelude::*;
+
+use super::availability::availability_from_table;
+use super::event::{datetime_from_lua, duration_from_lua, EventHandle};
+use crate::api::{
+    self,
+    error::*,
+    schedule::{self, Placement, SchedulerConfig, Unplaced},
+    time, Interval,
+};
+
+impl<'lua> ToLua<'lua> for Placement {
+    fn to_lua(self, ctx: LuaContext<'lua>) -> LuaResult<LuaValue<'lua>> {
+        let table = ctx.create_table()?;
+        table.set("task", EventHandle(self.task))?;
+        table.set("interval", self.interval)?;
+        table.set("reason", self.reason)?;
+        Ok(LuaValue::Table(table))
+    }
+}
+
+impl<'lua> ToLua<'lua> for Unplaced {
+    fn to_lua(self, ctx: LuaContext<'lua>) -> LuaResult<LuaValue<'lua>> {
+        let table = ctx.create_table()?;
+        table.set("task", EventHandle(self.task))?;
+        table.set("remaining", time::format_duration(&self.remaining))?;
+        table.set("reason", self.reason)?;
+        Ok(LuaValue::Table(table))
+    }
+}
+
+/// Reads the scheduler options of `schedule{}`: `min_session`, `max_session`, the `calendar` to
+/// schedule in, and changes to its working hours as taken by `add_calendar`
+fn config_from_table(table: &LuaTable) -> LuaResult<SchedulerConfig> {
+    let mut config = SchedulerConfig::default();
+    match table.get::<_, LuaValue>("min_session")? {
+        LuaValue::Nil => {}
+        v => config.min_session = duration_from_lua(v)?,
+    }
+    match table.get::<_, LuaValue>("max_session")? {
+        LuaValue::Nil => {}
+        v => config.max_session = duration_from_lua(v)?,
+    }
+    let invalid = |field: &str, message: &str| -> LuaError {
+        Error {
+            method: "schedule".into(),
+            kind: ErrorKind::InvalidField {
+                field: field.into(),
+                message: message.into(),
+            },
+        }
+        .into()
+    };
+    if config.min_session <= Duration::zero() {
+        return Err(invalid("min_session", "must be longer than 0s"));
+    }
+    if config.max_session < config.min_session {
+        return Err(invalid("max_session", "must be at least `min_session`"));
+    }
+    let calendar: Option<String> = table.get("calendar")?;
+    let base = api::calendar_or_default("schedule", calendar.as_deref())
+        .map_err(|e| -> LuaError { e.into() })?;
+    config.availability = availability_from_table("schedule", table, base)?;
+    Ok(config)
+}
+
+/// `schedule{from=, to=, ...}`: plans the unfinished tasks between `from` (default now) and `to`
+/// (default a week later), and returns `{placements={...}, unplaced={...}}`. Each placement has
+/// the `task`, the proposed `interval` and the `reason` it was placed there.
+pub fn schedule<'lua>(
+    ctx: LuaContext<'lua>,
+    table: Option<LuaTable<'lua>>,
+) -> LuaResult<LuaTable<'lua>> {
+    let table = match table {
+        Some(t) => t,
+        None => ctx.create_table()?,
+    };
+    let from = match table.get::<_, LuaValue>("from")? {
+        LuaValue::Nil => Local::now(),
+        v => datetime_from_lua(v)?,
+    };
+    let mut window = Interval::builder();
+    window.start(from);
+    match table.get::<_, LuaValue>("to")? {
+        LuaValue::Nil => window.length(chrono::Duration::weeks(1)),
+        v => window.end(datetime_from_lua(v)?),
+    };
+    let window = window.build().map_err(|e| -> LuaError {
+        Error {
+            method: "schedule".into(),
+            kind: e.into(),
+        }
+        .into()
+    })?;
+
+    let result = schedule::run_scheduler(&window, &config_from_table(&table)?);
+    let ret = ctx.create_table()?;
+    ret.set("placements", result.placements)?;
+    ret.set("unplaced", result.unplaced)?;
+    Ok(ret)
+}
+
+/// `get_plan()`: the stored plan, in order
+pub fn get_plan() -> Vec<Placement> {
+    schedule::get_plan()
+}
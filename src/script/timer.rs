@@ -0,0 +1,32 @@
+//! Lua side of task time tracking
+
+use chrono::Local;
+use rlua::prelude::*;
+
+use super::event::{datetime_to_lua, EventHandle};
+use crate::api::{self, Interval, RunningTimer};
+
+impl<'lua> ToLua<'lua> for RunningTimer {
+    fn to_lua(self, ctx: LuaContext<'lua>) -> LuaResult<LuaValue<'lua>> {
+        let table = ctx.create_table()?;
+        table.set("task", EventHandle(self.task))?;
+        table.set("start", datetime_to_lua(&self.start, ctx)?)?;
+        table.set("elapsed", self.elapsed(Local::now()).num_seconds())?;
+        Ok(LuaValue::Table(table))
+    }
+}
+
+/// `start_task(id)`, returning the session of the task that was stopped for it, if any
+pub fn start_task(id: i32) -> LuaResult<Option<Interval>> {
+    api::start_task(id).map_err(|e| e.into())
+}
+
+/// `stop_task(id)`, returning the recorded session
+pub fn stop_task(id: i32) -> LuaResult<Interval> {
+    api::stop_task(id).map_err(|e| e.into())
+}
+
+/// `current_task()`: `{task=, start=, elapsed=}` for the running timer, or `nil`
+pub fn current_task() -> Option<RunningTimer> {
+    api::current_task()
+}
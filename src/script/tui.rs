@@ -0,0 +1,735 @@
+//! The full-screen terminal UI: a list of the logs, the details of what is selected, and a
+//! timeline of the events of a day or week. Everything it changes goes through the API, and the
+//! handlers of the events it touches run like they do for changes made from Lua.
+
+use std::collections::HashMap;
+use std::io::{self, Stdout};
+use std::panic;
+use std::sync::Arc;
+
+use chrono::{Datelike, Duration, Local, NaiveDate, TimeZone};
+use crossterm::{
+    cursor::Show,
+    event::{self as term_event, Event as TermEvent, KeyCode, KeyEvent, KeyModifiers},
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use rlua::prelude::*;
+use tui::{
+    backend::{Backend, CrosstermBackend},
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Wrap},
+    Frame, Terminal,
+};
+
+use super::event::dispatch;
+use crate::api::{
+    self, agenda::agenda_range, time, Conflict, Event, EventCommon, EventType, Interval, LogQuery,
+    Priority,
+};
+use crate::storage::model::Log;
+
+const TIME_FORMAT: &str = "%Y-%m-%d %H:%M";
+const HELP: &str = "Tab pane  j/k move  a add  e edit  d delete  / filter  q quit";
+const TIMELINE_HELP: &str = "f finish  w day/week  h/l prev/next  t today";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Pane {
+    Logs,
+    Timeline,
+}
+
+/// What a form does with its fields once they are all filled in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FormAction {
+    AddLog,
+    EditLog(i32),
+    AddEvent,
+    EditEvent(i32),
+}
+
+/// A few text fields, read one after the other on the status line
+#[derive(Debug, Clone)]
+struct Form {
+    action: FormAction,
+    fields: Vec<(&'static str, String)>,
+    current: usize,
+}
+
+#[derive(Debug, Clone)]
+enum Mode {
+    Browse,
+    Filter(String),
+    Form(Form),
+    /// Waiting for `y` before deleting the log or event with this id
+    ConfirmDelete(Pane, i32),
+}
+
+/// One occurrence of an event on the timeline
+#[derive(Debug, Clone)]
+struct Occurrence {
+    id: i32,
+    name: String,
+    priority: Priority,
+    finished: bool,
+    interval: Interval,
+}
+
+struct App {
+    pane: Pane,
+    mode: Mode,
+    /// The filter as typed, which `LogQuery::parse` makes into a query
+    filter: String,
+    logs: Vec<Log>,
+    log_state: ListState,
+    /// The first day of the timeline
+    day: NaiveDate,
+    week: bool,
+    occurrences: Vec<Occurrence>,
+    timeline_state: ListState,
+    /// The result of the last action, or its error
+    status: Option<String>,
+    /// Set by actions, whose handlers may have printed over the screen
+    repaint: bool,
+    quit: bool,
+}
+
+/// Reads props written as `key=value key=value`
+fn parse_props(s: &str) -> Result<HashMap<String, String>, String> {
+    s.split_whitespace()
+        .map(|word| match word.find('=') {
+            Some(eq) => Ok((word[..eq].to_string(), word[eq + 1..].to_string())),
+            None => Err(format!("Props are written as key=value, not '{}'", word)),
+        })
+        .collect()
+}
+
+fn format_props(props: &HashMap<String, String>) -> String {
+    let mut props: Vec<String> = props.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+    props.sort();
+    props.join(" ")
+}
+
+/// `status`, followed by the conflicts to warn about
+fn with_conflicts(status: String, conflicts: &[Conflict]) -> String {
+    let warnings: Vec<_> = conflicts.iter().map(Conflict::to_string).collect();
+    if warnings.is_empty() {
+        status
+    } else {
+        format!("{}, but {}", status, warnings.join("; "))
+    }
+}
+
+/// Moves the selection of a list of `len` items by `by`, staying inside it
+fn move_selection(state: &mut ListState, len: usize, by: i64) {
+    if len == 0 {
+        state.select(None);
+        return;
+    }
+    let current = state.selected().unwrap_or(0) as i64;
+    state.select(Some((current + by).max(0).min(len as i64 - 1) as usize));
+}
+
+impl App {
+    fn new() -> Self {
+        let mut app = Self {
+            pane: Pane::Logs,
+            mode: Mode::Browse,
+            filter: String::new(),
+            logs: Vec::new(),
+            log_state: ListState::default(),
+            day: Local::today().naive_local(),
+            week: false,
+            occurrences: Vec::new(),
+            timeline_state: ListState::default(),
+            status: None,
+            repaint: false,
+            quit: false,
+        };
+        app.refresh();
+        app
+    }
+
+    /// The days shown on the timeline
+    fn range(&self) -> Interval {
+        if self.week {
+            let monday =
+                self.day - Duration::days(self.day.weekday().num_days_from_monday() as i64);
+            agenda_range(monday, 7)
+        } else {
+            agenda_range(self.day, 1)
+        }
+    }
+
+    /// Reloads the logs and the timeline from the store, keeping the selections where possible
+    fn refresh(&mut self) {
+        self.logs = api::query_logs(&LogQuery::parse(&self.filter));
+        self.logs.sort_by_key(|log| std::cmp::Reverse(log.time));
+        move_selection(&mut self.log_state, self.logs.len(), 0);
+
+        let range = self.range();
+        self.occurrences.clear();
+        for event in api::get_events() {
+            let common = event.common();
+            for interval in event.occurrences(&range) {
+                self.occurrences.push(Occurrence {
+                    id: common.id().unwrap(),
+                    name: common.name().to_string(),
+                    priority: common.priority(),
+                    finished: common.finished(),
+                    interval,
+                });
+            }
+        }
+        self.occurrences.sort_by_key(|o| o.interval.start());
+        move_selection(&mut self.timeline_state, self.occurrences.len(), 0);
+    }
+
+    fn selected_log(&self) -> Option<&Log> {
+        self.log_state.selected().and_then(|i| self.logs.get(i))
+    }
+
+    fn selected_occurrence(&self) -> Option<&Occurrence> {
+        self.timeline_state
+            .selected()
+            .and_then(|i| self.occurrences.get(i))
+    }
+
+    fn log_form(&self, action: FormAction) -> Form {
+        let (name, desc, typ, props) = match action {
+            FormAction::EditLog(id) => {
+                let log = self.logs.iter().find(|l| l.id == id).unwrap();
+                let mut props = api::get_props_for(id);
+                let typ = props.remove(api::TYPE_PROP).unwrap_or_default();
+                (
+                    log.name.clone(),
+                    log.desc.clone(),
+                    typ,
+                    format_props(&props),
+                )
+            }
+            _ => Default::default(),
+        };
+        Form {
+            action,
+            fields: vec![
+                ("Name", name),
+                ("Description", desc),
+                ("Type", typ),
+                ("Props", props),
+            ],
+            current: 0,
+        }
+    }
+
+    fn event_form(&self, action: FormAction) -> Form {
+        let (name, start, length, priority) = match action {
+            FormAction::EditEvent(id) => match api::get_event(id) {
+                Some(EventType::Event(event)) => {
+                    let interval = event.interval();
+                    (
+                        event.common().name().to_string(),
+                        interval.start().format(TIME_FORMAT).to_string(),
+                        time::format_duration(&interval.length()),
+                        event.common().priority().name().to_string(),
+                    )
+                }
+                _ => Default::default(),
+            },
+            _ => {
+                let start = Local.from_local_date(&self.day).unwrap().and_hms(9, 0, 0);
+                (
+                    String::new(),
+                    start.format(TIME_FORMAT).to_string(),
+                    "1h".to_string(),
+                    Priority::default().name().to_string(),
+                )
+            }
+        };
+        Form {
+            action,
+            fields: vec![
+                ("Name", name),
+                ("Start", start),
+                ("Length", length),
+                ("Priority", priority),
+            ],
+            current: 0,
+        }
+    }
+
+    /// Does what `form` was opened for, returning what to show on the status line
+    fn submit(&mut self, form: Form) -> Result<String, String> {
+        let field = |i: usize| form.fields[i].1.trim().to_string();
+        match form.action {
+            FormAction::AddLog | FormAction::EditLog(_) => {
+                let mut props = parse_props(&field(3))?;
+                let typ = Some(field(2)).filter(|t| !t.is_empty());
+                if let FormAction::EditLog(id) = form.action {
+                    if let Some(typ) = typ {
+                        props.insert(api::TYPE_PROP.into(), typ);
+                    }
+                    api::update_log(id, field(0), field(1), &props);
+                    return Ok(format!("Updated log {}", id));
+                }
+                if typ.is_some() {
+                    api::add_log_with_type(field(0), field(1), typ, props, false)
+                        .map_err(|e| e.to_string())?;
+                } else {
+                    api::add_log_with_props(field(0), field(1), &props);
+                }
+                Ok("Added log".into())
+            }
+            FormAction::AddEvent | FormAction::EditEvent(_) => {
+                let start = time::parse_datetime(field(1))
+                    .ok_or_else(|| format!("Invalid start: '{}'", field(1)))?;
+                let length = time::parse_duration(field(2))
+                    .ok_or_else(|| format!("Invalid length: '{}'", field(2)))?;
+                let priority: Priority = field(3)
+                    .parse()
+                    .map_err(|e: api::error::ErrorKind| e.to_string())?;
+                let interval = Interval::builder()
+                    .start(start)
+                    .length(length)
+                    .build()
+                    .map_err(|e| e.to_string())?;
+                let mut event = Event::new(EventCommon::new(field(0), priority), interval);
+                if let FormAction::EditEvent(id) = form.action {
+                    // The time changes, but not how it repeats
+                    if let Some(EventType::Event(old)) = api::get_event(id) {
+                        event.set_recurrence(old.recurrence().cloned());
+                    }
+                    let conflicts = api::update_event(id, &event).map_err(|e| e.to_string())?;
+                    return Ok(with_conflicts(format!("Updated event {}", id), &conflicts));
+                }
+                let (id, conflicts) = api::add_event(&mut event).map_err(|e| e.to_string())?;
+                Ok(with_conflicts(format!("Added event {}", id), &conflicts))
+            }
+        }
+    }
+
+    fn delete(&mut self, pane: Pane, id: i32) -> Result<String, String> {
+        match pane {
+            Pane::Logs => {
+                api::delete_log(id);
+                Ok(format!("Deleted log {}", id))
+            }
+            Pane::Timeline => {
+                api::delete_event(id).map_err(|e| e.to_string())?;
+                Ok(format!("Deleted event {}", id))
+            }
+        }
+    }
+
+    /// Runs the handlers of whatever the last action changed, and reloads everything
+    fn after_action(&mut self, ctx: LuaContext, result: Result<String, String>) {
+        let handled = dispatch(ctx).map_err(|e| e.to_string());
+        self.status = Some(match result.and_then(|msg| handled.map(|_| msg)) {
+            Ok(msg) => msg,
+            Err(e) => format!("Error: {}", e),
+        });
+        self.repaint = true;
+        self.refresh();
+    }
+
+    fn browse_key(&mut self, ctx: LuaContext, key: KeyEvent) {
+        let (state, len) = match self.pane {
+            Pane::Logs => (&mut self.log_state, self.logs.len()),
+            Pane::Timeline => (&mut self.timeline_state, self.occurrences.len()),
+        };
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => self.quit = true,
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => self.quit = true,
+            KeyCode::Tab | KeyCode::BackTab => {
+                self.pane = match self.pane {
+                    Pane::Logs => Pane::Timeline,
+                    Pane::Timeline => Pane::Logs,
+                }
+            }
+            KeyCode::Char('j') | KeyCode::Down => move_selection(state, len, 1),
+            KeyCode::Char('k') | KeyCode::Up => move_selection(state, len, -1),
+            KeyCode::PageDown => move_selection(state, len, 10),
+            KeyCode::PageUp => move_selection(state, len, -10),
+            KeyCode::Char('g') | KeyCode::Home => move_selection(state, len, -(len as i64)),
+            KeyCode::Char('G') | KeyCode::End => move_selection(state, len, len as i64),
+            KeyCode::Char('/') => self.mode = Mode::Filter(self.filter.clone()),
+            KeyCode::Char('a') => {
+                self.mode = Mode::Form(match self.pane {
+                    Pane::Logs => self.log_form(FormAction::AddLog),
+                    Pane::Timeline => self.event_form(FormAction::AddEvent),
+                })
+            }
+            KeyCode::Char('e') => match self.pane {
+                Pane::Logs => {
+                    if let Some(id) = self.selected_log().map(|l| l.id) {
+                        self.mode = Mode::Form(self.log_form(FormAction::EditLog(id)));
+                    }
+                }
+                Pane::Timeline => {
+                    if let Some(id) = self.selected_occurrence().map(|o| o.id) {
+                        self.mode = Mode::Form(self.event_form(FormAction::EditEvent(id)));
+                    }
+                }
+            },
+            KeyCode::Char('d') | KeyCode::Delete => {
+                let id = match self.pane {
+                    Pane::Logs => self.selected_log().map(|l| l.id),
+                    Pane::Timeline => self.selected_occurrence().map(|o| o.id),
+                };
+                if let Some(id) = id {
+                    self.mode = Mode::ConfirmDelete(self.pane, id);
+                }
+            }
+            KeyCode::Char('f') if self.pane == Pane::Timeline => {
+                if let Some(id) = self.selected_occurrence().map(|o| o.id) {
+                    api::finish_event(id);
+                    self.after_action(ctx, Ok(format!("Finished event {}", id)));
+                }
+            }
+            KeyCode::Char('w') => {
+                self.week = !self.week;
+                self.refresh();
+            }
+            KeyCode::Char('h') | KeyCode::Char('[') | KeyCode::Left => {
+                self.day -= Duration::days(if self.week { 7 } else { 1 });
+                self.refresh();
+            }
+            KeyCode::Char('l') | KeyCode::Char(']') | KeyCode::Right => {
+                self.day += Duration::days(if self.week { 7 } else { 1 });
+                self.refresh();
+            }
+            KeyCode::Char('t') => {
+                self.day = Local::today().naive_local();
+                self.refresh();
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_key(&mut self, ctx: LuaContext, key: KeyEvent) {
+        let mode = std::mem::replace(&mut self.mode, Mode::Browse);
+        self.mode = match mode {
+            Mode::Browse => {
+                self.status = None;
+                self.browse_key(ctx, key);
+                return;
+            }
+            Mode::Filter(mut filter) => match key.code {
+                KeyCode::Enter => {
+                    self.filter = filter;
+                    self.log_state.select(Some(0));
+                    self.refresh();
+                    Mode::Browse
+                }
+                KeyCode::Esc => Mode::Browse,
+                KeyCode::Backspace => {
+                    filter.pop();
+                    Mode::Filter(filter)
+                }
+                KeyCode::Char(c) => {
+                    filter.push(c);
+                    Mode::Filter(filter)
+                }
+                _ => Mode::Filter(filter),
+            },
+            Mode::Form(mut form) => match key.code {
+                KeyCode::Enter if form.current + 1 < form.fields.len() => {
+                    form.current += 1;
+                    Mode::Form(form)
+                }
+                KeyCode::Enter => {
+                    let result = self.submit(form);
+                    self.after_action(ctx, result);
+                    Mode::Browse
+                }
+                KeyCode::Esc => Mode::Browse,
+                KeyCode::BackTab | KeyCode::Up => {
+                    form.current = form.current.saturating_sub(1);
+                    Mode::Form(form)
+                }
+                KeyCode::Tab | KeyCode::Down => {
+                    form.current = (form.current + 1).min(form.fields.len() - 1);
+                    Mode::Form(form)
+                }
+                KeyCode::Backspace => {
+                    form.fields[form.current].1.pop();
+                    Mode::Form(form)
+                }
+                KeyCode::Char(c) => {
+                    form.fields[form.current].1.push(c);
+                    Mode::Form(form)
+                }
+                _ => Mode::Form(form),
+            },
+            Mode::ConfirmDelete(pane, id) => {
+                if let KeyCode::Char('y') | KeyCode::Char('Y') = key.code {
+                    let result = self.delete(pane, id);
+                    self.after_action(ctx, result);
+                }
+                Mode::Browse
+            }
+        };
+    }
+
+    fn pane_block(&self, title: String, pane: Option<Pane>) -> Block<'static> {
+        let style = if pane == Some(self.pane) {
+            Style::default().fg(Color::Yellow)
+        } else {
+            Style::default()
+        };
+        Block::default()
+            .title(title)
+            .borders(Borders::ALL)
+            .border_style(style)
+    }
+
+    fn draw_logs<B: Backend>(&mut self, f: &mut Frame<B>, area: Rect) {
+        let items: Vec<ListItem> = self
+            .logs
+            .iter()
+            .map(|log| {
+                let time = Local.from_utc_datetime(&log.time).format(TIME_FORMAT);
+                ListItem::new(Spans::from(vec![
+                    Span::styled(format!("{} ", time), Style::default().fg(Color::DarkGray)),
+                    Span::raw(log.name.clone()),
+                ]))
+            })
+            .collect();
+        let title = if self.filter.is_empty() {
+            format!("Logs ({})", self.logs.len())
+        } else {
+            format!("Logs ({}) [{}]", self.logs.len(), self.filter)
+        };
+        let list = List::new(items)
+            .block(self.pane_block(title, Some(Pane::Logs)))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        f.render_stateful_widget(list, area, &mut self.log_state);
+    }
+
+    fn detail_text(&self) -> Vec<Spans<'static>> {
+        let label = |s: &str| Span::styled(format!("{}: ", s), Style::default().fg(Color::Cyan));
+        let line = |l: &str, v: String| Spans::from(vec![label(l), Span::raw(v)]);
+        let mut lines = Vec::new();
+        match self.pane {
+            Pane::Logs => {
+                let log = match self.selected_log() {
+                    Some(log) => log,
+                    None => return lines,
+                };
+                let time = Local.from_utc_datetime(&log.time).format(TIME_FORMAT);
+                lines.push(line("Name", log.name.clone()));
+                lines.push(line("Time", time.to_string()));
+                lines.push(line("Description", log.desc.clone()));
+                let props = api::get_props_for(log.id);
+                let mut keys: Vec<&String> = props.keys().collect();
+                keys.sort();
+                for key in keys {
+                    lines.push(Spans::from(vec![
+                        Span::raw("  "),
+                        label(key),
+                        Span::raw(props[key].clone()),
+                    ]));
+                }
+            }
+            Pane::Timeline => {
+                let occurrence = match self.selected_occurrence() {
+                    Some(o) => o,
+                    None => return lines,
+                };
+                let interval = &occurrence.interval;
+                lines.push(line("Name", occurrence.name.clone()));
+                lines.push(line("Priority", occurrence.priority.name().to_string()));
+                lines.push(line(
+                    "Start",
+                    interval.start().format(TIME_FORMAT).to_string(),
+                ));
+                lines.push(line("End", interval.end().format(TIME_FORMAT).to_string()));
+                lines.push(line("Length", time::format_duration(&interval.length())));
+                lines.push(line("Finished", occurrence.finished.to_string()));
+                if let Some(EventType::Event(event)) = api::get_event(occurrence.id) {
+                    if let Some(recurrence) = event.recurrence() {
+                        lines.push(line("Repeats", recurrence.rule.to_string()));
+                    }
+                    let props = event.common().props();
+                    if !props.is_empty() {
+                        lines.push(line("Props", format_props(props)));
+                    }
+                }
+            }
+        }
+        lines
+    }
+
+    fn draw_timeline<B: Backend>(&mut self, f: &mut Frame<B>, area: Rect) {
+        let week = self.week;
+        let items: Vec<ListItem> = self
+            .occurrences
+            .iter()
+            .map(|o| {
+                let start = o.interval.start();
+                let time = if week {
+                    format!(
+                        "{} {}-{}",
+                        start.format("%a %m-%d"),
+                        start.format("%H:%M"),
+                        o.interval.end().format("%H:%M")
+                    )
+                } else {
+                    format!(
+                        "{}-{}",
+                        start.format("%H:%M"),
+                        o.interval.end().format("%H:%M")
+                    )
+                };
+                let mut style = Style::default();
+                if o.finished {
+                    style = style
+                        .fg(Color::DarkGray)
+                        .add_modifier(Modifier::CROSSED_OUT);
+                }
+                ListItem::new(Spans::from(vec![
+                    Span::styled(format!("{} ", time), Style::default().fg(Color::Green)),
+                    Span::styled(format!("{} ", o.name), style),
+                    Span::styled(
+                        format!("[{}]", o.priority.name()),
+                        Style::default().fg(Color::DarkGray),
+                    ),
+                ]))
+            })
+            .collect();
+        let range = self.range();
+        let title = if week {
+            format!(
+                "Week of {} ({})",
+                range.start().format("%Y-%m-%d"),
+                self.occurrences.len()
+            )
+        } else {
+            format!(
+                "{} ({})",
+                range.start().format("%a %Y-%m-%d"),
+                self.occurrences.len()
+            )
+        };
+        let list = List::new(items)
+            .block(self.pane_block(title, Some(Pane::Timeline)))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        f.render_stateful_widget(list, area, &mut self.timeline_state);
+    }
+
+    fn draw_status<B: Backend>(&self, f: &mut Frame<B>, area: Rect) {
+        let bold = Style::default().add_modifier(Modifier::BOLD);
+        let (line, cursor) = match &self.mode {
+            Mode::Browse => {
+                let text = match &self.status {
+                    Some(status) => status.clone(),
+                    None if self.pane == Pane::Timeline => format!("{}  {}", HELP, TIMELINE_HELP),
+                    None => HELP.to_string(),
+                };
+                (Spans::from(text), None)
+            }
+            Mode::Filter(filter) => {
+                let prompt = "Filter: ";
+                let cursor = prompt.len() + filter.chars().count();
+                (
+                    Spans::from(vec![Span::styled(prompt, bold), Span::raw(filter.clone())]),
+                    Some(cursor),
+                )
+            }
+            Mode::Form(form) => {
+                let (label, value) = &form.fields[form.current];
+                let prompt = format!("({}/{}) {}: ", form.current + 1, form.fields.len(), label);
+                let cursor = prompt.chars().count() + value.chars().count();
+                (
+                    Spans::from(vec![Span::styled(prompt, bold), Span::raw(value.clone())]),
+                    Some(cursor),
+                )
+            }
+            Mode::ConfirmDelete(pane, id) => {
+                let what = match pane {
+                    Pane::Logs => "log",
+                    Pane::Timeline => "event",
+                };
+                let text = format!("Delete {} {}? (y/n)", what, id);
+                (Spans::from(Span::styled(text, bold)), None)
+            }
+        };
+        f.render_widget(Paragraph::new(line), area);
+        if let Some(cursor) = cursor {
+            f.set_cursor(area.x + (cursor as u16).min(area.width), area.y);
+        }
+    }
+
+    fn draw<B: Backend>(&mut self, f: &mut Frame<B>) {
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(
+                [
+                    Constraint::Percentage(55),
+                    Constraint::Min(3),
+                    Constraint::Length(1),
+                ]
+                .as_ref(),
+            )
+            .split(f.size());
+        let top = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(45), Constraint::Percentage(55)].as_ref())
+            .split(rows[0]);
+        self.draw_logs(f, top[0]);
+        let detail = Paragraph::new(self.detail_text())
+            .block(self.pane_block("Detail".into(), None))
+            .wrap(Wrap { trim: false });
+        f.render_widget(detail, top[1]);
+        self.draw_timeline(f, rows[1]);
+        self.draw_status(f, rows[2]);
+    }
+}
+
+fn event_loop<B: Backend>(ctx: LuaContext, terminal: &mut Terminal<B>) -> crossterm::Result<()> {
+    let mut app = App::new();
+    while !app.quit {
+        terminal.draw(|f| app.draw(f))?;
+        if let TermEvent::Key(key) = term_event::read()? {
+            app.handle_key(ctx, key);
+        }
+        if app.repaint {
+            terminal.clear()?;
+            app.repaint = false;
+        }
+    }
+    Ok(())
+}
+
+fn restore() -> crossterm::Result<()> {
+    disable_raw_mode()?;
+    execute!(io::stdout(), LeaveAlternateScreen, Show)?;
+    Ok(())
+}
+
+/// Runs the terminal UI until it is quit, leaving the terminal as it was found. A panic restores
+/// the terminal before its message is printed.
+pub fn run(ctx: LuaContext) -> LuaResult<()> {
+    let setup = || -> crossterm::Result<Terminal<CrosstermBackend<Stdout>>> {
+        enable_raw_mode()?;
+        let mut stdout = io::stdout();
+        execute!(stdout, EnterAlternateScreen)?;
+        Ok(Terminal::new(CrosstermBackend::new(stdout))?)
+    };
+    let previous_hook = Arc::new(panic::take_hook());
+    let hook = previous_hook.clone();
+    panic::set_hook(Box::new(move |info| {
+        let _ = restore();
+        hook(info);
+    }));
+    let result = setup().and_then(|mut terminal| event_loop(ctx, &mut terminal));
+    let restored = restore();
+    drop(panic::take_hook());
+    if let Ok(hook) = Arc::try_unwrap(previous_hook) {
+        panic::set_hook(hook);
+    }
+    restored.map_err(LuaError::external)?;
+    result.map_err(LuaError::external)
+}
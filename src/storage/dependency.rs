@@ -0,0 +1,35 @@
+use diesel::prelude::*;
+
+use super::model::*;
+use super::schema::dependencies;
+use super::LogStorage;
+
+impl LogStorage {
+    /// Makes the task `id` depend on the task `depends_on`, replacing the lag if it already did
+    pub fn add_dependency(&mut self, id: i32, depends_on: i32, lag: i64) {
+        diesel::insert_into(dependencies::table)
+            .values(&NewDependencyRecord {
+                id,
+                depends_on,
+                lag,
+            })
+            .execute(&self.0)
+            .unwrap();
+    }
+
+    pub fn remove_dependency(&mut self, id: i32, depends_on: i32) {
+        diesel::delete(
+            dependencies::table
+                .filter(dependencies::id.eq(id))
+                .filter(dependencies::depends_on.eq(depends_on)),
+        )
+        .execute(&self.0)
+        .unwrap();
+    }
+
+    pub fn get_dependencies(&self) -> Vec<DependencyRecord> {
+        dependencies::table
+            .load::<DependencyRecord>(&self.0)
+            .unwrap()
+    }
+}
@@ -0,0 +1,273 @@
+use std::collections::HashMap;
+
+use chrono::naive::NaiveDateTime;
+use diesel::prelude::*;
+
+use super::model::*;
+use super::schema::{
+    deadlines, dependencies, estimates, event_attrs, events, focus_sessions, intervals,
+    planned_sessions, recurrence_exceptions, recurrences, timers, uids,
+};
+use super::LogStorage;
+
+impl LogStorage {
+    pub fn add_event<S1, S2>(
+        &mut self,
+        kind: S1,
+        name: S2,
+        priority: i32,
+        parent: Option<i32>,
+    ) -> i32
+    where
+        S1: AsRef<str>,
+        S2: AsRef<str>,
+    {
+        diesel::insert_into(events::table)
+            .values(&NewEventRecord {
+                kind: kind.as_ref(),
+                name: name.as_ref(),
+                priority,
+                finished: false,
+                parent,
+            })
+            .execute(&self.0)
+            .unwrap();
+        events::table
+            .select(diesel::dsl::max(events::id))
+            .first::<Option<i32>>(&self.0)
+            .unwrap()
+            .unwrap()
+    }
+
+    pub fn add_interval(&mut self, id: i32, start: NaiveDateTime, length: i64) -> i32 {
+        diesel::insert_into(intervals::table)
+            .values(&NewIntervalRecord { id, start, length })
+            .execute(&self.0)
+            .unwrap();
+        intervals::table
+            .select(diesel::dsl::max(intervals::interval_id))
+            .first::<Option<i32>>(&self.0)
+            .unwrap()
+            .unwrap()
+    }
+
+    pub fn clear_intervals(&mut self, id: i32) {
+        diesel::delete(intervals::table.filter(intervals::id.eq(id)))
+            .execute(&self.0)
+            .unwrap();
+    }
+
+    pub fn set_event_prop<S1, S2>(&mut self, id: i32, key: S1, val: S2)
+    where
+        S1: AsRef<str>,
+        S2: AsRef<str>,
+    {
+        diesel::insert_into(event_attrs::table)
+            .values(&NewEventAttr {
+                id,
+                key: key.as_ref(),
+                val: val.as_ref(),
+            })
+            .execute(&self.0)
+            .unwrap();
+    }
+
+    pub fn update_event<S: AsRef<str>>(&mut self, id: i32, name: S, priority: i32) {
+        diesel::update(events::table.find(id))
+            .set((
+                events::name.eq(name.as_ref()),
+                events::priority.eq(priority),
+            ))
+            .execute(&self.0)
+            .unwrap();
+    }
+
+    pub fn set_finished(&mut self, id: i32, finished: bool) {
+        diesel::update(events::table.find(id))
+            .set(events::finished.eq(finished))
+            .execute(&self.0)
+            .unwrap();
+    }
+
+    pub fn set_parent(&mut self, id: i32, parent: Option<i32>) {
+        diesel::update(events::table.find(id))
+            .set(events::parent.eq(parent))
+            .execute(&self.0)
+            .unwrap();
+    }
+
+    /// Removes the event `id` and everything stored about it. Sync state is kept, so that the
+    /// next sync deletes it on the server too.
+    pub fn delete_event(&mut self, id: i32) {
+        diesel::delete(event_attrs::table.filter(event_attrs::id.eq(id)))
+            .execute(&self.0)
+            .unwrap();
+        diesel::delete(
+            dependencies::table.filter(dependencies::id.eq(id).or(dependencies::depends_on.eq(id))),
+        )
+        .execute(&self.0)
+        .unwrap();
+        diesel::delete(planned_sessions::table.filter(planned_sessions::id.eq(id)))
+            .execute(&self.0)
+            .unwrap();
+        diesel::delete(timers::table.find(id))
+            .execute(&self.0)
+            .unwrap();
+        diesel::delete(focus_sessions::table.find(id))
+            .execute(&self.0)
+            .unwrap();
+        diesel::delete(uids::table.find(id))
+            .execute(&self.0)
+            .unwrap();
+        self.clear_intervals(id);
+        self.clear_recurrence_exceptions(id);
+        self.set_recurrence(id, None);
+        self.set_estimate(id, None);
+        self.set_deadline(id, None);
+        diesel::delete(events::table.find(id))
+            .execute(&self.0)
+            .unwrap();
+    }
+
+    pub fn get_event(&self, id: i32) -> Option<EventRecord> {
+        events::table
+            .find(id)
+            .first::<EventRecord>(&self.0)
+            .optional()
+            .unwrap()
+    }
+
+    pub fn get_events(&self) -> Vec<EventRecord> {
+        events::table.load::<EventRecord>(&self.0).unwrap()
+    }
+
+    pub fn get_events_of_kind<S: AsRef<str>>(&self, kind: S) -> Vec<EventRecord> {
+        events::table
+            .filter(events::kind.eq(kind.as_ref()))
+            .load::<EventRecord>(&self.0)
+            .unwrap()
+    }
+
+    pub fn get_children(&self, parent: i32) -> Vec<EventRecord> {
+        events::table
+            .filter(events::parent.eq(parent))
+            .load::<EventRecord>(&self.0)
+            .unwrap()
+    }
+
+    pub fn get_intervals_for(&self, id: i32) -> Vec<IntervalRecord> {
+        intervals::table
+            .filter(intervals::id.eq(id))
+            .order(intervals::start)
+            .load::<IntervalRecord>(&self.0)
+            .unwrap()
+    }
+
+    pub fn get_event_props_for(&self, id: i32) -> HashMap<String, String> {
+        event_attrs::table
+            .filter(event_attrs::id.eq(id))
+            .load::<Attr>(&self.0)
+            .unwrap()
+            .into_iter()
+            .map(|a| (a.key, a.val))
+            .collect()
+    }
+
+    /// Sets or removes the recurrence rule of the event `id`
+    pub fn set_recurrence(&mut self, id: i32, rule: Option<&str>) {
+        diesel::delete(recurrences::table.find(id))
+            .execute(&self.0)
+            .unwrap();
+        if let Some(rule) = rule {
+            diesel::insert_into(recurrences::table)
+                .values(&NewRecurrence { id, rule })
+                .execute(&self.0)
+                .unwrap();
+        }
+    }
+
+    pub fn get_recurrence(&self, id: i32) -> Option<String> {
+        recurrences::table
+            .find(id)
+            .select(recurrences::rule)
+            .first::<String>(&self.0)
+            .optional()
+            .unwrap()
+    }
+
+    /// Removes the occurrence of the event `id` starting at `original` if `replacement` is `None`,
+    /// or moves it to the given start and length otherwise
+    pub fn set_recurrence_exception(
+        &mut self,
+        id: i32,
+        original: NaiveDateTime,
+        replacement: Option<(NaiveDateTime, i64)>,
+    ) {
+        diesel::insert_into(recurrence_exceptions::table)
+            .values(&NewRecurrenceException {
+                id,
+                original,
+                start: replacement.map(|r| r.0),
+                length: replacement.map(|r| r.1),
+            })
+            .execute(&self.0)
+            .unwrap();
+    }
+
+    pub fn clear_recurrence_exceptions(&mut self, id: i32) {
+        diesel::delete(recurrence_exceptions::table.filter(recurrence_exceptions::id.eq(id)))
+            .execute(&self.0)
+            .unwrap();
+    }
+
+    pub fn get_recurrence_exceptions(&self, id: i32) -> Vec<RecurrenceException> {
+        recurrence_exceptions::table
+            .filter(recurrence_exceptions::id.eq(id))
+            .load::<RecurrenceException>(&self.0)
+            .unwrap()
+    }
+
+    /// Sets or removes the estimated effort of the task `id`, in seconds
+    pub fn set_estimate(&mut self, id: i32, length: Option<i64>) {
+        diesel::delete(estimates::table.find(id))
+            .execute(&self.0)
+            .unwrap();
+        if let Some(length) = length {
+            diesel::insert_into(estimates::table)
+                .values(&Estimate { id, length })
+                .execute(&self.0)
+                .unwrap();
+        }
+    }
+
+    pub fn get_estimate(&self, id: i32) -> Option<i64> {
+        estimates::table
+            .find(id)
+            .select(estimates::length)
+            .first::<i64>(&self.0)
+            .optional()
+            .unwrap()
+    }
+
+    /// Sets or removes the deadline of the task or project `id`
+    pub fn set_deadline(&mut self, id: i32, due: Option<NaiveDateTime>) {
+        diesel::delete(deadlines::table.find(id))
+            .execute(&self.0)
+            .unwrap();
+        if let Some(due) = due {
+            diesel::insert_into(deadlines::table)
+                .values(&Deadline { id, due })
+                .execute(&self.0)
+                .unwrap();
+        }
+    }
+
+    pub fn get_deadline(&self, id: i32) -> Option<NaiveDateTime> {
+        deadlines::table
+            .find(id)
+            .select(deadlines::due)
+            .first::<NaiveDateTime>(&self.0)
+            .optional()
+            .unwrap()
+    }
+}
@@ -0,0 +1,29 @@
+use diesel::prelude::*;
+
+use super::model::*;
+use super::schema::focus_sessions;
+use super::LogStorage;
+
+impl LogStorage {
+    /// Stores the running focus session, replacing whichever one was running
+    pub fn set_focus(&mut self, session: &FocusRecord) {
+        self.clear_focus();
+        diesel::insert_into(focus_sessions::table)
+            .values(session)
+            .execute(&self.0)
+            .unwrap();
+    }
+
+    pub fn clear_focus(&mut self) {
+        diesel::delete(focus_sessions::table)
+            .execute(&self.0)
+            .unwrap();
+    }
+
+    pub fn get_focus(&self) -> Option<FocusRecord> {
+        focus_sessions::table
+            .first::<FocusRecord>(&self.0)
+            .optional()
+            .unwrap()
+    }
+}
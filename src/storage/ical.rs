@@ -0,0 +1,39 @@
+use diesel::prelude::*;
+
+use super::model::*;
+use super::schema::uids;
+use super::LogStorage;
+
+impl LogStorage {
+    /// Remembers the iCalendar UID of the item `id`, replacing any earlier one
+    pub fn set_uid<S: AsRef<str>>(&mut self, id: i32, uid: S) {
+        diesel::delete(uids::table.find(id))
+            .execute(&self.0)
+            .unwrap();
+        diesel::insert_into(uids::table)
+            .values(&NewUid {
+                id,
+                uid: uid.as_ref(),
+            })
+            .execute(&self.0)
+            .unwrap();
+    }
+
+    pub fn get_uid(&self, id: i32) -> Option<String> {
+        uids::table
+            .find(id)
+            .select(uids::uid)
+            .first::<String>(&self.0)
+            .optional()
+            .unwrap()
+    }
+
+    pub fn get_id_for_uid<S: AsRef<str>>(&self, uid: S) -> Option<i32> {
+        uids::table
+            .filter(uids::uid.eq(uid.as_ref()))
+            .select(uids::id)
+            .first::<i32>(&self.0)
+            .optional()
+            .unwrap()
+    }
+}
@@ -0,0 +1,27 @@
+use chrono::naive::NaiveDateTime;
+use diesel::prelude::*;
+
+use super::model::*;
+use super::schema::transition_checks;
+use super::LogStorage;
+
+impl LogStorage {
+    /// Records that timed transitions were checked for up to `time`
+    pub fn set_last_check(&mut self, time: NaiveDateTime) {
+        diesel::delete(transition_checks::table)
+            .execute(&self.0)
+            .unwrap();
+        diesel::insert_into(transition_checks::table)
+            .values(&TransitionCheck { id: 1, time })
+            .execute(&self.0)
+            .unwrap();
+    }
+
+    pub fn get_last_check(&self) -> Option<NaiveDateTime> {
+        transition_checks::table
+            .select(transition_checks::time)
+            .first(&self.0)
+            .optional()
+            .unwrap()
+    }
+}
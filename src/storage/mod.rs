@@ -1,18 +1,39 @@
 use std::collections::HashMap;
+use std::sync::Mutex;
 
+use chrono::NaiveDateTime;
 use redis::{Connection, Commands, Client};
 
+mod dependency;
+mod event;
+mod focus;
+mod ical;
+mod lifecycle;
 pub mod model;
+mod schedule;
 mod schema;
+mod sync;
+mod timer;
 
 use model::*;
 use schema::{attrs, logs};
 
+lazy_static! {
+    /// Where the storage is opened
+    static ref DATABASE: Mutex<String> = Mutex::new("localhost".into());
+}
+
+/// Makes storage opened from now on use `url` instead of the default. The API opens its storage
+/// when it is first used, so this has to be called before that.
+pub fn set_database<S: Into<String>>(url: S) {
+    *DATABASE.lock().unwrap() = url.into();
+}
+
 pub struct LogStorage(Connection);
 
 impl LogStorage {
     pub fn new() -> LogStorage {
-        let conn = Client::open("localhost").unwrap();
+        let conn = Client::open(DATABASE.lock().unwrap().as_str()).unwrap();
         LogStorage(conn)
     }
 
@@ -55,7 +76,8 @@ impl LogStorage {
         name: S1,
         desc: S2,
         props: &HashMap<String, String>,
-    ) where
+    ) -> i32
+    where
         S1: AsRef<str>,
         S2: AsRef<str>,
     {
@@ -63,12 +85,49 @@ impl LogStorage {
         for (key, val) in props.iter() {
             self.set_prop(id, key, val);
         }
+        id
+    }
+
+    pub fn update_log<S1, S2>(&mut self, id: i32, name: S1, desc: S2)
+    where
+        S1: AsRef<str>,
+        S2: AsRef<str>,
+    {
+        diesel::update(logs::table.find(id))
+            .set((logs::name.eq(name.as_ref()), logs::desc.eq(desc.as_ref())))
+            .execute(&self.0)
+            .unwrap();
+    }
+
+    pub fn set_log_time(&mut self, id: i32, time: NaiveDateTime) {
+        diesel::update(logs::table.find(id))
+            .set(logs::time.eq(time))
+            .execute(&self.0)
+            .unwrap();
+    }
+
+    pub fn clear_props(&mut self, id: i32) {
+        diesel::delete(attrs::table.filter(attrs::id.eq(id)))
+            .execute(&self.0)
+            .unwrap();
+    }
+
+    /// Removes the log `id` along with its props
+    pub fn delete_log(&mut self, id: i32) {
+        self.clear_props(id);
+        diesel::delete(logs::table.find(id))
+            .execute(&self.0)
+            .unwrap();
     }
 
     pub fn get_logs(&self) -> Vec<Log> {
         logs::table.load::<Log>(&self.0).unwrap()
     }
 
+    pub fn get_log(&self, id: i32) -> Option<Log> {
+        logs::table.find(id).first::<Log>(&self.0).optional().unwrap()
+    }
+
     pub fn get_props_for(&self, id: i32) -> HashMap<String, String> {
         attrs::table
             .filter(attrs::id.eq(id))
@@ -2,7 +2,11 @@ use chrono::naive::NaiveDateTime;
 use rlua::{prelude::*, Context, Value};
 use rlua_serde::to_value;
 
-use super::schema::{attrs, logs};
+use super::schema::{
+    attrs, deadlines, dependencies, estimates, event_attrs, events, focus_sessions, intervals,
+    logs, planned_sessions, recurrence_exceptions, recurrences, sync_items, timers,
+    transition_checks, uids,
+};
 
 #[derive(Queryable, Serialize)]
 pub struct Log {
@@ -40,3 +44,170 @@ pub struct NewAttr<'a> {
     pub key: &'a str,
     pub val: &'a str,
 }
+
+#[derive(Queryable)]
+pub struct EventRecord {
+    pub id: i32,
+    pub kind: String,
+    pub name: String,
+    pub priority: i32,
+    pub finished: bool,
+    pub parent: Option<i32>,
+    pub created: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[table_name = "events"]
+pub struct NewEventRecord<'a> {
+    pub kind: &'a str,
+    pub name: &'a str,
+    pub priority: i32,
+    pub finished: bool,
+    pub parent: Option<i32>,
+}
+
+#[derive(Queryable)]
+pub struct IntervalRecord {
+    pub interval_id: i32,
+    pub id: i32,
+    pub start: NaiveDateTime,
+    pub length: i64,
+}
+
+#[derive(Insertable)]
+#[table_name = "intervals"]
+pub struct NewIntervalRecord {
+    pub id: i32,
+    pub start: NaiveDateTime,
+    pub length: i64,
+}
+
+#[derive(Insertable, AsChangeset)]
+#[table_name = "event_attrs"]
+pub struct NewEventAttr<'a> {
+    pub id: i32,
+    pub key: &'a str,
+    pub val: &'a str,
+}
+
+#[derive(Insertable)]
+#[table_name = "recurrences"]
+pub struct NewRecurrence<'a> {
+    pub id: i32,
+    pub rule: &'a str,
+}
+
+/// An occurrence of a recurring event that was removed (when `start` is `None`) or moved
+#[derive(Queryable)]
+pub struct RecurrenceException {
+    pub exception_id: i32,
+    pub id: i32,
+    pub original: NaiveDateTime,
+    pub start: Option<NaiveDateTime>,
+    pub length: Option<i64>,
+}
+
+#[derive(Insertable)]
+#[table_name = "recurrence_exceptions"]
+pub struct NewRecurrenceException {
+    pub id: i32,
+    pub original: NaiveDateTime,
+    pub start: Option<NaiveDateTime>,
+    pub length: Option<i64>,
+}
+
+#[derive(Queryable)]
+pub struct PlannedSession {
+    pub session_id: i32,
+    pub id: i32,
+    pub start: NaiveDateTime,
+    pub length: i64,
+    pub reason: String,
+}
+
+#[derive(Insertable)]
+#[table_name = "planned_sessions"]
+pub struct NewPlannedSession<'a> {
+    pub id: i32,
+    pub start: NaiveDateTime,
+    pub length: i64,
+    pub reason: &'a str,
+}
+
+#[derive(Queryable, Insertable)]
+#[table_name = "timers"]
+pub struct Timer {
+    pub id: i32,
+    pub start: NaiveDateTime,
+}
+
+/// The running focus session on the task `id`, with its lengths in seconds
+#[derive(Queryable, Insertable)]
+#[table_name = "focus_sessions"]
+pub struct FocusRecord {
+    pub id: i32,
+    pub work: i64,
+    pub short_break: i64,
+    pub long_break: i64,
+    pub long_break_every: i32,
+    pub max_idle_blocks: Option<i32>,
+    pub phase: String,
+    pub phase_start: NaiveDateTime,
+    pub blocks: i32,
+    pub interruptions: i32,
+}
+
+#[derive(Queryable, Insertable)]
+#[table_name = "transition_checks"]
+pub struct TransitionCheck {
+    pub id: i32,
+    pub time: NaiveDateTime,
+}
+
+#[derive(Queryable, Insertable)]
+#[table_name = "estimates"]
+pub struct Estimate {
+    pub id: i32,
+    pub length: i64,
+}
+
+#[derive(Queryable)]
+pub struct DependencyRecord {
+    pub dependency_id: i32,
+    pub id: i32,
+    pub depends_on: i32,
+    pub lag: i64,
+}
+
+#[derive(Insertable)]
+#[table_name = "dependencies"]
+pub struct NewDependencyRecord {
+    pub id: i32,
+    pub depends_on: i32,
+    pub lag: i64,
+}
+
+#[derive(Queryable, Insertable)]
+#[table_name = "deadlines"]
+pub struct Deadline {
+    pub id: i32,
+    pub due: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[table_name = "uids"]
+pub struct NewUid<'a> {
+    pub id: i32,
+    pub uid: &'a str,
+}
+
+/// What both sides of a CalDAV sync had for the item `id` after the last sync. `etag` is `None`
+/// once the resource was deleted on the server.
+#[derive(Queryable, Insertable)]
+#[table_name = "sync_items"]
+pub struct SyncItem {
+    pub id: i32,
+    pub href: String,
+    pub etag: Option<String>,
+    pub data: String,
+}
@@ -0,0 +1,39 @@
+use chrono::naive::NaiveDateTime;
+use diesel::prelude::*;
+
+use super::model::*;
+use super::schema::planned_sessions;
+use super::LogStorage;
+
+impl LogStorage {
+    pub fn add_planned_session<S: AsRef<str>>(
+        &mut self,
+        id: i32,
+        start: NaiveDateTime,
+        length: i64,
+        reason: S,
+    ) {
+        diesel::insert_into(planned_sessions::table)
+            .values(&NewPlannedSession {
+                id,
+                start,
+                length,
+                reason: reason.as_ref(),
+            })
+            .execute(&self.0)
+            .unwrap();
+    }
+
+    pub fn clear_planned_sessions(&mut self) {
+        diesel::delete(planned_sessions::table)
+            .execute(&self.0)
+            .unwrap();
+    }
+
+    pub fn get_planned_sessions(&self) -> Vec<PlannedSession> {
+        planned_sessions::table
+            .order(planned_sessions::start)
+            .load::<PlannedSession>(&self.0)
+            .unwrap()
+    }
+}
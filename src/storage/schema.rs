@@ -7,6 +7,74 @@ table! {
     }
 }
 
+table! {
+    event_attrs (attr_id) {
+        attr_id -> Integer,
+        id -> Integer,
+        key -> Text,
+        val -> Text,
+    }
+}
+
+table! {
+    deadlines (id) {
+        id -> Integer,
+        due -> Timestamp,
+    }
+}
+
+table! {
+    dependencies (dependency_id) {
+        dependency_id -> Integer,
+        id -> Integer,
+        depends_on -> Integer,
+        lag -> BigInt,
+    }
+}
+
+table! {
+    estimates (id) {
+        id -> Integer,
+        length -> BigInt,
+    }
+}
+
+table! {
+    events (id) {
+        id -> Integer,
+        kind -> Text,
+        name -> Text,
+        priority -> Integer,
+        finished -> Bool,
+        parent -> Nullable<Integer>,
+        created -> Timestamp,
+    }
+}
+
+table! {
+    focus_sessions (id) {
+        id -> Integer,
+        work -> BigInt,
+        short_break -> BigInt,
+        long_break -> BigInt,
+        long_break_every -> Integer,
+        max_idle_blocks -> Nullable<Integer>,
+        phase -> Text,
+        phase_start -> Timestamp,
+        blocks -> Integer,
+        interruptions -> Integer,
+    }
+}
+
+table! {
+    intervals (interval_id) {
+        interval_id -> Integer,
+        id -> Integer,
+        start -> Timestamp,
+        length -> BigInt,
+    }
+}
+
 table! {
     logs (id) {
         id -> Integer,
@@ -16,4 +84,78 @@ table! {
     }
 }
 
-allow_tables_to_appear_in_same_query!(attrs, logs,);
+table! {
+    planned_sessions (session_id) {
+        session_id -> Integer,
+        id -> Integer,
+        start -> Timestamp,
+        length -> BigInt,
+        reason -> Text,
+    }
+}
+
+table! {
+    recurrence_exceptions (exception_id) {
+        exception_id -> Integer,
+        id -> Integer,
+        original -> Timestamp,
+        start -> Nullable<Timestamp>,
+        length -> Nullable<BigInt>,
+    }
+}
+
+table! {
+    recurrences (id) {
+        id -> Integer,
+        rule -> Text,
+    }
+}
+
+table! {
+    sync_items (id) {
+        id -> Integer,
+        href -> Text,
+        etag -> Nullable<Text>,
+        data -> Text,
+    }
+}
+
+table! {
+    timers (id) {
+        id -> Integer,
+        start -> Timestamp,
+    }
+}
+
+table! {
+    transition_checks (id) {
+        id -> Integer,
+        time -> Timestamp,
+    }
+}
+
+table! {
+    uids (id) {
+        id -> Integer,
+        uid -> Text,
+    }
+}
+
+allow_tables_to_appear_in_same_query!(
+    attrs,
+    deadlines,
+    dependencies,
+    estimates,
+    event_attrs,
+    events,
+    focus_sessions,
+    intervals,
+    logs,
+    planned_sessions,
+    recurrence_exceptions,
+    recurrences,
+    sync_items,
+    timers,
+    transition_checks,
+    uids,
+);
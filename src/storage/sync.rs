@@ -0,0 +1,26 @@
+use diesel::prelude::*;
+
+use super::model::*;
+use super::schema::sync_items;
+use super::LogStorage;
+
+impl LogStorage {
+    /// Records the state of the item `id` after a sync, replacing the earlier one
+    pub fn set_sync_item(&mut self, item: &SyncItem) {
+        self.clear_sync_item(item.id);
+        diesel::insert_into(sync_items::table)
+            .values(item)
+            .execute(&self.0)
+            .unwrap();
+    }
+
+    pub fn clear_sync_item(&mut self, id: i32) {
+        diesel::delete(sync_items::table.find(id))
+            .execute(&self.0)
+            .unwrap();
+    }
+
+    pub fn get_sync_items(&self) -> Vec<SyncItem> {
+        sync_items::table.load::<SyncItem>(&self.0).unwrap()
+    }
+}
@@ -0,0 +1,25 @@
+use chrono::naive::NaiveDateTime;
+use diesel::prelude::*;
+
+use super::model::*;
+use super::schema::timers;
+use super::LogStorage;
+
+impl LogStorage {
+    /// Starts the timer of the task `id`, replacing whichever timer was running
+    pub fn set_timer(&mut self, id: i32, start: NaiveDateTime) {
+        self.clear_timer();
+        diesel::insert_into(timers::table)
+            .values(&Timer { id, start })
+            .execute(&self.0)
+            .unwrap();
+    }
+
+    pub fn clear_timer(&mut self) {
+        diesel::delete(timers::table).execute(&self.0).unwrap();
+    }
+
+    pub fn get_timer(&self) -> Option<Timer> {
+        timers::table.first::<Timer>(&self.0).optional().unwrap()
+    }
+}